@@ -0,0 +1,229 @@
+use std::{
+  fs,
+  io::{BufRead, BufReader, Read, Write},
+  path::Path,
+  thread,
+  time::Duration,
+};
+
+use anyhow::Error;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+///Steps the server plays in order, afterwards it only answers `shutdown` and `exit`
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Scenario {
+  pub steps: Vec<Step>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Step {
+  ///Waits for the next message of the client, which has to be `method`
+  Expect {
+    method: String,
+    ///Has to be contained in the params of the message if set
+    #[serde(default)]
+    params: Option<Value>,
+    ///Sent for requests, `null` if not set
+    #[serde(default)]
+    reply: Option<Reply>,
+    ///Milliseconds to wait before replying
+    #[serde(default)]
+    delay: u64,
+  },
+  ///Sends a notification to the client
+  Notify {
+    method: String,
+    #[serde(default)]
+    params: Value,
+  },
+  ///Sends a request to the client and waits for the response
+  Request {
+    method: String,
+    #[serde(default)]
+    params: Value,
+    ///Has to be contained in the result of the response if set
+    #[serde(default)]
+    result: Option<Value>,
+  },
+  ///Milliseconds to wait before the next step
+  Sleep(u64),
+  ///Closes the connection without a shutdown
+  Crash,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Reply {
+  Result(Value),
+  Error { code: i64, message: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Outcome {
+  ///Every message sent by the client in the order they arrived
+  pub received: Vec<Value>,
+  pub crashed: bool,
+}
+
+impl Scenario {
+  pub fn load(path: &Path) -> Result<Self, Error> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+  }
+}
+
+///Plays the scenario on one connection, fails as soon as the client deviates from it
+pub fn serve(
+  scenario: &Scenario,
+  reader: impl Read,
+  mut writer: impl Write,
+) -> Result<Outcome, Error> {
+  let mut reader = BufReader::new(reader);
+  let mut outcome = Outcome::default();
+  let mut next_id = 0;
+
+  for step in &scenario.steps {
+    match step {
+      Step::Expect {
+        method,
+        params,
+        reply,
+        delay,
+      } => {
+        let msg = read_msg(&mut reader)?
+          .ok_or_else(|| anyhow::anyhow!("Connection closed while expecting {}", method))?;
+        outcome.received.push(msg.clone());
+
+        if msg["method"] != *method {
+          return Err(anyhow::anyhow!("Expected {} but got {}", method, msg));
+        }
+        if let Some(params) = params {
+          if !contains(&msg["params"], params) {
+            return Err(anyhow::anyhow!("Unexpected params for {}: {}", method, msg));
+          }
+        }
+
+        if let Some(id) = msg.get("id") {
+          thread::sleep(Duration::from_millis(*delay));
+          let response = match reply {
+            Some(Reply::Result(result)) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Some(Reply::Error { code, message }) => json!({
+              "jsonrpc": "2.0",
+              "id": id,
+              "error": { "code": code, "message": message },
+            }),
+            None => json!({ "jsonrpc": "2.0", "id": id, "result": null }),
+          };
+          write_msg(&mut writer, &response)?;
+        }
+      }
+      Step::Notify { method, params } => {
+        let not = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        write_msg(&mut writer, &not)?;
+      }
+      Step::Request {
+        method,
+        params,
+        result,
+      } => {
+        next_id += 1;
+        let id = format!("mock-{}", next_id);
+        let req = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        write_msg(&mut writer, &req)?;
+
+        let msg = read_msg(&mut reader)?
+          .ok_or_else(|| anyhow::anyhow!("Connection closed while waiting for {}", id))?;
+        outcome.received.push(msg.clone());
+
+        if msg["id"] != id || msg.get("method").is_some() {
+          return Err(anyhow::anyhow!(
+            "Expected response to {} but got {}",
+            id,
+            msg
+          ));
+        }
+        if let Some(result) = result {
+          if !contains(&msg["result"], result) {
+            return Err(anyhow::anyhow!("Unexpected result for {}: {}", method, msg));
+          }
+        }
+      }
+      Step::Sleep(millis) => thread::sleep(Duration::from_millis(*millis)),
+      Step::Crash => {
+        outcome.crashed = true;
+        return Ok(outcome);
+      }
+    }
+  }
+
+  while let Some(msg) = read_msg(&mut reader)? {
+    outcome.received.push(msg.clone());
+
+    let Some(id) = msg.get("id") else {
+      if msg["method"] == "exit" {
+        break;
+      }
+      continue;
+    };
+    let response = match msg["method"].as_str() {
+      Some("shutdown") => json!({ "jsonrpc": "2.0", "id": id, "result": null }),
+      Some(method) => json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": -32601, "message": format!("Unscripted method {}", method) },
+      }),
+      //a response to a request of the scenario that was not waited for
+      None => continue,
+    };
+    write_msg(&mut writer, &response)?;
+  }
+
+  Ok(outcome)
+}
+
+///Objects may have more keys than expected, everything else has to be equal
+fn contains(actual: &Value, expected: &Value) -> bool {
+  match (actual, expected) {
+    (Value::Object(actual), Value::Object(expected)) => expected.iter().all(|(key, value)| {
+      actual
+        .get(key)
+        .is_some_and(|actual| contains(actual, value))
+    }),
+    _ => actual == expected,
+  }
+}
+
+pub fn read_msg(reader: &mut impl BufRead) -> Result<Option<Value>, Error> {
+  let mut size = None;
+  let mut buf = String::new();
+  loop {
+    buf.clear();
+    if reader.read_line(&mut buf)? == 0 {
+      return Ok(None);
+    }
+
+    let line = buf.trim_end();
+    if line.is_empty() {
+      break;
+    }
+    if let Some((key, value)) = line.split_once(": ") {
+      if key.eq_ignore_ascii_case("Content-Length") {
+        size = Some(value.parse::<usize>()?);
+      }
+    }
+  }
+
+  let size = size.ok_or_else(|| anyhow::anyhow!("Missing Content-Length header"))?;
+  let mut body = vec![0; size];
+  reader.read_exact(&mut body)?;
+
+  Ok(Some(serde_json::from_slice(&body)?))
+}
+
+pub fn write_msg(writer: &mut impl Write, msg: &Value) -> Result<(), Error> {
+  let body = msg.to_string();
+  write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+  writer.flush()?;
+  Ok(())
+}
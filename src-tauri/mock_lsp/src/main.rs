@@ -0,0 +1,22 @@
+use std::{io, path::PathBuf, process};
+
+use anyhow::Error;
+use mock_lsp::{serve, Scenario};
+
+///Plays the scenario file given as first argument over stdin and stdout
+fn main() -> Result<(), Error> {
+  let path = std::env::args()
+    .nth(1)
+    .map(PathBuf::from)
+    .ok_or(anyhow::anyhow!("Usage: mock-lsp <scenario.json>"))?;
+  let scenario = Scenario::load(&path)?;
+  eprintln!("Playing {}", path.display());
+
+  let outcome = serve(&scenario, io::stdin().lock(), io::stdout().lock())?;
+  if outcome.crashed {
+    eprintln!("Crashing as scripted");
+    process::exit(1);
+  }
+
+  Ok(())
+}
@@ -0,0 +1,30 @@
+use std::process::{Command, Stdio};
+
+#[test]
+fn scripted_crash_exits_with_failure() {
+  let scenario = std::env::temp_dir().join(format!("mock-lsp-crash-{}.json", std::process::id()));
+  std::fs::write(&scenario, r#"{ "steps": ["crash"] }"#).unwrap();
+
+  let output = Command::new(env!("CARGO_BIN_EXE_mock-lsp"))
+    .arg(&scenario)
+    .stdin(Stdio::null())
+    .output()
+    .unwrap();
+  assert_eq!(output.status.code(), Some(1));
+  let stderr = String::from_utf8(output.stderr).unwrap();
+  assert!(stderr.contains("Playing"));
+  assert!(stderr.contains("Crashing as scripted"));
+  std::fs::remove_file(&scenario).unwrap();
+}
+
+#[test]
+fn missing_scenario_is_reported() {
+  let output = Command::new(env!("CARGO_BIN_EXE_mock-lsp"))
+    .stdin(Stdio::null())
+    .output()
+    .unwrap();
+  assert!(!output.status.success());
+  assert!(String::from_utf8(output.stderr)
+    .unwrap()
+    .contains("Usage: mock-lsp <scenario.json>"));
+}
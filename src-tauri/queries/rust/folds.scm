@@ -0,0 +1,26 @@
+[
+  (mod_item)
+  (foreign_mod_item)
+  (function_item)
+  (struct_item)
+  (trait_item)
+  (enum_item)
+  (impl_item)
+  (type_item)
+  (union_item)
+  (const_item)
+  (let_declaration)
+  (loop_expression)
+  (for_expression)
+  (while_expression)
+  (if_expression)
+  (match_expression)
+  (match_arm)
+  (call_expression)
+  (closure_expression)
+  (macro_definition)
+  (macro_invocation)
+  (attribute_item)
+  (use_declaration)
+  (block_comment)
+] @fold
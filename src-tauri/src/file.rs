@@ -1,3 +1,8 @@
 pub(crate) mod parser;
+pub(crate) mod folding;
+pub(crate) mod indentation;
+pub(crate) mod linked_editing;
 pub(crate) mod manager;
-pub(crate) mod token;
\ No newline at end of file
+pub(crate) mod markdown;
+pub(crate) mod token;
+pub(crate) mod workspace_edit;
\ No newline at end of file
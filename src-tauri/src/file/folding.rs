@@ -0,0 +1,179 @@
+use std::{cmp::Reverse, collections::HashMap, path::PathBuf};
+
+use anyhow::Error;
+use lsp_types::{FoldingRange, TextEdit};
+use serde::Serialize;
+
+///Region the editor can fold, `collapsed` is set if the user folded it
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct FoldRegion {
+  #[serde(flatten)]
+  pub(crate) range: FoldingRange,
+  pub(crate) collapsed: bool,
+}
+
+///Start lines of the collapsed regions of every file, saved to disk so they survive restarts
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FoldState {
+  ///Nothing is saved without a file, like in tests
+  file: Option<PathBuf>,
+  collapsed: HashMap<String, Vec<u32>>,
+}
+
+impl FoldState {
+  ///Reads the state saved in `file`, a missing or broken file means nothing is collapsed
+  pub(crate) fn load(file: PathBuf) -> Self {
+    let collapsed = std::fs::read_to_string(&file)
+      .ok()
+      .and_then(|content| serde_json::from_str(&content).ok())
+      .unwrap_or_default();
+
+    FoldState {
+      file: Some(file),
+      collapsed,
+    }
+  }
+
+  pub(crate) fn is_collapsed(&self, path: &str, line: u32) -> bool {
+    self
+      .collapsed
+      .get(path)
+      .is_some_and(|lines| lines.contains(&line))
+  }
+
+  pub(crate) fn set_collapsed(
+    &mut self,
+    path: &str,
+    line: u32,
+    collapsed: bool,
+  ) -> Result<(), Error> {
+    let lines = self.collapsed.entry(path.to_string()).or_default();
+    lines.retain(|other| *other != line);
+    if collapsed {
+      lines.push(line);
+      lines.sort();
+    }
+    if lines.is_empty() {
+      self.collapsed.remove(path);
+    }
+
+    self.save()
+  }
+
+  ///Moves the collapsed regions with the lines they start at. `edits` are the edits applied to
+  ///the file from the back to the front, regions starting in a replaced line are dropped.
+  pub(crate) fn shift(&mut self, path: &str, edits: &[TextEdit]) {
+    let Some(lines) = self.collapsed.get_mut(path) else {
+      return;
+    };
+
+    for edit in edits {
+      let (start, end) = (edit.range.start, edit.range.end);
+      let added = edit.new_text.matches('\n').count() as i64;
+      let removed = (end.line - start.line) as i64;
+      //text inserted at the start of a line pushes it down
+      let pushed = start == end && end.character == 0;
+
+      lines.retain(|line| *line <= start.line || *line > end.line || (pushed && *line == end.line));
+      for line in lines.iter_mut() {
+        if *line > end.line || (pushed && *line == end.line) {
+          *line = (*line as i64 + added - removed) as u32;
+        }
+      }
+    }
+  }
+
+  pub(crate) fn rename(&mut self, path: &str, new_path: &str) {
+    if let Some(lines) = self.collapsed.remove(path) {
+      self.collapsed.insert(new_path.to_string(), lines);
+    }
+  }
+
+  pub(crate) fn save(&self) -> Result<(), Error> {
+    let Some(file) = &self.file else {
+      return Ok(());
+    };
+
+    if let Some(dir) = file.parent() {
+      std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(file, serde_json::to_string(&self.collapsed)?)?;
+    Ok(())
+  }
+}
+
+///Ranges of the server with the ones of tree-sitter starting at other lines, outer ranges first
+pub(crate) fn merge(server: Vec<FoldingRange>, syntax: Vec<FoldingRange>) -> Vec<FoldingRange> {
+  let mut ranges: Vec<FoldingRange> = Vec::new();
+  for range in server.into_iter().chain(syntax) {
+    let folds = range.end_line > range.start_line;
+    if folds
+      && !ranges
+        .iter()
+        .any(|other| other.start_line == range.start_line)
+    {
+      ranges.push(range);
+    }
+  }
+
+  ranges.sort_by_key(|range| (range.start_line, Reverse(range.end_line)));
+  ranges
+}
+
+#[cfg(test)]
+mod tests {
+  use lsp_types::{FoldingRange, Position, Range, TextEdit};
+
+  use super::{merge, FoldState};
+
+  fn range(start_line: u32, end_line: u32) -> FoldingRange {
+    FoldingRange {
+      start_line,
+      end_line,
+      ..Default::default()
+    }
+  }
+
+  fn edit(start: (u32, u32), end: (u32, u32), text: &str) -> TextEdit {
+    let range = Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1));
+    TextEdit::new(range, text.into())
+  }
+
+  #[test]
+  fn collapsed_regions_move_with_their_lines() {
+    let mut state = FoldState::default();
+    for line in [2, 5, 9] {
+      state.set_collapsed("a.rs", line, true).unwrap();
+    }
+
+    //edits from the back to the front: a line broken before line 9, lines 4 and 5 joined and
+    //a line inserted at the start of line 2
+    state.shift(
+      "a.rs",
+      &[
+        edit((8, 3), (8, 3), "\n"),
+        edit((4, 1), (5, 2), "b"),
+        edit((2, 0), (2, 0), "//\n"),
+      ],
+    );
+    assert!(state.is_collapsed("a.rs", 3));
+    assert!(!state.is_collapsed("a.rs", 5));
+    assert!(state.is_collapsed("a.rs", 10));
+
+    state.rename("a.rs", "b.rs");
+    assert!(!state.is_collapsed("a.rs", 3));
+    assert!(state.is_collapsed("b.rs", 3));
+  }
+
+  #[test]
+  fn server_ranges_win_at_the_same_start_line() {
+    let server = vec![range(4, 8), range(1, 1)];
+    let syntax = vec![range(0, 2), range(4, 9), range(6, 7)];
+
+    let lines: Vec<(u32, u32)> = merge(server, syntax)
+      .iter()
+      .map(|range| (range.start_line, range.end_line))
+      .collect();
+    assert_eq!(lines, vec![(0, 2), (4, 8), (6, 7)]);
+  }
+}
@@ -0,0 +1,81 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use lsp_types::FormattingOptions;
+
+///How the lines of a file are indented
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Indentation {
+  pub(crate) tabs: bool,
+  ///Spaces per level, also the width of a tab
+  pub(crate) size: u32,
+}
+
+impl Indentation {
+  ///Guesses the indentation from the steps between indented lines, 4 spaces if none are
+  pub(crate) fn detect<'a>(lines: impl Iterator<Item = &'a str>) -> Self {
+    let (mut tabs, mut spaces) = (0, 0);
+    let mut steps: HashMap<usize, usize> = HashMap::new();
+    let mut previous = 0;
+
+    for line in lines.filter(|line| !line.trim().is_empty()) {
+      let indent = &line[..line.len() - line.trim_start().len()];
+      if indent.starts_with('\t') {
+        tabs += 1;
+        continue;
+      }
+
+      let width = indent.len();
+      if width > 0 {
+        spaces += 1;
+      }
+      if width > previous {
+        *steps.entry(width - previous).or_default() += 1;
+      }
+      previous = width;
+    }
+
+    //the most common step wins, smaller ones on a tie
+    let size = steps
+      .into_iter()
+      .max_by_key(|(step, count)| (*count, Reverse(*step)))
+      .map_or(4, |(step, _)| step as u32);
+    Indentation {
+      tabs: tabs > spaces,
+      size,
+    }
+  }
+}
+
+impl From<Indentation> for FormattingOptions {
+  fn from(indentation: Indentation) -> Self {
+    FormattingOptions {
+      tab_size: indentation.size,
+      insert_spaces: !indentation.tabs,
+      properties: HashMap::new(),
+      trim_trailing_whitespace: Some(true),
+      //lines are always kept with their line ending
+      insert_final_newline: Some(true),
+      trim_final_newlines: Some(true),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Indentation;
+
+  #[test]
+  fn indentation_is_detected_from_the_steps_between_lines() {
+    let two = Indentation::detect(["fn main() {", "  if a {", "    b();", "  }", "}"].into_iter());
+    assert!(!two.tabs);
+    assert_eq!(two.size, 2);
+
+    let tabs = Indentation::detect(["fn main() {", "\tif a {", "\t\tb();", "\t}"].into_iter());
+    assert!(tabs.tabs);
+
+    //blank lines do not count as steps back to the start of the line
+    let blank = Indentation::detect(["a", "   b", "", "   c", "      d"].into_iter());
+    assert_eq!(blank.size, 3);
+    assert_eq!(Indentation::detect(["a", "b"].into_iter()).size, 4);
+  }
+}
@@ -0,0 +1,90 @@
+use lsp_types::{LinkedEditingRanges, Position, PositionEncodingKind, Range, TextEdit};
+use regex::Regex;
+
+use crate::lsp::encoding::byte_offset;
+
+///The edit with a copy at the same offset in every other linked range. `line` is the content of
+///the edited line, the columns are counted in `encoding`. Edits outside of the ranges, or leaving
+///a word that no longer matches the pattern of the server, are not mirrored.
+pub(crate) fn mirror(
+  edit: TextEdit,
+  linked: &LinkedEditingRanges,
+  line: &str,
+  encoding: Option<&PositionEncodingKind>,
+) -> Vec<TextEdit> {
+  let (start, end) = (edit.range.start, edit.range.end);
+  let Some(edited) = linked
+    .ranges
+    .iter()
+    .find(|range| single_line(range) && range.start <= start && end <= range.end)
+  else {
+    return vec![edit];
+  };
+
+  let bytes = [edited.start, start, end, edited.end]
+    .map(|position| byte_offset(line, position.character, encoding));
+  let [Some(word_start), Some(from), Some(to), Some(word_end)] = bytes else {
+    return vec![edit];
+  };
+  let new_word = format!(
+    "{}{}{}",
+    &line[word_start..from],
+    edit.new_text,
+    &line[to..word_end]
+  );
+  //an invalid pattern does not stop the edit from being mirrored
+  let pattern = linked
+    .word_pattern
+    .as_ref()
+    .and_then(|pattern| Regex::new(&format!("^(?:{})$", pattern)).ok());
+  if pattern.is_some_and(|pattern| !pattern.is_match(&new_word)) {
+    return vec![edit];
+  }
+
+  let mirrored = linked
+    .ranges
+    .iter()
+    .filter(|range| *range != edited && single_line(range))
+    .map(|range| {
+      //every linked range holds the same word, so the edit is at the same offset in it
+      let line = range.start.line;
+      let character = range.start.character;
+      TextEdit::new(
+        Range::new(
+          Position::new(line, character + start.character - edited.start.character),
+          Position::new(line, character + end.character - edited.start.character),
+        ),
+        edit.new_text.clone(),
+      )
+    })
+    .collect::<Vec<TextEdit>>();
+  [vec![edit], mirrored].concat()
+}
+
+fn single_line(range: &Range) -> bool {
+  range.start.line == range.end.line
+}
+
+#[cfg(test)]
+mod tests {
+  use lsp_types::{LinkedEditingRanges, Position, Range, TextEdit};
+
+  use super::mirror;
+
+  #[test]
+  fn words_with_multi_byte_characters_are_mirrored() {
+    let range = |start: u32, end: u32| Range::new(Position::new(0, start), Position::new(0, end));
+    //`é` is one UTF-16 code unit but two bytes
+    let linked = LinkedEditingRanges {
+      ranges: vec![range(1, 2), range(5, 6)],
+      word_pattern: Some("\\w+".to_string()),
+    };
+
+    let edit = TextEdit::new(range(2, 2), "t".to_string());
+    let edits = mirror(edit.clone(), &linked, "<é></é>", None);
+    assert_eq!(
+      edits,
+      vec![edit, TextEdit::new(range(6, 6), "t".to_string())]
+    );
+  }
+}
@@ -1,74 +1,309 @@
+use std::{cmp::Reverse, path::PathBuf, sync::Arc};
+
 use anyhow::Error;
-use tree_sitter_highlight::{HighlightConfiguration, HighlightEvent, Highlighter};
+use lsp_types::{Position, PositionEncodingKind, Range, TextEdit};
+use tree_sitter_highlight::{HighlightConfiguration, Highlighter};
 
-use crate::lsp::manager::LSPManager;
+use crate::lsp::{
+  encoding::{byte_offset, encoded_len},
+  manager::LSPManager,
+};
 
 use super::{
-  parser::{ParsersManager, HIGHLIGHTING_NAMES},
-  token::{Token, TokenTree},
+  folding::{merge, FoldRegion, FoldState},
+  indentation::Indentation,
+  linked_editing::mirror,
+  parser::{highlight, ParsersManager},
+  token::TokenTree,
 };
 
 pub(crate) struct FileManager {
   open_files: Vec<File>,
+  folds: FoldState,
+}
+
+///Change of an open file the language server was not told about yet
+pub(crate) struct PendingChange {
+  path: String,
+  version: i32,
+  edits: Vec<TextEdit>,
+  text: String,
 }
 
 pub(crate) struct File {
   path: String,
   content: Vec<String>,
-  highlighter: Option<(Highlighter, HighlightConfiguration)>,
+  highlighter: Option<(Highlighter, Arc<HighlightConfiguration>)>,
+  ///Incremented with every edit, sent to the language server with the changes
+  version: i32,
+  ///Lines are kept with `\r\n`, this is what the file on disk uses
+  line_ending: &'static str,
+  ///Edits reverting each change, the last one is undone first
+  undo: Vec<Vec<TextEdit>>,
+  redo: Vec<Vec<TextEdit>>,
 }
 
 impl FileManager {
   pub(crate) fn new() -> Self {
     FileManager {
       open_files: Vec::new(),
+      folds: FoldState::default(),
     }
   }
 
+  ///Restores the collapsed regions saved in `file` and saves them there from now on
+  pub(crate) fn load_folds(&mut self, file: PathBuf) {
+    self.folds = FoldState::load(file);
+  }
+
   pub(crate) fn open_file(
     &mut self,
     path: &str,
     parser_manager: &ParsersManager,
+    lsp_manager: &LSPManager,
   ) -> Result<(), Error> {
-    let highlighter = match parser_manager.get_language(path) {
-      Some((language, highlights_query, injection_query, locals_query)) => {
-        match HighlightConfiguration::new(
-          language.clone(),
-          "name",
-          highlights_query,
-          injection_query,
-          locals_query,
-        ) {
-          Ok(mut config) => {
-            config.configure(&HIGHLIGHTING_NAMES);
-            Some((Highlighter::new(), config))
-          }
-          Err(err) => {
-            eprintln!("Error creating highlight configuration: {}", err);
-            None
-          }
-        }
-      }
-      None => None,
+    if self.get_file(path).is_some() {
+      return Ok(());
+    }
+
+    let mut file = File::read(path)?;
+    file.set_highlighter(parser_manager);
+
+    lsp_manager.open_document(path, 0, file.content.join(""))?;
+    self.open_files.push(file);
+    Ok(())
+  }
+
+  pub(crate) fn close_file(&mut self, path: &str, lsp_manager: &LSPManager) -> Result<(), Error> {
+    self.open_files.retain(|file| file.path != path);
+    lsp_manager.close_document(path)
+  }
+
+  ///Closes the file and returns it, so it can be reopened with `restore_file`
+  pub(crate) fn take_file(
+    &mut self,
+    path: &str,
+    lsp_manager: &LSPManager,
+  ) -> Result<Option<File>, Error> {
+    let Some(index) = self.open_files.iter().position(|file| file.path == path) else {
+      return Ok(None);
     };
 
-    //read an add \r\n to each line
-    let content = std::fs::read_to_string(path)?
-      .lines()
-      .map(|s| s.to_string())
-      .map(|s| s + "\r\n")
-      .collect();
+    let file = self.open_files.remove(index);
+    lsp_manager.close_document(path)?;
+    Ok(Some(file))
+  }
+
+  ///Reopens a file returned by `take_file` with its unsaved content
+  pub(crate) fn restore_file(&mut self, file: File, lsp_manager: &LSPManager) -> Result<(), Error> {
+    lsp_manager.open_document(&file.path, file.version, file.content.join(""))?;
+    self.open_files.push(file);
+    Ok(())
+  }
+
+  ///Moves an open file to `new_path`, the server sees it as closed and opened again
+  pub(crate) fn rename_file(
+    &mut self,
+    path: &str,
+    new_path: &str,
+    parser_manager: &ParsersManager,
+    lsp_manager: &LSPManager,
+  ) -> Result<(), Error> {
+    let Some(mut file) = self.take_file(path, lsp_manager)? else {
+      return Ok(());
+    };
+
+    file.path = new_path.to_string();
+    file.set_highlighter(parser_manager);
+    self.folds.rename(path, new_path);
+    self.restore_file(file, lsp_manager)
+  }
+
+  ///Paths of the open files, in the order they were opened
+  pub(crate) fn open_paths(&self) -> Vec<String> {
+    self
+      .open_files
+      .iter()
+      .map(|file| file.path.clone())
+      .collect()
+  }
+
+  pub(crate) fn save_file(&self, path: &str, lsp_manager: &LSPManager) -> Result<(), Error> {
+    let file = self
+      .get_file(path)
+      .ok_or(anyhow::anyhow!("File {} is not open", path))?;
+
+    std::fs::write(path, file.text())?;
+    //collapsed regions moved by edits are saved along with the content
+    if let Err(err) = self.folds.save() {
+      eprintln!("Error saving fold state: {}", err);
+    }
+
+    lsp_manager.save_document(path)
+  }
+
+  ///Applies edits that all refer to the current content and sends them to the language server
+  pub(crate) fn edit_file(
+    &mut self,
+    path: &str,
+    edits: Vec<TextEdit>,
+    lsp_manager: &LSPManager,
+  ) -> Result<(), Error> {
+    let change = self.change_file(path, edits, lsp_manager)?;
+    change.send(lsp_manager)
+  }
+
+  ///Applies the edits like `edit_file`, but the server only sees them once the change is sent.
+  ///Until then `discard_change` can revert it without a trace.
+  pub(crate) fn change_file(
+    &mut self,
+    path: &str,
+    edits: Vec<TextEdit>,
+    lsp_manager: &LSPManager,
+  ) -> Result<PendingChange, Error> {
+    let file = self
+      .get_file_mut(path)
+      .ok_or(anyhow::anyhow!("File {} is not open", path))?;
 
-    self.open_files.push(File {
+    let encoding = lsp_manager.position_encoding(path);
+    let edits = file.apply_edits(edits, encoding.as_ref())?;
+    let (version, text) = (file.version, file.content.join(""));
+    self.folds.shift(path, &edits);
+    Ok(PendingChange {
       path: path.to_string(),
-      content,
-      highlighter,
-    });
+      version,
+      edits,
+      text,
+    })
+  }
+
+  ///Reverts the last change of the file, which was not sent to the server, as if it was never
+  ///applied. `redo` are the changes that could be redone before it.
+  pub(crate) fn discard_change(
+    &mut self,
+    path: &str,
+    redo: Vec<Vec<TextEdit>>,
+    lsp_manager: &LSPManager,
+  ) -> Result<(), Error> {
+    let file = self
+      .get_file_mut(path)
+      .ok_or(anyhow::anyhow!("File {} is not open", path))?;
+
+    let encoding = lsp_manager.position_encoding(path);
+    let edits = file.discard_change(redo, encoding.as_ref())?;
+    self.folds.shift(path, &edits);
     Ok(())
   }
 
-  pub(crate) fn close_file(&mut self, path: &str) {
-    self.open_files.retain(|file| file.path != path);
+  ///Applies `edit` and mirrors it into the ranges linked to the edited one, like the closing tag
+  ///of a renamed HTML element. Returns all applied edits, as one change that can be undone.
+  pub(crate) async fn edit_linked(
+    &mut self,
+    path: &str,
+    edit: TextEdit,
+    lsp_manager: &LSPManager,
+  ) -> Result<Vec<TextEdit>, Error> {
+    let linked = lsp_manager
+      .linked_editing_ranges(path, edit.range.start)
+      .await?;
+    let file = self
+      .get_file(path)
+      .ok_or(anyhow::anyhow!("File {} is not open", path))?;
+
+    let edits = match (linked, file.line(edit.range.start.line)) {
+      (Some(linked), Some(line)) => {
+        let encoding = lsp_manager.position_encoding(path);
+        mirror(edit, &linked, line, encoding.as_ref())
+      }
+      _ => vec![edit],
+    };
+    self.edit_file(path, edits.clone(), lsp_manager)?;
+    Ok(edits)
+  }
+
+  ///Reverts the last change of the file, false if there was nothing to undo
+  pub(crate) fn undo_file(&mut self, path: &str, lsp_manager: &LSPManager) -> Result<bool, Error> {
+    let file = self
+      .get_file_mut(path)
+      .ok_or(anyhow::anyhow!("File {} is not open", path))?;
+
+    let encoding = lsp_manager.position_encoding(path);
+    let Some(edits) = file.undo(encoding.as_ref())? else {
+      return Ok(false);
+    };
+    let (version, text) = (file.version, file.content.join(""));
+    self.folds.shift(path, &edits);
+    lsp_manager.change_document(path, version, edits, text)?;
+    Ok(true)
+  }
+
+  ///Applies the last undone change again, false if there was nothing to redo
+  pub(crate) fn redo_file(&mut self, path: &str, lsp_manager: &LSPManager) -> Result<bool, Error> {
+    let file = self
+      .get_file_mut(path)
+      .ok_or(anyhow::anyhow!("File {} is not open", path))?;
+
+    let encoding = lsp_manager.position_encoding(path);
+    let Some(edits) = file.redo(encoding.as_ref())? else {
+      return Ok(false);
+    };
+    let (version, text) = (file.version, file.content.join(""));
+    self.folds.shift(path, &edits);
+    lsp_manager.change_document(path, version, edits, text)?;
+    Ok(true)
+  }
+
+  ///Replaces the whole content of an open file with `text`
+  pub(crate) fn set_text(
+    &mut self,
+    path: &str,
+    text: String,
+    lsp_manager: &LSPManager,
+  ) -> Result<(), Error> {
+    let file = self
+      .get_file(path)
+      .ok_or(anyhow::anyhow!("File {} is not open", path))?;
+
+    let edit = TextEdit::new(file.range(), text);
+    self.edit_file(path, vec![edit], lsp_manager)
+  }
+
+  ///Regions of the server merged with the ones of tree-sitter, either may be missing
+  pub(crate) async fn folding_ranges(
+    &mut self,
+    path: &str,
+    parser_manager: &ParsersManager,
+    lsp_manager: &LSPManager,
+  ) -> Result<Vec<FoldRegion>, Error> {
+    let file = self
+      .get_file(path)
+      .ok_or(anyhow::anyhow!("File {} is not open", path))?;
+
+    let syntax = parser_manager
+      .folds(path, &file.content.join(""))
+      .unwrap_or_default();
+    let server = match lsp_manager.folding_ranges(path).await {
+      Ok(ranges) => ranges,
+      Err(err) => {
+        eprintln!("Error requesting folding ranges for {}: {}", path, err);
+        Vec::new()
+      }
+    };
+
+    Ok(
+      merge(server, syntax)
+        .into_iter()
+        .map(|range| FoldRegion {
+          collapsed: self.folds.is_collapsed(path, range.start_line),
+          range,
+        })
+        .collect(),
+    )
+  }
+
+  ///Folds or unfolds the region starting at `line`
+  pub(crate) fn set_folded(&mut self, path: &str, line: u32, collapsed: bool) -> Result<(), Error> {
+    self.folds.set_collapsed(path, line, collapsed)
   }
 
   pub(crate) fn get_file(&self, path: &str) -> Option<&File> {
@@ -83,39 +318,7 @@ impl FileManager {
     let file = self.get_file_mut(path)?;
     let content = file.content.join("");
     let (highlighter, config) = file.highlighter.as_mut()?;
-    let highlights = highlighter
-      .highlight(config, content.as_bytes(), None, |_| None)
-      .ok()?;
-
-    let mut tokens = Vec::new();
-    let mut current_token_types: Vec<String> = Vec::new();
-    for event in highlights {
-      match event.unwrap() {
-        HighlightEvent::Source { start, end } => {
-          if !current_token_types.is_empty() {
-            tokens.push(Token {
-              start: 0,
-              token: content[start..end].to_string(),
-              type_: current_token_types.last().unwrap().to_string(),
-              modifiers: None,
-            });
-          } else {
-            tokens.push(Token {
-              start: 0,
-              token: content[start..end].to_string(),
-              type_: "".to_string(),
-              modifiers: None,
-            });
-          }
-        }
-        HighlightEvent::HighlightStart(s) => {
-          current_token_types.push(HIGHLIGHTING_NAMES[s.0].to_string());
-        }
-        HighlightEvent::HighlightEnd => {
-          current_token_types.pop();
-        }
-      }
-    }
+    let tokens = highlight(highlighter, config, &content)?;
 
     let mut token_tree = TokenTree::new();
     token_tree.set_raw_tokens(tokens);
@@ -123,23 +326,403 @@ impl FileManager {
     Some(token_tree)
   }
 
+  ///Highlighting with the semantic tokens and inlay hints of `viewport`, or of the whole file.
+  ///The occurrences of the symbol at `cursor` are marked in the modifiers of their tokens.
   pub(crate) async fn get_semantic_highlighting(
     &mut self,
     path: &str,
     lsp_manager: &LSPManager,
+    viewport: Option<Range>,
+    cursor: Option<Position>,
   ) -> Option<TokenTree> {
     let mut syntax_tree = self.get_highlighting(path)?;
-    let file = self.get_file_mut(path)?;
+    let file = self.get_file(path)?;
 
-    let mut semantic_tree = TokenTree::new();
-    semantic_tree.set_tokens(
-      lsp_manager
-        .get_semantic_tokens(path, &file.content)
-        .await?,
-    );
+    //without semantic tokens the syntax highlighting is kept
+    let tokens = lsp_manager
+      .get_semantic_tokens(path, &file.content, viewport)
+      .await;
+    if let Some(tokens) = tokens {
+      let mut semantic_tree = TokenTree::new();
+      semantic_tree.set_tokens(tokens);
+      syntax_tree.merge(semantic_tree);
+    }
 
-    syntax_tree.merge(semantic_tree);
+    if let Some(cursor) = cursor {
+      let encoding = lsp_manager.position_encoding(path);
+      match lsp_manager.document_highlights(path, cursor).await {
+        Ok(mut highlights) => {
+          for highlight in &mut highlights {
+            highlight.range = file.byte_range(highlight.range, encoding.as_ref());
+          }
+          syntax_tree.mark_occurrences(&highlights);
+        }
+        Err(err) => eprintln!("Error requesting document highlights for {}: {}", path, err),
+      }
+    }
 
+    self
+      .insert_inlay_hints(&mut syntax_tree, path, lsp_manager, viewport)
+      .await;
     Some(syntax_tree)
   }
+
+  ///Adds the inlay hints of `viewport`, or of the whole file, to the highlighting of `path`
+  pub(crate) async fn insert_inlay_hints(
+    &self,
+    tree: &mut TokenTree,
+    path: &str,
+    lsp_manager: &LSPManager,
+    viewport: Option<Range>,
+  ) {
+    let Some(file) = self.get_file(path) else {
+      return;
+    };
+    let range = viewport.unwrap_or(file.range());
+    //hints are placed in code units of the server, the tokens in bytes.
+    //A file without hints is still highlighted.
+    let encoding = lsp_manager.position_encoding(path);
+    match lsp_manager.inlay_hints(path, range).await {
+      Ok(hints) => tree.insert_virtual(
+        hints
+          .into_iter()
+          .map(|(line, mut token)| {
+            let position = Position::new(line, token.start);
+            token.start = file.byte_position(position, encoding.as_ref()).character;
+            (line, token)
+          })
+          .collect(),
+      ),
+      Err(err) => eprintln!("Error requesting inlay hints for {}: {}", path, err),
+    }
+  }
+}
+
+impl File {
+  ///Reads the file from disk, lines are kept with `\r\n`
+  pub(crate) fn read(path: &str) -> Result<Self, Error> {
+    let text = std::fs::read_to_string(path)?;
+    let line_ending = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let content: Vec<String> = text
+      .lines()
+      .map(|s| s.to_string())
+      .map(|s| s + "\r\n")
+      .collect();
+
+    Ok(File {
+      path: path.to_string(),
+      content,
+      highlighter: None,
+      version: 0,
+      line_ending,
+      undo: Vec::new(),
+      redo: Vec::new(),
+    })
+  }
+
+  pub(crate) fn version(&self) -> i32 {
+    self.version
+  }
+
+  ///Changes that were undone and can be applied again, the last one first
+  pub(crate) fn redo_stack(&self) -> Vec<Vec<TextEdit>> {
+    self.redo.clone()
+  }
+
+  pub(crate) fn indentation(&self) -> Indentation {
+    Indentation::detect(
+      self
+        .content
+        .iter()
+        .map(|line| line.trim_end_matches(['\r', '\n'])),
+    )
+  }
+
+  ///Range of the whole content
+  pub(crate) fn range(&self) -> Range {
+    Range::new(
+      Position::new(0, 0),
+      Position::new(self.content.len() as u32, 0),
+    )
+  }
+
+  ///Content with the line endings of the file on disk
+  pub(crate) fn text(&self) -> String {
+    self
+      .content
+      .iter()
+      .map(|line| line.trim_end_matches(['\r', '\n']).to_string() + self.line_ending)
+      .collect()
+  }
+
+  fn set_highlighter(&mut self, parser_manager: &ParsersManager) {
+    self.highlighter = parser_manager
+      .highlight_config(&self.path)
+      .map(|config| (Highlighter::new(), config));
+  }
+
+  ///Content of the line without its line ending
+  pub(crate) fn line(&self, line: u32) -> Option<&str> {
+    self
+      .content
+      .get(line as usize)
+      .map(|content| content.trim_end_matches(['\r', '\n']))
+  }
+
+  ///`range` with its characters counted in bytes of their lines, like the columns of the
+  ///tokens of the highlighting
+  pub(crate) fn byte_range(&self, range: Range, encoding: Option<&PositionEncodingKind>) -> Range {
+    Range::new(
+      self.byte_position(range.start, encoding),
+      self.byte_position(range.end, encoding),
+    )
+  }
+
+  ///`position` with its character counted in bytes of the line. Characters past the end of the
+  ///line mean the end of the line.
+  pub(crate) fn byte_position(
+    &self,
+    position: Position,
+    encoding: Option<&PositionEncodingKind>,
+  ) -> Position {
+    let line = self.line(position.line).unwrap_or_default();
+    let character = byte_offset(line, position.character, encoding).unwrap_or(line.len());
+    Position::new(position.line, character as u32)
+  }
+
+  ///Byte offset of `position`, whose character is counted in code units of `encoding`.
+  ///Characters past the end of the line mean the end of the line.
+  fn offset(&self, position: Position, encoding: Option<&PositionEncodingKind>) -> Option<usize> {
+    let line = position.line as usize;
+    let start: usize = self.content.iter().take(line).map(|line| line.len()).sum();
+    let Some(content) = self.content.get(line) else {
+      //the position right after the last line
+      return (line == self.content.len() && position.character == 0).then_some(start);
+    };
+
+    let content = content.trim_end_matches(['\r', '\n']);
+    if position.character >= encoded_len(content, encoding) {
+      return Some(start + content.len());
+    }
+    byte_offset(content, position.character, encoding).map(|character| start + character)
+  }
+
+  ///Applies the edits as one change that can be undone. Returns the edits in the order they were
+  ///applied, with the line endings used in the content.
+  pub(crate) fn apply_edits(
+    &mut self,
+    edits: Vec<TextEdit>,
+    encoding: Option<&PositionEncodingKind>,
+  ) -> Result<Vec<TextEdit>, Error> {
+    let (applied, revert) = self.replace(edits, encoding)?;
+    self.undo.push(revert);
+    self.redo.clear();
+    Ok(applied)
+  }
+
+  ///Reverts the last change without recording it and restores the version and the changes
+  ///that could be redone from before it. Returns the reverting edits.
+  fn discard_change(
+    &mut self,
+    redo: Vec<Vec<TextEdit>>,
+    encoding: Option<&PositionEncodingKind>,
+  ) -> Result<Vec<TextEdit>, Error> {
+    let edits = self
+      .undo
+      .pop()
+      .ok_or(anyhow::anyhow!("No change to discard in {}", self.path))?;
+    let version = self.version;
+    let (applied, _) = self.replace(edits, encoding)?;
+    self.version = version - 1;
+    self.redo = redo;
+    Ok(applied)
+  }
+
+  fn undo(
+    &mut self,
+    encoding: Option<&PositionEncodingKind>,
+  ) -> Result<Option<Vec<TextEdit>>, Error> {
+    let Some(edits) = self.undo.pop() else {
+      return Ok(None);
+    };
+    let (applied, revert) = self.replace(edits, encoding)?;
+    self.redo.push(revert);
+    Ok(Some(applied))
+  }
+
+  fn redo(
+    &mut self,
+    encoding: Option<&PositionEncodingKind>,
+  ) -> Result<Option<Vec<TextEdit>>, Error> {
+    let Some(edits) = self.redo.pop() else {
+      return Ok(None);
+    };
+    let (applied, revert) = self.replace(edits, encoding)?;
+    self.undo.push(revert);
+    Ok(Some(applied))
+  }
+
+  ///Position of a byte offset in the content, with the character in code units of `encoding`
+  fn position(&self, offset: usize, encoding: Option<&PositionEncodingKind>) -> Position {
+    let mut start = 0;
+    for (line, content) in self.content.iter().enumerate() {
+      if offset < start + content.len() {
+        let character = encoded_len(&content[..offset - start], encoding);
+        return Position::new(line as u32, character);
+      }
+      start += content.len();
+    }
+    Position::new(self.content.len() as u32, 0)
+  }
+
+  ///Returns the applied edits and the edits reverting them
+  fn replace(
+    &mut self,
+    mut edits: Vec<TextEdit>,
+    encoding: Option<&PositionEncodingKind>,
+  ) -> Result<(Vec<TextEdit>, Vec<TextEdit>), Error> {
+    //applying from the back keeps the positions of the remaining edits valid,
+    //inserts at the same position still end up in the given order
+    edits.reverse();
+    edits.sort_by_key(|edit| Reverse(edit.range.start));
+    let overlapping = edits
+      .windows(2)
+      .any(|pair| pair[1].range.end > pair[0].range.start);
+    if overlapping {
+      return Err(anyhow::anyhow!("Overlapping edits in {}", self.path));
+    }
+
+    let mut text = self.content.join("");
+    //offsets and replaced text of every edit, to revert them once the new content is known
+    let mut replaced = Vec::with_capacity(edits.len());
+    for edit in &mut edits {
+      let invalid = || anyhow::anyhow!("Invalid range {:?} in {}", edit.range, self.path);
+      let start = self
+        .offset(edit.range.start, encoding)
+        .ok_or_else(invalid)?;
+      let end = self.offset(edit.range.end, encoding).ok_or_else(invalid)?;
+      if start > end {
+        return Err(invalid());
+      }
+
+      edit.new_text = edit.new_text.replace("\r\n", "\n").replace('\n', "\r\n");
+      replaced.push((start, text[start..end].to_string(), edit.new_text.len()));
+      text.replace_range(start..end, &edit.new_text);
+    }
+
+    self.content = text.split_inclusive('\n').map(|s| s.to_string()).collect();
+    self.version += 1;
+
+    //edits before an edit move it by the difference in length of their text
+    let mut shift = 0;
+    let revert = replaced
+      .into_iter()
+      .rev()
+      .map(|(start, old_text, new_len)| {
+        let start = start.saturating_add_signed(shift);
+        shift += new_len as isize - old_text.len() as isize;
+        TextEdit::new(
+          Range::new(
+            self.position(start, encoding),
+            self.position(start + new_len, encoding),
+          ),
+          old_text,
+        )
+      })
+      .collect();
+    Ok((edits, revert))
+  }
+}
+
+impl PendingChange {
+  pub(crate) fn path(&self) -> &str {
+    &self.path
+  }
+
+  pub(crate) fn send(self, lsp_manager: &LSPManager) -> Result<(), Error> {
+    lsp_manager.change_document(&self.path, self.version, self.edits, self.text)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use lsp_types::{Position, PositionEncodingKind, Range, TextEdit};
+
+  use super::File;
+
+  fn read(name: &str, content: &str) -> File {
+    let path = std::env::temp_dir().join(format!("codeforge-{}-{}.rs", name, std::process::id()));
+    std::fs::write(&path, content).unwrap();
+    let file = File::read(&path.display().to_string()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    file
+  }
+
+  #[test]
+  fn ranges_are_converted_to_byte_columns() {
+    let path = std::env::temp_dir().join(format!("codeforge-columns-{}.rs", std::process::id()));
+    std::fs::write(&path, "let é = é;\n").unwrap();
+    let file = File::read(&path.display().to_string()).unwrap();
+
+    //`é` is one UTF-16 code unit but two bytes
+    let range = Range::new(Position::new(0, 8), Position::new(0, 9));
+    let bytes = Range::new(Position::new(0, 9), Position::new(0, 11));
+    assert_eq!(file.byte_range(range, None), bytes);
+    std::fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn edit_positions_are_counted_in_the_encoding() {
+    //`😀` is two UTF-16 code units, four bytes and one UTF-32 code unit
+    let mut file = read("encoded-edits", "a😀b\n");
+    let after =
+      |character: u32| Range::new(Position::new(0, character), Position::new(0, character));
+    let insert = |character: u32| vec![TextEdit::new(after(character), "c".into())];
+
+    file.apply_edits(insert(3), None).unwrap();
+    assert_eq!(file.line(0), Some("a😀cb"));
+    file
+      .apply_edits(insert(2), Some(&PositionEncodingKind::UTF32))
+      .unwrap();
+    assert_eq!(file.line(0), Some("a😀ccb"));
+    file
+      .apply_edits(insert(5), Some(&PositionEncodingKind::UTF8))
+      .unwrap();
+    assert_eq!(file.line(0), Some("a😀cccb"));
+
+    //the reverting edits are counted in the same encoding
+    let applied = file
+      .undo(Some(&PositionEncodingKind::UTF8))
+      .unwrap()
+      .unwrap();
+    assert_eq!(
+      applied[0].range,
+      Range::new(Position::new(0, 5), Position::new(0, 6))
+    );
+    assert_eq!(file.line(0), Some("a😀ccb"));
+  }
+
+  #[test]
+  fn overlapping_edits_are_rejected() {
+    let mut file = read("overlapping", "let a = 1;\n");
+    let edit = |start: u32, end: u32| {
+      TextEdit::new(
+        Range::new(Position::new(0, start), Position::new(0, end)),
+        "b".into(),
+      )
+    };
+
+    let err = file
+      .apply_edits(vec![edit(4, 6), edit(5, 7)], None)
+      .unwrap_err();
+    assert!(err.to_string().starts_with("Overlapping edits"));
+    assert_eq!(file.line(0), Some("let a = 1;"));
+    assert_eq!(file.version(), 0);
+
+    //edits touching at one position are not overlapping
+    file
+      .apply_edits(vec![edit(4, 5), edit(5, 5)], None)
+      .unwrap();
+    assert_eq!(file.line(0), Some("let bb = 1;"));
+  }
 }
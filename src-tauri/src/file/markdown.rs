@@ -0,0 +1,336 @@
+use tree_sitter_highlight::Highlighter;
+
+use super::parser::{highlight, ParsersManager};
+
+///Languages of code blocks named differently than the extension of their files
+const LANGUAGE_EXTENSIONS: [(&str, &str); 8] = [
+  ("rust", "rs"),
+  ("python", "py"),
+  ("javascript", "js"),
+  ("typescript", "ts"),
+  ("markdown", "md"),
+  ("shell", "sh"),
+  ("bash", "sh"),
+  ("csharp", "cs"),
+];
+
+///Schemes links may use, others like `javascript:` are rendered as plain text
+const LINK_SCHEMES: [&str; 3] = ["http", "https", "file"];
+
+///Renders the markdown of language servers as html. Code blocks are highlighted with the parser
+///of their language, blocks without one use the parser of `path`.
+pub(crate) fn to_html(markdown: &str, parsers: &ParsersManager, path: &str) -> String {
+  let mut html = String::new();
+  let mut paragraph: Vec<&str> = Vec::new();
+  let mut list: Option<&str> = None;
+  let mut lines = markdown.lines();
+
+  while let Some(line) = lines.next() {
+    let trimmed = line.trim_start();
+
+    if let Some((fence, language)) = fence(trimmed) {
+      flush(&mut html, &mut paragraph, &mut list);
+      let code: Vec<&str> = lines
+        .by_ref()
+        .take_while(|line| !line.trim_start().starts_with(fence))
+        .collect();
+      html += &code_block(&code.join("\n"), language, parsers, path);
+      continue;
+    }
+
+    if trimmed.is_empty() {
+      flush(&mut html, &mut paragraph, &mut list);
+      continue;
+    }
+
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+      flush(&mut html, &mut paragraph, &mut list);
+      let heading = trimmed[level..].trim().trim_end_matches('#').trim_end();
+      html += &format!("<h{0}>{1}</h{0}>", level, inline(heading));
+      continue;
+    }
+
+    if is_rule(trimmed) {
+      flush(&mut html, &mut paragraph, &mut list);
+      html += "<hr>";
+      continue;
+    }
+
+    if let Some(quote) = trimmed.strip_prefix('>') {
+      flush(&mut html, &mut paragraph, &mut list);
+      html += &format!("<blockquote>{}</blockquote>", inline(quote.trim_start()));
+      continue;
+    }
+
+    if let Some((kind, item)) = list_item(trimmed) {
+      flush(&mut html, &mut paragraph, &mut None);
+      if list != Some(kind) {
+        flush(&mut html, &mut Vec::new(), &mut list);
+        html += &format!("<{}>", kind);
+        list = Some(kind);
+      }
+      html += &format!("<li>{}</li>", inline(item));
+      continue;
+    }
+
+    if list.is_some() && paragraph.is_empty() && line.starts_with(char::is_whitespace) {
+      //continuation of the previous list item
+      html.truncate(html.len() - "</li>".len());
+      html += &format!(" {}</li>", inline(trimmed));
+      continue;
+    }
+
+    flush(&mut html, &mut Vec::new(), &mut list);
+    paragraph.push(line);
+  }
+
+  flush(&mut html, &mut paragraph, &mut list);
+  html
+}
+
+///Plain text keeps its line breaks
+pub(crate) fn plaintext_to_html(text: &str) -> String {
+  format!("<p>{}</p>", escape(text).replace('\n', "<br>"))
+}
+
+pub(crate) fn escape(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+fn flush(html: &mut String, paragraph: &mut Vec<&str>, list: &mut Option<&str>) {
+  if !paragraph.is_empty() {
+    let lines: Vec<String> = paragraph
+      .iter()
+      .enumerate()
+      .map(|(i, line)| {
+        let last = i == paragraph.len() - 1;
+        //two trailing spaces or a backslash break the line
+        match line.strip_suffix('\\') {
+          Some(line) if !last => inline(line.trim()) + "<br>",
+          _ if line.ends_with("  ") && !last => inline(line.trim()) + "<br>",
+          _ => inline(line.trim()),
+        }
+      })
+      .collect();
+    *html += &format!("<p>{}</p>", lines.join("\n"));
+    paragraph.clear();
+  }
+
+  if let Some(kind) = list.take() {
+    *html += &format!("</{}>", kind);
+  }
+}
+
+///The fence and the language of an opening code fence
+fn fence(line: &str) -> Option<(&str, &str)> {
+  ["```", "~~~"].into_iter().find_map(|fence| {
+    let language = line.strip_prefix(fence)?;
+    Some((fence, language.trim_start_matches(&fence[..1]).trim()))
+  })
+}
+
+fn is_rule(line: &str) -> bool {
+  let line: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+  line.len() >= 3
+    && ['-', '*', '_']
+      .into_iter()
+      .any(|marker| line.chars().all(|c| c == marker))
+}
+
+fn list_item(line: &str) -> Option<(&'static str, &str)> {
+  for bullet in ["- ", "* ", "+ "] {
+    if let Some(item) = line.strip_prefix(bullet) {
+      return Some(("ul", item.trim_start()));
+    }
+  }
+
+  let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+  let item = line[digits..]
+    .strip_prefix(". ")
+    .or_else(|| line[digits..].strip_prefix(") "))?;
+  (digits > 0).then_some(("ol", item.trim_start()))
+}
+
+pub(crate) fn code_block(
+  code: &str,
+  language: &str,
+  parsers: &ParsersManager,
+  path: &str,
+) -> String {
+  let extension = LANGUAGE_EXTENSIONS
+    .iter()
+    .find(|(name, _)| *name == language)
+    .map_or(language, |(_, extension)| extension);
+  let file_name = match language.is_empty() {
+    true => path.to_string(),
+    false => format!("code.{}", extension),
+  };
+
+  let tokens = parsers
+    .highlight_config(&file_name)
+    .and_then(|config| highlight(&mut Highlighter::new(), &config, code));
+  let code = match tokens {
+    Some(tokens) => tokens
+      .iter()
+      .map(|token| match token.type_.is_empty() {
+        true => escape(&token.token),
+        false => format!(
+          "<span class=\"token\" data-type=\"{}\">{}</span>",
+          token.type_,
+          escape(&token.token)
+        ),
+      })
+      .collect(),
+    None => escape(code),
+  };
+
+  format!(
+    "<pre><code data-language=\"{}\">{}</code></pre>",
+    escape(language),
+    code
+  )
+}
+
+///Renders code spans, emphasis, links and escapes of a single block
+fn inline(text: &str) -> String {
+  let mut html = String::new();
+  let mut rest = text;
+
+  while let Some(c) = rest.chars().next() {
+    if c == '\\' {
+      if let Some(escaped) = rest[1..].chars().next().filter(char::is_ascii_punctuation) {
+        html += &escape(&escaped.to_string());
+        rest = &rest[1 + escaped.len_utf8()..];
+        continue;
+      }
+    }
+
+    if c == '`' {
+      let ticks = rest.chars().take_while(|c| *c == '`').count();
+      let fence = &rest[..ticks];
+      if let Some(end) = rest[ticks..].find(fence) {
+        let code = &rest[ticks..ticks + end];
+        html += &format!("<code>{}</code>", escape(code.trim()));
+        rest = &rest[ticks + end + ticks..];
+        continue;
+      }
+      html += fence;
+      rest = &rest[ticks..];
+      continue;
+    }
+
+    //underscores inside words are not emphasis
+    let in_word = text[..text.len() - rest.len()]
+      .chars()
+      .last()
+      .is_some_and(char::is_alphanumeric);
+    if c == '*' || (c == '_' && !in_word) {
+      let marker = if rest[1..].starts_with(c) {
+        &rest[..2]
+      } else {
+        &rest[..1]
+      };
+      let tag = if marker.len() == 2 { "strong" } else { "em" };
+      let content = &rest[marker.len()..];
+      let closing = content
+        .find(marker)
+        .filter(|end| *end > 0 && !content[..*end].ends_with(char::is_whitespace))
+        .filter(|end| {
+          c == '*' || !content[end + marker.len()..].starts_with(char::is_alphanumeric)
+        });
+      if let Some(end) = closing.filter(|_| !content.starts_with(char::is_whitespace)) {
+        html += &format!("<{0}>{1}</{0}>", tag, inline(&content[..end]));
+        rest = &content[end + marker.len()..];
+        continue;
+      }
+      html += marker;
+      rest = content;
+      continue;
+    }
+
+    if c == '[' {
+      if let Some((label, url, len)) = link(rest) {
+        html += &match is_allowed(url) {
+          true => format!("<a href=\"{}\">{}</a>", escape(url), inline(label)),
+          false => inline(label),
+        };
+        rest = &rest[len..];
+        continue;
+      }
+    }
+
+    if c == '<' {
+      if let Some(end) = rest.find('>') {
+        let url = &rest[1..end];
+        if is_allowed(url) && !url.contains(char::is_whitespace) {
+          html += &format!("<a href=\"{0}\">{0}</a>", escape(url));
+          rest = &rest[end + 1..];
+          continue;
+        }
+      }
+    }
+
+    html += &escape(&c.to_string());
+    rest = &rest[c.len_utf8()..];
+  }
+
+  html
+}
+
+fn is_allowed(url: &str) -> bool {
+  url
+    .split_once(':')
+    .is_some_and(|(scheme, _)| LINK_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str()))
+}
+
+///Label, url and length of a `[label](url)` link at the start of `text`
+fn link(text: &str) -> Option<(&str, &str, usize)> {
+  let mut depth = 0;
+  let label_end = text.char_indices().find_map(|(i, c)| {
+    match c {
+      '[' => depth += 1,
+      ']' => depth -= 1,
+      _ => {}
+    }
+    (depth == 0).then_some(i)
+  })?;
+
+  let rest = text[label_end + 1..].strip_prefix('(')?;
+  let url_end = rest.find(')')?;
+  let url = rest[..url_end]
+    .split_whitespace()
+    .next()
+    .unwrap_or_default();
+  Some((&text[1..label_end], url, label_end + 2 + url_end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::file::parser::ParsersManager;
+
+  use super::to_html;
+
+  #[test]
+  fn markdown_is_rendered_as_html() {
+    let markdown = "# Title\nSome *text* with `code` and snake_case_name.\nnext line\n\n- one\n- **two**\n\n```python\nx = 1 < 2\n```\n[docs](https://docs.rs)";
+    assert_eq!(
+      to_html(markdown, &ParsersManager::new(), "/workspace/main.rs"),
+      "<h1>Title</h1>\
+       <p>Some <em>text</em> with <code>code</code> and snake_case_name.\nnext line</p>\
+       <ul><li>one</li><li><strong>two</strong></li></ul>\
+       <pre><code data-language=\"python\">x = 1 &lt; 2</code></pre>\
+       <p><a href=\"https://docs.rs\">docs</a></p>"
+    );
+
+    let markdown = "[run](javascript:void) [local](File:///a.rs) <data:text/html,x>";
+    assert_eq!(
+      to_html(markdown, &ParsersManager::new(), "/workspace/main.rs"),
+      "<p>run <a href=\"File:///a.rs\">local</a> &lt;data:text/html,x&gt;</p>"
+    );
+  }
+}
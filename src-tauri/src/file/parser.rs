@@ -1,6 +1,12 @@
+use std::sync::{Arc, OnceLock};
+
 use anyhow::Error;
+use lsp_types::FoldingRange;
 use regex::Regex;
-use tree_sitter::Language;
+use tree_sitter::{Language, Parser, Query, QueryCursor};
+use tree_sitter_highlight::{HighlightConfiguration, HighlightEvent, Highlighter};
+
+use super::token::Token;
 
 pub(crate) const HIGHLIGHTING_NAMES: [&str; 46] = [
   "variable",
@@ -91,13 +97,52 @@ pub(crate) fn get_highlighting_name(name: &str) -> Option<String> {
   })
 }
 
+///Highlights `content` into tokens without positions, which `TokenTree::set_raw_tokens` adds
+pub(crate) fn highlight(
+  highlighter: &mut Highlighter,
+  config: &HighlightConfiguration,
+  content: &str,
+) -> Option<Vec<Token>> {
+  let highlights = highlighter
+    .highlight(config, content.as_bytes(), None, |_| None)
+    .ok()?;
+
+  let mut tokens = Vec::new();
+  let mut current_token_types: Vec<String> = Vec::new();
+  for event in highlights {
+    match event.ok()? {
+      HighlightEvent::Source { start, end } => {
+        tokens.push(Token {
+          start: 0,
+          token: content[start..end].to_string(),
+          type_: current_token_types.last().cloned().unwrap_or_default(),
+          modifiers: None,
+          ..Default::default()
+        });
+      }
+      HighlightEvent::HighlightStart(s) => {
+        current_token_types.push(HIGHLIGHTING_NAMES[s.0].to_string());
+      }
+      HighlightEvent::HighlightEnd => {
+        current_token_types.pop();
+      }
+    }
+  }
+
+  Some(tokens)
+}
+
 pub(crate) struct ParsersManager {
   languages: Vec<ParserLanguage>,
 }
 
 pub(crate) struct ParserLanguage {
   language: (Language, String, String, String),
+  ///Nodes captured as `@fold` can be folded
+  folds_query: Query,
   file_pattern: Regex,
+  ///Compiling the queries is slow, so the configuration is built once and shared
+  highlight_config: OnceLock<Option<Arc<HighlightConfiguration>>>,
 }
 
 impl ParsersManager {
@@ -113,6 +158,7 @@ impl ParsersManager {
     highlights_query: &str,
     injection_query: &str,
     locals_query: &str,
+    folds_query: &str,
     file_pattern: String,
   ) -> Result<(), Error> {
     if tree_sitter::LANGUAGE_VERSION != language.version() {
@@ -120,6 +166,8 @@ impl ParsersManager {
     }
 
     let language_name = Regex::new(&file_pattern)?;
+    let folds_query = Query::new(&language, folds_query)
+      .map_err(|err| anyhow::anyhow!("Invalid folds query: {}", err))?;
 
     self.languages.push(ParserLanguage {
       language: (
@@ -128,7 +176,9 @@ impl ParsersManager {
         injection_query.to_string(),
         locals_query.to_string(),
       ),
+      folds_query,
       file_pattern: language_name,
+      highlight_config: OnceLock::new(),
     });
 
     Ok(())
@@ -146,4 +196,75 @@ impl ParsersManager {
 
     None
   }
+
+  ///Configuration of the language of the file, built the first time it is needed
+  pub(crate) fn highlight_config(&self, file_name: &str) -> Option<Arc<HighlightConfiguration>> {
+    let parser_language = self
+      .languages
+      .iter()
+      .find(|parser_language| parser_language.file_pattern.is_match(file_name))?;
+    parser_language
+      .highlight_config
+      .get_or_init(|| {
+        let (language, highlights_query, injection_query, locals_query) = &parser_language.language;
+        match HighlightConfiguration::new(
+          language.clone(),
+          "name",
+          highlights_query,
+          injection_query,
+          locals_query,
+        ) {
+          Ok(mut config) => {
+            config.configure(&HIGHLIGHTING_NAMES);
+            Some(Arc::new(config))
+          }
+          Err(err) => {
+            eprintln!("Error creating highlight configuration: {}", err);
+            None
+          }
+        }
+      })
+      .clone()
+  }
+
+  ///Ranges of the nodes the folds query of the language captures, nodes on one line are skipped.
+  ///A last line with only the closing bracket stays visible, like with servers folding lines only.
+  pub(crate) fn folds(&self, file_name: &str, content: &str) -> Option<Vec<FoldingRange>> {
+    let parser_language = self
+      .languages
+      .iter()
+      .find(|parser_language| parser_language.file_pattern.is_match(file_name))?;
+    let query = &parser_language.folds_query;
+    let fold = query.capture_index_for_name("fold")?;
+
+    let mut parser = Parser::new();
+    parser.set_language(&parser_language.language.0).ok()?;
+    let tree = parser.parse(content, None)?;
+    let lines: Vec<&str> = content.split('\n').collect();
+
+    let mut cursor = QueryCursor::new();
+    let ranges = cursor
+      .matches(query, tree.root_node(), content.as_bytes())
+      .flat_map(|query_match| query_match.captures.iter())
+      .filter(|capture| capture.index == fold)
+      .filter_map(|capture| {
+        let (start, end) = (capture.node.start_position(), capture.node.end_position());
+        let closing = lines
+          .get(end.row)
+          .and_then(|line| line.get(..end.column))
+          .is_some_and(|line| matches!(line.trim(), "}" | ")" | "]"));
+        let end_line = if closing { end.row - 1 } else { end.row };
+
+        (end_line > start.row).then_some(FoldingRange {
+          start_line: start.row as u32,
+          start_character: None,
+          end_line: end_line as u32,
+          end_character: None,
+          kind: None,
+          collapsed_text: None,
+        })
+      })
+      .collect();
+    Some(ranges)
+  }
 }
@@ -1,3 +1,4 @@
+use lsp_types::{DocumentHighlight, DocumentHighlightKind};
 use serde::Serialize;
 
 #[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash, Default)]
@@ -7,6 +8,10 @@ pub(crate) struct Token {
   #[serde(rename = "type")]
   pub(crate) type_: String,
   pub(crate) modifiers: Option<Vec<String>>,
+  ///Set for text that is shown but not part of the file, like inlay hints. `start` is the column
+  ///it is shown at, the columns of the other tokens do not include it.
+  #[serde(rename = "virtual", skip_serializing_if = "std::ops::Not::not")]
+  pub(crate) virtual_: bool,
 }
 
 #[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash, Default)]
@@ -43,6 +48,7 @@ impl TokenTree {
             token: tokens[i].clone(),
             type_: token.type_.clone(),
             modifiers: token.modifiers.clone(),
+            ..Default::default()
           });
 
           if i != tokens.len() - 1 {
@@ -54,12 +60,69 @@ impl TokenTree {
             token: tokens[i].clone(),
             type_: token.type_.clone(),
             modifiers: token.modifiers.clone(),
+            ..Default::default()
           });
         }
       }
     }
   }
 
+  ///Adds virtual tokens in front of the tokens starting at or after their byte column, lines that
+  ///are not in the tree are skipped
+  pub(crate) fn insert_virtual(&mut self, tokens: Vec<(u32, Token)>) {
+    for (line, token) in tokens {
+      let Some(line) = self.tokens.get_mut(line as usize) else {
+        continue;
+      };
+
+      //virtual tokens at the same column stay in the given order
+      let index = line
+        .iter()
+        .position(|other| {
+          other.start > token.start || (other.start == token.start && !other.virtual_)
+        })
+        .unwrap_or(line.len());
+      line.insert(index, token);
+    }
+  }
+
+  ///Adds `occurrence.read`, `occurrence.write` or `occurrence.text` to the modifiers of the
+  ///tokens overlapping a highlight. The ranges are in byte columns, like the tokens.
+  pub(crate) fn mark_occurrences(&mut self, highlights: &[DocumentHighlight]) {
+    for highlight in highlights {
+      let modifier = match highlight.kind {
+        Some(DocumentHighlightKind::READ) => "occurrence.read",
+        Some(DocumentHighlightKind::WRITE) => "occurrence.write",
+        _ => "occurrence.text",
+      };
+      let (start, end) = (highlight.range.start, highlight.range.end);
+
+      for line in start.line..=end.line {
+        let Some(tokens) = self.tokens.get_mut(line as usize) else {
+          break;
+        };
+        let from = match line == start.line {
+          true => start.character,
+          false => 0,
+        };
+        let to = match line == end.line {
+          true => end.character,
+          false => u32::MAX,
+        };
+
+        let overlapping = tokens.iter_mut().filter(|token| {
+          !token.virtual_ && token.start < to && token.start + token.token.len() as u32 > from
+        });
+        for token in overlapping {
+          let modifiers = token.modifiers.get_or_insert_with(Vec::new);
+          if !modifiers.iter().any(|other| other == modifier) {
+            modifiers.push(modifier.to_string());
+          }
+        }
+      }
+    }
+  }
+
   pub(crate) fn merge(&mut self, other: TokenTree) {
     let mut open_brackets = Vec::new();
     let default = Vec::new();
@@ -0,0 +1,766 @@
+use std::{
+  collections::HashSet,
+  fmt, fs, mem,
+  path::{Path, PathBuf},
+};
+
+use anyhow::Error;
+use lsp_types::{
+  request::{ApplyWorkspaceEdit, Request},
+  ApplyWorkspaceEditResponse, ChangeAnnotationIdentifier, DocumentChangeOperation, DocumentChanges,
+  OneOf, ResourceOp, TextEdit, Url, WorkspaceEdit,
+};
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Value};
+
+use crate::lsp::manager::LSPManager;
+
+use super::{
+  manager::{File, FileManager, PendingChange},
+  parser::ParsersManager,
+};
+
+///Why a workspace edit was not applied, nothing of it is left behind
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct WorkspaceEditFailure {
+  ///Index of the change that failed, in `documentChanges` if the edit has them
+  pub(crate) failed_change: Option<u32>,
+  pub(crate) reason: String,
+}
+
+///Workspace edit keeping the annotations of its text edits, lsp-types reads annotated text edits
+///as plain ones and drops their `annotationId`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct AnnotatedWorkspaceEdit {
+  pub(crate) edit: WorkspaceEdit,
+  ///Annotation of every text edit, by the index of its entry in `documentChanges`
+  annotations: Vec<Vec<Option<ChangeAnnotationIdentifier>>>,
+}
+
+///`workspace/applyEdit` with the annotations of the text edits
+pub(crate) enum ApplyAnnotatedWorkspaceEdit {}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct ApplyAnnotatedWorkspaceEditParams {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) label: Option<String>,
+  pub(crate) edit: AnnotatedWorkspaceEdit,
+}
+
+impl Request for ApplyAnnotatedWorkspaceEdit {
+  type Params = ApplyAnnotatedWorkspaceEditParams;
+  type Result = ApplyWorkspaceEditResponse;
+  const METHOD: &'static str = ApplyWorkspaceEdit::METHOD;
+}
+
+///One entry of `changes` or `documentChanges`
+enum Change {
+  Edit {
+    path: String,
+    ///Version the open file has to have
+    version: Option<i32>,
+    edits: Vec<TextEdit>,
+  },
+  Create {
+    path: String,
+    overwrite: bool,
+    ignore_if_exists: bool,
+  },
+  Rename {
+    from: String,
+    to: String,
+    overwrite: bool,
+    ignore_if_exists: bool,
+  },
+  Delete {
+    path: String,
+    recursive: bool,
+    ignore_if_not_exists: bool,
+  },
+}
+
+///What has to be done to revert an applied change
+enum Undo {
+  ///Open file that was edited. The change is discarded as long as the server was not told about
+  ///it, otherwise `text` is restored as a new change.
+  Buffer {
+    path: String,
+    text: String,
+    ///Changes that could be redone before the edit
+    redo: Vec<Vec<TextEdit>>,
+    sent: bool,
+  },
+  ///Content of a file on disk before it was written, `None` if it did not exist
+  Disk {
+    path: PathBuf,
+    content: Option<Vec<u8>>,
+  },
+  ///File or directory that was moved from `to` to `from`
+  Moved { from: PathBuf, to: PathBuf },
+  ///Open file that was moved from `to` to `from`
+  BufferMoved { from: String, to: String },
+  ///Open file that was closed because it was deleted
+  BufferClosed(File),
+}
+
+struct Transaction<'a> {
+  files: &'a mut FileManager,
+  parsers: &'a ParsersManager,
+  lsp: &'a LSPManager,
+  undo: Vec<Undo>,
+  ///Changes of open files, only sent to the servers once every change succeeded
+  pending: Vec<PendingChange>,
+  ///Deleted files and directories, only removed once every change succeeded
+  trash: Vec<PathBuf>,
+  changed: Vec<String>,
+}
+
+///Applies the edit to open files and files on disk. Changes with annotations that need a
+///confirmation are only applied if `confirmed` is set. If one change fails, the applied ones
+///are reverted. Returns the paths of the changed files.
+pub(crate) fn apply_workspace_edit(
+  edit: AnnotatedWorkspaceEdit,
+  confirmed: bool,
+  files: &mut FileManager,
+  parsers: &ParsersManager,
+  lsp: &LSPManager,
+) -> Result<Vec<String>, WorkspaceEditFailure> {
+  let annotations = edit.edit.change_annotations.clone().unwrap_or_default();
+  let changes = changes(edit)?;
+
+  for (index, (_, ids)) in changes.iter().enumerate() {
+    let failure = |reason: String| WorkspaceEditFailure::new(Some(index), reason);
+    let mut labels = Vec::new();
+    for id in ids {
+      let annotation = annotations
+        .get(id)
+        .ok_or_else(|| failure(format!("Unknown change annotation {}", id)))?;
+      if annotation.needs_confirmation == Some(true) {
+        labels.push(annotation.label.as_str());
+      }
+    }
+    if !confirmed && !labels.is_empty() {
+      return Err(failure(format!(
+        "Needs confirmation: {}",
+        labels.join(", ")
+      )));
+    }
+  }
+
+  let mut transaction = Transaction {
+    files,
+    parsers,
+    lsp,
+    undo: Vec::new(),
+    pending: Vec::new(),
+    trash: Vec::new(),
+    changed: Vec::new(),
+  };
+  for (index, (change, _)) in changes.into_iter().enumerate() {
+    if let Err(err) = transaction.apply(change) {
+      transaction.rollback();
+      return Err(WorkspaceEditFailure::new(Some(index), err.to_string()));
+    }
+  }
+
+  Ok(transaction.commit())
+}
+
+impl From<WorkspaceEdit> for AnnotatedWorkspaceEdit {
+  fn from(edit: WorkspaceEdit) -> Self {
+    AnnotatedWorkspaceEdit {
+      edit,
+      annotations: Vec::new(),
+    }
+  }
+}
+
+impl<'de> Deserialize<'de> for AnnotatedWorkspaceEdit {
+  fn deserialize<D: Deserializer<'de>>(des: D) -> Result<Self, D::Error> {
+    let value = Value::deserialize(des)?;
+    let annotations = value["documentChanges"]
+      .as_array()
+      .into_iter()
+      .flatten()
+      .map(|change| {
+        change["edits"]
+          .as_array()
+          .into_iter()
+          .flatten()
+          .map(|edit| edit["annotationId"].as_str().map(str::to_string))
+          .collect()
+      })
+      .collect();
+
+    let edit = serde_json::from_value(value).map_err(de::Error::custom)?;
+    Ok(AnnotatedWorkspaceEdit { edit, annotations })
+  }
+}
+
+impl Serialize for AnnotatedWorkspaceEdit {
+  fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+    let mut value = serde_json::to_value(&self.edit).map_err(ser::Error::custom)?;
+    for (change, annotations) in self.annotations.iter().enumerate() {
+      for (index, annotation) in annotations.iter().enumerate() {
+        let pointer = format!("/documentChanges/{}/edits/{}", change, index);
+        if let (Some(Value::Object(edit)), Some(annotation)) =
+          (value.pointer_mut(&pointer), annotation)
+        {
+          edit.insert("annotationId".to_string(), json!(annotation));
+        }
+      }
+    }
+    value.serialize(ser)
+  }
+}
+
+impl WorkspaceEditFailure {
+  fn new(failed_change: Option<usize>, reason: String) -> Self {
+    WorkspaceEditFailure {
+      failed_change: failed_change.map(|index| index as u32),
+      reason,
+    }
+  }
+}
+
+impl fmt::Display for WorkspaceEditFailure {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.failed_change {
+      Some(index) => write!(f, "Change {} failed: {}", index, self.reason),
+      None => write!(f, "{}", self.reason),
+    }
+  }
+}
+
+impl From<WorkspaceEditFailure> for ApplyWorkspaceEditResponse {
+  fn from(failure: WorkspaceEditFailure) -> Self {
+    ApplyWorkspaceEditResponse {
+      applied: false,
+      failure_reason: Some(failure.reason),
+      failed_change: failure.failed_change,
+    }
+  }
+}
+
+///`documentChanges` take precedence over `changes`, every change comes with its annotations
+fn changes(
+  edit: AnnotatedWorkspaceEdit,
+) -> Result<Vec<(Change, Vec<ChangeAnnotationIdentifier>)>, WorkspaceEditFailure> {
+  let AnnotatedWorkspaceEdit { edit, annotations } = edit;
+  let mut changes = Vec::new();
+  let mut push = |change: Result<Change, Error>, ids: Vec<ChangeAnnotationIdentifier>| {
+    let index = changes.len();
+    let change = change.map_err(|err| WorkspaceEditFailure::new(Some(index), err.to_string()))?;
+    changes.push((change, ids));
+    Ok(())
+  };
+
+  let operations = match edit.document_changes {
+    Some(DocumentChanges::Edits(edits)) => edits
+      .into_iter()
+      .map(DocumentChangeOperation::Edit)
+      .collect(),
+    Some(DocumentChanges::Operations(operations)) => operations,
+    None => {
+      //the order of `changes` does not matter, they all edit different files
+      for (uri, edits) in edit.changes.unwrap_or_default() {
+        let change = file_path(&uri).map(|path| Change::Edit {
+          path,
+          version: None,
+          edits,
+        });
+        push(change, Vec::new())?;
+      }
+      return Ok(changes);
+    }
+  };
+
+  for (index, operation) in operations.into_iter().enumerate() {
+    match operation {
+      DocumentChangeOperation::Edit(edit) => {
+        let mut ids: Vec<ChangeAnnotationIdentifier> = annotations
+          .get(index)
+          .into_iter()
+          .flatten()
+          .flatten()
+          .cloned()
+          .collect();
+        let edits = edit
+          .edits
+          .into_iter()
+          .map(|edit| match edit {
+            OneOf::Left(edit) => edit,
+            OneOf::Right(annotated) => {
+              ids.push(annotated.annotation_id);
+              annotated.text_edit
+            }
+          })
+          .collect();
+        let change = file_path(&edit.text_document.uri).map(|path| Change::Edit {
+          path,
+          version: edit.text_document.version,
+          edits,
+        });
+        push(change, ids)?;
+      }
+      DocumentChangeOperation::Op(ResourceOp::Create(create)) => {
+        let options = create.options.as_ref();
+        let change = file_path(&create.uri).map(|path| Change::Create {
+          path,
+          overwrite: options
+            .and_then(|options| options.overwrite)
+            .unwrap_or(false),
+          ignore_if_exists: options
+            .and_then(|options| options.ignore_if_exists)
+            .unwrap_or(false),
+        });
+        push(change, create.annotation_id.into_iter().collect())?;
+      }
+      DocumentChangeOperation::Op(ResourceOp::Rename(rename)) => {
+        let options = rename.options.as_ref();
+        let change = file_path(&rename.old_uri).and_then(|from| {
+          Ok(Change::Rename {
+            from,
+            to: file_path(&rename.new_uri)?,
+            overwrite: options
+              .and_then(|options| options.overwrite)
+              .unwrap_or(false),
+            ignore_if_exists: options
+              .and_then(|options| options.ignore_if_exists)
+              .unwrap_or(false),
+          })
+        });
+        push(change, rename.annotation_id.into_iter().collect())?;
+      }
+      DocumentChangeOperation::Op(ResourceOp::Delete(delete)) => {
+        let options = delete.options.as_ref();
+        let change = file_path(&delete.uri).map(|path| Change::Delete {
+          path,
+          recursive: options
+            .and_then(|options| options.recursive)
+            .unwrap_or(false),
+          ignore_if_not_exists: options
+            .and_then(|options| options.ignore_if_not_exists)
+            .unwrap_or(false),
+        });
+        let ids = options.and_then(|options| options.annotation_id.clone());
+        push(change, ids.into_iter().collect())?;
+      }
+    }
+  }
+
+  Ok(changes)
+}
+
+impl Transaction<'_> {
+  fn apply(&mut self, change: Change) -> Result<(), Error> {
+    match change {
+      Change::Edit {
+        path,
+        version,
+        edits,
+      } => self.edit(path, version, edits),
+      Change::Create {
+        path,
+        overwrite,
+        ignore_if_exists,
+      } => {
+        let previous = fs::read(&path).ok();
+        if previous.is_some() && !overwrite {
+          return match ignore_if_exists {
+            true => Ok(()),
+            false => Err(anyhow::anyhow!("{} already exists", path)),
+          };
+        }
+
+        fs::write(&path, "")?;
+        self.undo.push(Undo::Disk {
+          path: PathBuf::from(&path),
+          content: previous,
+        });
+        //an open file would keep the content that was overwritten
+        if let Some(file) = self.files.get_file(&path) {
+          let edit = TextEdit::new(file.range(), String::new());
+          return self.edit(path, None, vec![edit]);
+        }
+        self.changed.push(path);
+        Ok(())
+      }
+      Change::Rename {
+        from,
+        to,
+        overwrite,
+        ignore_if_exists,
+      } => {
+        if Path::new(&to).exists() {
+          if !overwrite {
+            return match ignore_if_exists {
+              true => Ok(()),
+              false => Err(anyhow::anyhow!("{} already exists", to)),
+            };
+          }
+          self.remove(&to)?;
+        }
+
+        fs::rename(&from, &to)?;
+        self.undo.push(Undo::Moved {
+          from: PathBuf::from(&to),
+          to: PathBuf::from(&from),
+        });
+        for path in self.open_paths_in(&from) {
+          self.send_pending(&path)?;
+          let new_path = format!("{}{}", to, &path[from.len()..]);
+          self
+            .files
+            .rename_file(&path, &new_path, self.parsers, self.lsp)?;
+          self.undo.push(Undo::BufferMoved {
+            from: new_path,
+            to: path,
+          });
+        }
+        self.changed.push(to);
+        Ok(())
+      }
+      Change::Delete {
+        path,
+        recursive,
+        ignore_if_not_exists,
+      } => {
+        let metadata = match fs::metadata(&path) {
+          Ok(metadata) => metadata,
+          Err(_) if ignore_if_not_exists => return Ok(()),
+          Err(err) => return Err(anyhow::anyhow!("Cannot delete {}: {}", path, err)),
+        };
+        if metadata.is_dir() && !recursive && fs::read_dir(&path)?.next().is_some() {
+          return Err(anyhow::anyhow!("{} is not empty", path));
+        }
+
+        self.remove(&path)?;
+        for open in self.open_paths_in(&path) {
+          self.send_pending(&open)?;
+          if let Some(file) = self.files.take_file(&open, self.lsp)? {
+            self.undo.push(Undo::BufferClosed(file));
+          }
+        }
+        Ok(())
+      }
+    }
+  }
+
+  ///Edits the open file, or the file on disk if it is not open
+  fn edit(
+    &mut self,
+    path: String,
+    version: Option<i32>,
+    edits: Vec<TextEdit>,
+  ) -> Result<(), Error> {
+    if let Some(file) = self.files.get_file(&path) {
+      if version.is_some_and(|version| version != file.version()) {
+        return Err(anyhow::anyhow!(
+          "{} changed since version {}",
+          path,
+          version.unwrap_or_default()
+        ));
+      }
+
+      let (text, redo) = (file.text(), file.redo_stack());
+      let change = self.files.change_file(&path, edits, self.lsp)?;
+      self.pending.push(change);
+      self.undo.push(Undo::Buffer {
+        path: path.clone(),
+        text,
+        redo,
+        sent: false,
+      });
+    } else {
+      let mut file = File::read(&path)?;
+      let previous = fs::read(&path)?;
+      file.apply_edits(edits, self.lsp.position_encoding(&path).as_ref())?;
+      fs::write(&path, file.text())?;
+      self.undo.push(Undo::Disk {
+        path: PathBuf::from(&path),
+        content: Some(previous),
+      });
+    }
+
+    self.changed.push(path);
+    Ok(())
+  }
+
+  ///Moves the file or directory next to itself, so it can be restored until the edit is done
+  fn remove(&mut self, path: &str) -> Result<(), Error> {
+    let path = PathBuf::from(path);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let trash = path.with_file_name(format!(".{}.{}.deleted", name, std::process::id()));
+
+    fs::rename(&path, &trash)?;
+    self.undo.push(Undo::Moved {
+      from: trash.clone(),
+      to: path,
+    });
+    self.trash.push(trash);
+    Ok(())
+  }
+
+  ///Tells the server about the changes of an open file before it is closed or moved, from then
+  ///on they are reverted as new changes
+  fn send_pending(&mut self, path: &str) -> Result<(), Error> {
+    let (changes, pending): (Vec<PendingChange>, Vec<PendingChange>) = mem::take(&mut self.pending)
+      .into_iter()
+      .partition(|change| change.path() == path);
+    self.pending = pending;
+    for change in changes {
+      change.send(self.lsp)?;
+    }
+
+    for undo in &mut self.undo {
+      if let Undo::Buffer {
+        path: edited, sent, ..
+      } = undo
+      {
+        *sent |= *edited == path;
+      }
+    }
+    Ok(())
+  }
+
+  ///Open files that are `path` or inside of it
+  fn open_paths_in(&self, path: &str) -> Vec<String> {
+    self
+      .files
+      .open_paths()
+      .into_iter()
+      .filter(|open| Path::new(open).starts_with(path))
+      .collect()
+  }
+
+  fn rollback(&mut self) {
+    self.pending.clear();
+    while let Some(undo) = self.undo.pop() {
+      let result = match undo {
+        Undo::Buffer {
+          path,
+          redo,
+          sent: false,
+          ..
+        } => self.files.discard_change(&path, redo, self.lsp),
+        Undo::Buffer {
+          path,
+          text,
+          sent: true,
+          ..
+        } => self.files.set_text(&path, text, self.lsp),
+        Undo::Disk {
+          path,
+          content: Some(content),
+        } => fs::write(path, content).map_err(Error::from),
+        Undo::Disk {
+          path,
+          content: None,
+        } => fs::remove_file(path).map_err(Error::from),
+        Undo::Moved { from, to } => fs::rename(from, to).map_err(Error::from),
+        Undo::BufferMoved { from, to } => {
+          self.files.rename_file(&from, &to, self.parsers, self.lsp)
+        }
+        Undo::BufferClosed(file) => self.files.restore_file(file, self.lsp),
+      };
+
+      if let Err(err) = result {
+        eprintln!("Error reverting workspace edit: {}", err);
+      }
+    }
+  }
+
+  fn commit(self) -> Vec<String> {
+    for change in self.pending {
+      let path = change.path().to_string();
+      if let Err(err) = change.send(self.lsp) {
+        eprintln!("Error sending change of {}: {}", path, err);
+      }
+    }
+    for trash in self.trash {
+      let result = match trash.is_dir() {
+        true => fs::remove_dir_all(&trash),
+        false => fs::remove_file(&trash),
+      };
+      if let Err(err) = result {
+        eprintln!("Error deleting {}: {}", trash.display(), err);
+      }
+    }
+
+    //files changed more than once are reported where they were changed first
+    let mut changed = self.changed;
+    let mut reported = HashSet::new();
+    changed.retain(|path| reported.insert(path.clone()));
+    changed
+  }
+}
+
+fn file_path(uri: &Url) -> Result<String, Error> {
+  uri
+    .to_file_path()
+    .map(|path| path.display().to_string())
+    .map_err(|_| anyhow::anyhow!("{} is not a file", uri))
+}
+
+#[cfg(test)]
+mod tests {
+  use lsp_types::{Position, Range, TextEdit, Url};
+  use serde_json::json;
+
+  use crate::{
+    file::{manager::FileManager, parser::ParsersManager},
+    lsp::manager::LSPManager,
+  };
+
+  use super::{apply_workspace_edit, AnnotatedWorkspaceEdit};
+
+  #[test]
+  fn failed_workspace_edits_are_rolled_back() {
+    let dir = std::env::temp_dir().join(format!("codeforge-rollback-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).display().to_string();
+    let uri = |name: &str| Url::from_file_path(dir.join(name)).unwrap();
+    std::fs::write(path("open.rs"), "let a = 1;\n").unwrap();
+    std::fs::write(path("disk.rs"), "let b = 2;\r\n").unwrap();
+    std::fs::write(path("old.rs"), "let c = 3;\n").unwrap();
+
+    //files no server is responsible for are only edited locally
+    let manager = LSPManager::new();
+    let parsers = ParsersManager::new();
+    let mut files = FileManager::new();
+    files
+      .open_file(&path("open.rs"), &parsers, &manager)
+      .unwrap();
+
+    let replace = json!([{
+      "range": { "start": { "line": 0, "character": 4 }, "end": { "line": 0, "character": 5 } },
+      "newText": "x",
+    }]);
+    let edit: AnnotatedWorkspaceEdit = serde_json::from_value(json!({ "documentChanges": [
+      { "textDocument": { "uri": uri("open.rs"), "version": 0 }, "edits": replace },
+      { "textDocument": { "uri": uri("disk.rs"), "version": null }, "edits": replace },
+      { "kind": "create", "uri": uri("new.rs") },
+      { "kind": "delete", "uri": uri("old.rs") },
+      { "kind": "create", "uri": uri("disk.rs") },
+    ] }))
+    .unwrap();
+
+    let failure = apply_workspace_edit(edit, false, &mut files, &parsers, &manager).unwrap_err();
+    assert_eq!(failure.failed_change, Some(4));
+    //the edit of the open file is discarded as if it was never applied
+    let open = files.get_file(&path("open.rs")).unwrap();
+    assert_eq!(open.line(0), Some("let a = 1;"));
+    assert_eq!(open.version(), 0);
+    assert!(!files.undo_file(&path("open.rs"), &manager).unwrap());
+    assert_eq!(
+      std::fs::read_to_string(path("disk.rs")).unwrap(),
+      "let b = 2;\r\n"
+    );
+    assert_eq!(
+      std::fs::read_to_string(path("old.rs")).unwrap(),
+      "let c = 3;\n"
+    );
+    let mut names: Vec<String> = std::fs::read_dir(&dir)
+      .unwrap()
+      .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+      .collect();
+    names.sort();
+    assert_eq!(names, vec!["disk.rs", "old.rs", "open.rs"]);
+
+    //the open file was edited since the version the edit refers to
+    let comment = TextEdit::new(
+      Range::new(Position::new(0, 0), Position::new(0, 0)),
+      "//\n".into(),
+    );
+    files
+      .edit_file(&path("open.rs"), vec![comment], &manager)
+      .unwrap();
+    let stale: AnnotatedWorkspaceEdit = serde_json::from_value(json!({ "documentChanges": [
+      { "textDocument": { "uri": uri("open.rs"), "version": 0 }, "edits": replace },
+    ] }))
+    .unwrap();
+    let failure = apply_workspace_edit(stale, false, &mut files, &parsers, &manager).unwrap_err();
+    assert_eq!(failure.failed_change, Some(0));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn renamed_open_files_are_moved_back_on_failure() {
+    let dir = std::env::temp_dir().join(format!("codeforge-moved-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).display().to_string();
+    let uri = |name: &str| Url::from_file_path(dir.join(name)).unwrap();
+    std::fs::write(path("open.rs"), "let a = 1;\n").unwrap();
+
+    let manager = LSPManager::new();
+    let parsers = ParsersManager::new();
+    let mut files = FileManager::new();
+    files
+      .open_file(&path("open.rs"), &parsers, &manager)
+      .unwrap();
+
+    //the edit is sent before the file is moved, so it is reverted as a new change
+    let edit: AnnotatedWorkspaceEdit = serde_json::from_value(json!({ "documentChanges": [
+      { "textDocument": { "uri": uri("open.rs"), "version": 0 }, "edits": [{
+        "range": { "start": { "line": 0, "character": 4 }, "end": { "line": 0, "character": 5 } },
+        "newText": "x",
+      }] },
+      { "kind": "rename", "oldUri": uri("open.rs"), "newUri": uri("moved.rs") },
+      { "kind": "delete", "uri": uri("missing.rs") },
+    ] }))
+    .unwrap();
+
+    let failure = apply_workspace_edit(edit, false, &mut files, &parsers, &manager).unwrap_err();
+    assert_eq!(failure.failed_change, Some(2));
+    assert!(files.get_file(&path("moved.rs")).is_none());
+    assert_eq!(
+      files.get_file(&path("open.rs")).unwrap().line(0),
+      Some("let a = 1;")
+    );
+    assert_eq!(files.open_paths(), vec![path("open.rs")]);
+    assert_eq!(
+      std::fs::read_to_string(path("open.rs")).unwrap(),
+      "let a = 1;\n"
+    );
+    assert!(!dir.join("moved.rs").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn overwritten_open_files_are_reset_and_reported_once() {
+    let dir = std::env::temp_dir().join(format!("codeforge-overwrite-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).display().to_string();
+    let uri = |name: &str| Url::from_file_path(dir.join(name)).unwrap();
+    std::fs::write(path("open.rs"), "let a = 1;\n").unwrap();
+    std::fs::write(path("disk.rs"), "let b = 2;\n").unwrap();
+
+    let manager = LSPManager::new();
+    let parsers = ParsersManager::new();
+    let mut files = FileManager::new();
+    files
+      .open_file(&path("open.rs"), &parsers, &manager)
+      .unwrap();
+
+    let replace = json!([{
+      "range": { "start": { "line": 0, "character": 4 }, "end": { "line": 0, "character": 5 } },
+      "newText": "x",
+    }]);
+    let edit: AnnotatedWorkspaceEdit = serde_json::from_value(json!({ "documentChanges": [
+      { "textDocument": { "uri": uri("open.rs"), "version": 0 }, "edits": replace },
+      { "textDocument": { "uri": uri("disk.rs"), "version": null }, "edits": replace },
+      { "kind": "create", "uri": uri("open.rs"), "options": { "overwrite": true } },
+    ] }))
+    .unwrap();
+
+    let changed = apply_workspace_edit(edit, false, &mut files, &parsers, &manager).unwrap();
+    assert_eq!(changed, vec![path("open.rs"), path("disk.rs")]);
+    assert_eq!(files.get_file(&path("open.rs")).unwrap().text(), "");
+    assert_eq!(std::fs::read_to_string(path("open.rs")).unwrap(), "");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
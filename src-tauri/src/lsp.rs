@@ -1,8 +1,32 @@
 pub(crate) mod capabilities;
 pub(crate) mod client;
+pub(crate) mod code_action;
+pub(crate) mod code_lens;
+pub(crate) mod command;
+pub(crate) mod completion;
+pub(crate) mod diagnostics;
+pub(crate) mod document_store;
+pub(crate) mod encoding;
+pub(crate) mod handler;
+pub(crate) mod hierarchy;
+pub(crate) mod hover;
 pub(crate) mod info;
+pub(crate) mod inlay_hint;
+pub(crate) mod lifecycle;
 pub(crate) mod manager;
+pub(crate) mod navigation;
 pub(crate) mod notification;
+pub(crate) mod process;
+pub(crate) mod rename;
+pub(crate) mod semantic_tokens;
 pub(crate) mod request;
 pub(crate) mod response;
+pub(crate) mod signature;
+pub(crate) mod subscription;
+pub(crate) mod symbol;
+pub(crate) mod trace;
+pub(crate) mod transport;
 pub(crate) mod utils;
+
+#[cfg(test)]
+mod tests;
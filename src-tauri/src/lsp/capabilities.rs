@@ -2,40 +2,45 @@ use lsp_types::{
   ChangeAnnotationWorkspaceEditClientCapabilities, ClientCapabilities, CodeActionCapabilityResolveSupport, CodeActionClientCapabilities, CodeActionKindLiteralSupport, CodeActionLiteralSupport, CodeLensClientCapabilities, CodeLensWorkspaceClientCapabilities, CompletionClientCapabilities, CompletionItemKind, CompletionItemKindCapability, CompletionListCapability, DiagnosticClientCapabilities, DiagnosticTag, DiagnosticWorkspaceClientCapabilities, DidChangeWatchedFilesClientCapabilities, DocumentFormattingClientCapabilities, DocumentHighlightClientCapabilities, DocumentLinkClientCapabilities, DocumentSymbolClientCapabilities, DynamicRegistrationClientCapabilities, FoldingRangeCapability, FoldingRangeClientCapabilities, FoldingRangeKind, FoldingRangeKindCapability, GeneralClientCapabilities, GotoCapability, HoverClientCapabilities, InlayHintClientCapabilities, InlayHintResolveClientCapabilities, InlayHintWorkspaceClientCapabilities, InlineValueWorkspaceClientCapabilities, InsertTextMode, MarkdownClientCapabilities, MarkupKind, MessageActionItemCapabilities, PositionEncodingKind, PrepareSupportDefaultBehavior, PublishDiagnosticsClientCapabilities, ReferenceClientCapabilities, RegularExpressionsClientCapabilities, RenameClientCapabilities, ResourceOperationKind, SelectionRangeClientCapabilities, SemanticTokenModifier, SemanticTokenType, SemanticTokensClientCapabilities, SemanticTokensClientCapabilitiesRequests, SemanticTokensFullOptions, SemanticTokensWorkspaceClientCapabilities, ShowDocumentClientCapabilities, ShowMessageRequestClientCapabilities, SignatureHelpClientCapabilities, SignatureInformationSettings, StaleRequestSupportClientCapabilities, SymbolKind, SymbolKindCapability, SymbolTag, TagSupport, TextDocumentClientCapabilities, TextDocumentSyncClientCapabilities, TokenFormat, WindowClientCapabilities, WorkspaceClientCapabilities, WorkspaceEditClientCapabilities, WorkspaceFileOperationsClientCapabilities, WorkspaceSymbolClientCapabilities, WorkspaceSymbolResolveSupportCapability
 };
 
+///Symbol kinds the client understands, others are shown as variables
+pub(crate) const SYMBOL_KINDS: [SymbolKind; 26] = [
+  SymbolKind::FILE,
+  SymbolKind::MODULE,
+  SymbolKind::NAMESPACE,
+  SymbolKind::PACKAGE,
+  SymbolKind::CLASS,
+  SymbolKind::METHOD,
+  SymbolKind::PROPERTY,
+  SymbolKind::FIELD,
+  SymbolKind::CONSTRUCTOR,
+  SymbolKind::ENUM,
+  SymbolKind::INTERFACE,
+  SymbolKind::FUNCTION,
+  SymbolKind::VARIABLE,
+  SymbolKind::CONSTANT,
+  SymbolKind::STRING,
+  SymbolKind::NUMBER,
+  SymbolKind::BOOLEAN,
+  SymbolKind::ARRAY,
+  SymbolKind::OBJECT,
+  SymbolKind::KEY,
+  SymbolKind::NULL,
+  SymbolKind::ENUM_MEMBER,
+  SymbolKind::STRUCT,
+  SymbolKind::EVENT,
+  SymbolKind::OPERATOR,
+  SymbolKind::TYPE_PARAMETER,
+];
+
+pub(crate) const SYMBOL_TAGS: [SymbolTag; 1] = [SymbolTag::DEPRECATED];
+
 pub(crate) fn get_capabilities() -> ClientCapabilities {
   let symbol_kind = Some(SymbolKindCapability {
-    value_set: Some(vec![
-      SymbolKind::FILE,
-      SymbolKind::MODULE,
-      SymbolKind::NAMESPACE,
-      SymbolKind::PACKAGE,
-      SymbolKind::CLASS,
-      SymbolKind::METHOD,
-      SymbolKind::PROPERTY,
-      SymbolKind::FIELD,
-      SymbolKind::CONSTRUCTOR,
-      SymbolKind::ENUM,
-      SymbolKind::INTERFACE,
-      SymbolKind::FUNCTION,
-      SymbolKind::VARIABLE,
-      SymbolKind::CONSTANT,
-      SymbolKind::STRING,
-      SymbolKind::NUMBER,
-      SymbolKind::BOOLEAN,
-      SymbolKind::ARRAY,
-      SymbolKind::OBJECT,
-      SymbolKind::KEY,
-      SymbolKind::NULL,
-      SymbolKind::ENUM_MEMBER,
-      SymbolKind::STRUCT,
-      SymbolKind::EVENT,
-      SymbolKind::OPERATOR,
-      SymbolKind::TYPE_PARAMETER,
-    ]),
+    value_set: Some(SYMBOL_KINDS.to_vec()),
   });
 
   let tag_support = Some(TagSupport {
-    value_set: vec![SymbolTag::DEPRECATED],
+    value_set: SYMBOL_TAGS.to_vec(),
   });
 
   ClientCapabilities {
@@ -89,12 +94,12 @@ pub(crate) fn get_capabilities() -> ClientCapabilities {
       }),
       hover: Some(HoverClientCapabilities {
         dynamic_registration: Some(false),
-        content_format: Some(vec![MarkupKind::PlainText, MarkupKind::Markdown]),
+        content_format: Some(vec![MarkupKind::Markdown, MarkupKind::PlainText]),
       }),
       signature_help: Some(SignatureHelpClientCapabilities {
         dynamic_registration: Some(false),
         signature_information: Some(SignatureInformationSettings {
-          documentation_format: Some(vec![MarkupKind::PlainText, MarkupKind::Markdown]),
+          documentation_format: Some(vec![MarkupKind::Markdown, MarkupKind::PlainText]),
           parameter_information: Some(lsp_types::ParameterInformationSettings {
             label_offset_support: Some(true),
           }),
@@ -266,7 +271,14 @@ pub(crate) fn get_capabilities() -> ClientCapabilities {
       }),
       inlay_hint: Some(InlayHintClientCapabilities {
         dynamic_registration: Some(false),
-        resolve_support: Some(InlayHintResolveClientCapabilities { properties: vec![] }),
+        resolve_support: Some(InlayHintResolveClientCapabilities {
+          properties: vec![
+            "tooltip".to_string(),
+            "label.tooltip".to_string(),
+            "label.location".to_string(),
+            "label.command".to_string(),
+          ],
+        }),
       }),
       inline_value: Some(DynamicRegistrationClientCapabilities {
         dynamic_registration: Some(false),
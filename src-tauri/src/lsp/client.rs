@@ -1,47 +1,98 @@
 use std::{
-  io::{BufRead, BufReader, Read, Write},
-  os::windows::process::CommandExt,
-  process::Command,
-  sync::{Arc, Condvar, Mutex},
-  thread::{self, sleep},
+  collections::HashMap,
+  future::Future,
+  path::PathBuf,
+  sync::{Arc, Mutex, RwLock},
+  thread,
+  time::Duration,
 };
 
 use anyhow::Error;
 use lsp_types::{
-  notification::{Initialized, Notification as LSPNotificationTrait},
+  notification::{
+    Cancel, DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+    Initialized, Notification as LSPNotificationTrait, SetTrace,
+  },
   request::{Initialize, Request as LSPRequestTrait},
-  ClientCapabilities, InitializeParams, NumberOrString, WorkspaceFolder,
+  CancelParams, ClientCapabilities, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+  DidOpenTextDocumentParams, DidSaveTextDocumentParams, InitializeParams, NumberOrString,
+  SetTraceParams, TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem,
+  TextDocumentSyncKind, TextEdit, TraceValue, VersionedTextDocumentIdentifier, WorkspaceFolder,
 };
 use regex::Regex;
+use serde_json::Value;
+use tauri::Url;
 
 use super::{
-  info::LSPInfo,
+  handler::RequestHandlers,
+  info::{LSPInfo, SyncInfo},
+  lifecycle::ServerState,
   notification::{LSPNotification, RawLSPNotification},
-  request::{LSPRequest, PendingRequest},
-  response::{LSPMessage, LSPResponse},
+  process::{default_stderr_log, SpawnOptions},
+  request::{LSPRequest, PendingRequests, RawLSPRequest, RequestHandle},
+  response::{LSPMessage, RawLSPResponse},
+  subscription::{NotificationBus, Subscriber, Subscription},
+  trace::{Direction, TrafficEntry, TrafficLog},
+  transport::{Connection, Endpoint, MessageReader, MessageWriter, Transport},
+  utils::LSPError,
 };
 
 #[derive(Debug)]
 pub(crate) struct LSPClient {
-  stdin: std::process::ChildStdin,
-  pending: Vec<PendingRequest>,
+  writer: MessageWriter,
+  pub(super) endpoint: Endpoint,
+  pub(super) pending: PendingRequests,
+  pub(super) state: ServerState,
+  ///Incremented for every started process so a stale reader cannot report the new one as crashed
+  pub(super) generation: u32,
+  documents: HashMap<Url, TextDocumentItem>,
+  traffic: TrafficLog,
+  ///Sent with every initialize so a restarted server keeps tracing
+  trace: TraceValue,
 }
 
 #[derive(Debug)]
 pub(crate) struct LSPClientBuilder {
   path: Option<String>,
+  tcp: Option<String>,
+  #[cfg(unix)]
+  unix_socket: Option<PathBuf>,
   args: Option<Vec<String>>,
+  cwd: Option<PathBuf>,
+  env: Option<Vec<(String, String)>>,
+  stderr_log: Option<PathBuf>,
   file_patterns: Option<Vec<String>>,
   workspace_folders: Option<Vec<WorkspaceFolder>>,
   capabilities: Option<ClientCapabilities>,
-  not_handler: Option<fn(RawLSPNotification)>,
+  settings: Option<Value>,
+  language_id: Option<String>,
+  trace: Option<TraceValue>,
+  trace_file: Option<PathBuf>,
+}
+
+///Rust-analyzer answers initialize only after loading the workspace metadata
+const INIT_TIMEOUT: Duration = Duration::from_secs(120);
+
+///Everything needed to start the server again after it crashed
+#[derive(Debug)]
+pub(super) struct LSPConfig {
+  pub(super) transport: Transport,
+  ///Language of the opened documents, their extension if not set
+  pub(super) language_id: Option<String>,
+  workspace_folders: Vec<WorkspaceFolder>,
+  capabilities: ClientCapabilities,
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct LSPData {
-  pub(self) lsp_client: Arc<Mutex<LSPClient>>,
+  pub(super) lsp_client: Arc<Mutex<LSPClient>>,
   pub(super) file_patterns: Vec<Regex>,
-  pub(crate) lsp_info: Option<LSPInfo>,
+  ///Replaced by every initialize, so a restarted server is asked with its new capabilities
+  info: Arc<RwLock<Option<LSPInfo>>>,
+  pub(super) config: Arc<LSPConfig>,
+  notifications: NotificationBus,
+  handlers: RequestHandlers,
+  pub(super) traffic: TrafficLog,
 }
 
 impl LSPClient {
@@ -49,29 +100,36 @@ impl LSPClient {
   where
     T: LSPRequestTrait,
   {
-    self.stdin.write_all(req.as_bytes()?.as_slice())?;
-    self.stdin.flush()?;
-    Ok(())
+    self.write(req.as_bytes()?)
   }
 
-  pub(crate) fn resolve_pending(&mut self, id: i32, body: String) {
-    if let Some(request) = self.pending.iter_mut().find(|p| p.id == id) {
-      request.response = Some(body);
+  pub(crate) fn send_res(&mut self, res: RawLSPResponse) -> Result<(), Error> {
+    self.write(res.as_bytes()?)
+  }
 
-      let (lock, cvar) = &*request.condition;
-      let mut started = lock.lock().unwrap();
-      *started = true;
-      cvar.notify_one();
-    }
+  pub(crate) fn resolve_pending(&mut self, id: &NumberOrString, body: String) {
+    self.pending.resolve(id, body);
+  }
+
+  ///Replaces a crashed connection with a new one and returns its generation
+  pub(super) fn attach(&mut self, writer: MessageWriter, endpoint: Endpoint) -> u32 {
+    self.writer = writer;
+    self.endpoint = endpoint;
+    self.state = ServerState::Starting;
+    self.generation += 1;
+    self.generation
   }
 
   pub(crate) fn send_not<T>(&mut self, not: LSPNotification<T>) -> Result<(), Error>
   where
     T: LSPNotificationTrait,
   {
-    self.stdin.write_all(not.as_bytes()?.as_slice())?;
-    self.stdin.flush()?;
-    Ok(())
+    self.write(not.as_bytes()?)
+  }
+
+  fn write(&mut self, frame: Vec<u8>) -> Result<(), Error> {
+    self.traffic.record(Direction::Outgoing, &frame);
+    self.writer.send(&frame)
   }
 }
 
@@ -81,11 +139,39 @@ impl LSPClientBuilder {
     self
   }
 
+  ///Connects to an already running server instead of spawning one
+  pub(crate) fn tcp(mut self, address: String) -> Self {
+    self.tcp = Some(address);
+    self
+  }
+
+  ///Connects to an already running server instead of spawning one
+  #[cfg(unix)]
+  pub(crate) fn unix_socket(mut self, path: PathBuf) -> Self {
+    self.unix_socket = Some(path);
+    self
+  }
+
   pub(crate) fn args(mut self, args: Vec<String>) -> Self {
     self.args = Some(args);
     self
   }
 
+  pub(crate) fn cwd(mut self, cwd: PathBuf) -> Self {
+    self.cwd = Some(cwd);
+    self
+  }
+
+  pub(crate) fn env(mut self, env: Vec<(String, String)>) -> Self {
+    self.env = Some(env);
+    self
+  }
+
+  pub(crate) fn stderr_log(mut self, stderr_log: PathBuf) -> Self {
+    self.stderr_log = Some(stderr_log);
+    self
+  }
+
   pub(crate) fn file_patterns(mut self, file_patterns: Vec<String>) -> Self {
     self.file_patterns = Some(file_patterns);
     self
@@ -101,18 +187,51 @@ impl LSPClientBuilder {
     self
   }
 
-  pub(crate) fn not_handler(mut self, not_handler: fn(RawLSPNotification)) -> Self {
-    self.not_handler = Some(not_handler);
+  pub(crate) fn language_id(mut self, language_id: String) -> Self {
+    self.language_id = Some(language_id);
+    self
+  }
+
+  ///Trace level requested from the server, `off` by default
+  pub(crate) fn trace(mut self, trace: TraceValue) -> Self {
+    self.trace = Some(trace);
     self
   }
 
-  pub(crate) async fn build(self) -> Result<LSPData, Error> {
-    let path = self.path.ok_or(anyhow::anyhow!("No path"))?;
-    let args = self.args.unwrap_or_default();
+  ///Writes the recorded traffic to `trace_file` in addition to keeping it in memory
+  pub(crate) fn trace_file(mut self, trace_file: PathBuf) -> Self {
+    self.trace_file = Some(trace_file);
+    self
+  }
+
+  ///Settings returned for `workspace/configuration`, sections are looked up by their dotted path
+  pub(crate) fn settings(mut self, settings: Value) -> Self {
+    self.settings = Some(settings);
+    self
+  }
+
+  ///Transport of a server that is already running, if one was configured
+  fn socket_transport(&mut self) -> Option<Transport> {
+    if let Some(address) = self.tcp.take() {
+      return Some(Transport::Tcp(address));
+    }
+    #[cfg(unix)]
+    if let Some(path) = self.unix_socket.take() {
+      return Some(Transport::Unix(path));
+    }
+    None
+  }
+
+  pub(crate) async fn build(mut self) -> Result<LSPData, Error> {
+    let socket_transport = self.socket_transport();
     let file_patterns = self.file_patterns.ok_or(anyhow::anyhow!("No file patterns"))?;
     let workspace_folders = self.workspace_folders.ok_or(anyhow::anyhow!("No workspace folders"))?;
     let capabilities = self.capabilities.ok_or(anyhow::anyhow!("No capabilities"))?;
-    let not_handler = self.not_handler.unwrap_or(|_| ());
+    let settings = self.settings.unwrap_or(Value::Null);
+    let traffic = match &self.trace_file {
+      Some(trace_file) => TrafficLog::with_file(trace_file)?,
+      None => TrafficLog::default(),
+    };
 
     let mut file_patterns_reg: Vec<Regex> = Vec::new();
     for pattern in file_patterns {
@@ -120,178 +239,396 @@ impl LSPClientBuilder {
       file_patterns_reg.push(reg);
     }
 
-    let mut lsp = Command::new(path);
-
-    #[cfg(target_os = "windows")]
-    let lsp = lsp.creation_flags(0x08000000);
-
-    let mut lsp = lsp
-      .stdin(std::process::Stdio::piped())
-      .stdout(std::process::Stdio::piped())
-      .args(args)
-      .spawn()?;
-
-    let stdin = lsp
-      .stdin
-      .take()
-      .ok_or(anyhow::anyhow!("failed to open stdin"))?;
+    let transport = match socket_transport {
+      Some(transport) => transport,
+      None => {
+        let path = self.path.ok_or(anyhow::anyhow!("No path"))?;
+        let stderr_log = self.stderr_log.unwrap_or_else(|| default_stderr_log(&path));
+
+        //default to the first workspace folder so relative paths in the server config resolve
+        let cwd = self.cwd.or_else(|| {
+          workspace_folders
+            .first()
+            .and_then(|folder| folder.uri.to_file_path().ok())
+        });
+
+        Transport::Stdio(SpawnOptions {
+          path,
+          args: self.args.unwrap_or_default(),
+          cwd,
+          env: self.env.unwrap_or_default(),
+          stderr_log,
+        })
+      }
+    };
 
-    let stdout = lsp
-      .stdout
-      .take()
-      .ok_or(anyhow::anyhow!("failed to open stdout"))?;
-
-    let working_token = String::from("init_token");
+    let Connection {
+      writer,
+      reader,
+      endpoint,
+    } = transport.connect().await?;
 
     let lsp_client = LSPClient {
-      stdin,
-      pending: Vec::new(),
+      writer,
+      endpoint,
+      pending: PendingRequests::default(),
+      state: ServerState::Starting,
+      generation: 0,
+      documents: HashMap::new(),
+      traffic: traffic.clone(),
+      trace: self.trace.unwrap_or_default(),
     };
 
-    let mut lsp = LSPData {
+    let lsp = LSPData {
       lsp_client: Arc::new(Mutex::new(lsp_client)),
       file_patterns: file_patterns_reg,
-      lsp_info: None,
+      info: Arc::new(RwLock::new(None)),
+      config: Arc::new(LSPConfig {
+        transport,
+        language_id: self.language_id,
+        workspace_folders: workspace_folders.clone(),
+        capabilities,
+      }),
+      notifications: NotificationBus::default(),
+      handlers: RequestHandlers::new(workspace_folders, settings),
+      traffic,
     };
 
-    let lsp_client = lsp.lsp_client.clone();
-    thread::spawn(move || {
-      let mut reader = BufReader::new(stdout);
+    lsp.listen(reader, 0);
+    lsp.initialize().await?;
 
-      while let Ok(Some(msg_string)) = read_msg(&mut reader) {
+    Ok(lsp)
+  }
+}
+
+impl LSPData {
+  pub(crate) fn create() -> LSPClientBuilder {
+    LSPClientBuilder {
+      path: None,
+      tcp: None,
+      #[cfg(unix)]
+      unix_socket: None,
+      args: None,
+      cwd: None,
+      env: None,
+      stderr_log: None,
+      file_patterns: None,
+      workspace_folders: None,
+      capabilities: None,
+      settings: None,
+      language_id: None,
+      trace: None,
+      trace_file: None,
+    }
+  }
+
+  ///Reads the messages of the connection made as `generation` until it closes
+  pub(super) fn listen(&self, mut reader: MessageReader, generation: u32) {
+    let lsp = self.clone();
+    thread::spawn(move || {
+      while let Ok(Some(msg_string)) = reader.read_msg() {
+        lsp
+          .traffic
+          .record(Direction::Incoming, msg_string.as_bytes());
         let msg: LSPMessage = match serde_json::from_str(&msg_string) {
           Ok(msg) => msg,
           Err(_) => continue,
         };
 
-        if msg.is_response() {
-          let mut lsp = lsp_client.lock().unwrap();
-          lsp.resolve_pending(msg.get_id().unwrap(), msg_string);
+        if msg.is_request() {
+          let req: RawLSPRequest = match serde_json::from_str(&msg_string) {
+            Ok(req) => req,
+            Err(_) => continue,
+          };
+
+          let handlers = lsp.handlers.clone();
+          let lsp_client = lsp.lsp_client.clone();
+          async_std::task::spawn(async move {
+            let res = handlers.handle(req).await;
+            if let Err(err) = lsp_client.lock().unwrap().send_res(res) {
+              eprintln!("Error answering server request: {}", err);
+            }
+          });
+        } else if msg.is_response() {
+          if let Some(id) = msg.get_id() {
+            let mut lsp = lsp.lsp_client.lock().unwrap();
+            lsp.resolve_pending(id, msg_string);
+          }
         } else {
           let not: RawLSPNotification = match serde_json::from_str(&msg_string) {
             Ok(not) => not,
             Err(_) => continue,
           };
 
-          thread::spawn(move || not_handler(not));
+          lsp.notifications.publish(not);
         }
       }
+
+      lsp.on_exit(generation);
     });
+  }
+
+  ///Runs the initialize handshake and opens the documents that were open before a restart. The
+  ///capabilities of the server replace the ones of a previous instance.
+  pub(super) async fn initialize(&self) -> Result<(), Error> {
+    let working_token = String::from("init_token");
+    let trace = self.lsp_client.lock().unwrap().trace;
 
     let params = InitializeParams {
       process_id: Some(std::process::id()),
-      workspace_folders: Some(workspace_folders),
-      capabilities,
+      workspace_folders: Some(self.config.workspace_folders.clone()),
+      capabilities: self.config.capabilities.clone(),
+      trace: Some(trace),
       work_done_progress_params: lsp_types::WorkDoneProgressParams {
         work_done_token: Some(NumberOrString::String(working_token)),
       },
       ..Default::default()
     };
 
-    //wait for listener to start
-    sleep(std::time::Duration::from_millis(1));
-
-    let init: LSPRequest<Initialize> = LSPRequest::new(Some(params.clone()));
-    let init_res = lsp.send_req(init).await?;
+    let init: LSPRequest<Initialize> = LSPRequest::new(Some(params)).with_timeout(INIT_TIMEOUT);
+    let init_res = self.send_req(init)?.response().await?;
 
     let lsp_info = LSPInfo::new(init_res.ok_or(anyhow::anyhow!("No Init Response"))?)?;
-    println!("{:?}", lsp_info);
-    lsp.lsp_info = Some(lsp_info);
 
     let inited: LSPNotification<Initialized> = LSPNotification::new(None)?;
+    let mut lsp = self.lsp_client.lock().unwrap();
     lsp.send_not(inited)?;
 
-    Ok(lsp)
+    let documents: Vec<TextDocumentItem> = match lsp_info.sync.open_close {
+      true => lsp.documents.values().cloned().collect(),
+      false => Vec::new(),
+    };
+    for text_document in documents {
+      let open: LSPNotification<DidOpenTextDocument> =
+        LSPNotification::new(Some(DidOpenTextDocumentParams { text_document }))?;
+      lsp.send_not(open)?;
+    }
+    *self.info.write().unwrap() = Some(lsp_info);
+    lsp.state = ServerState::Running;
+
+    Ok(())
   }
-}
 
-fn read_msg(reader: &mut BufReader<std::process::ChildStdout>) -> Result<Option<String>, Error> {
-  let mut size = None;
-  let mut buf = String::new();
-  loop {
-    buf.clear();
-    if reader.read_line(&mut buf)? == 0 {
-      return Ok(None);
-    }
-    if !buf.ends_with("\r\n") {
-      return Err(anyhow::anyhow!("Malformed header: {:?}", buf));
-    }
+  ///Capabilities of the running server instance, `None` before it was initialized
+  pub(crate) fn lsp_info(&self) -> Option<LSPInfo> {
+    self.info.read().unwrap().clone()
+  }
+
+  pub(crate) fn send_req<T>(&self, req: LSPRequest<T>) -> Result<RequestHandle<T>, Error>
+  where
+    T: LSPRequestTrait,
+  {
+    let id = req.get_id();
+    let timeout = req.get_timeout();
 
-    let buf = &buf[..buf.len() - 2];
-    if buf.is_empty() {
-      break;
+    let mut lsp = self.lsp_client.lock().unwrap();
+    if matches!(lsp.state, ServerState::Crashed | ServerState::Stopped) {
+      return Err(anyhow::anyhow!("Language server is not running"));
     }
-    let mut parts = buf.splitn(2, ": ");
-    let key = parts.next().unwrap();
-    let value = parts
-      .next()
-      .ok_or_else(|| anyhow::anyhow!("Malformed header: {:?}", buf))?;
-
-    if key.eq_ignore_ascii_case("Content-Length") {
-      size = Some(value.parse::<usize>()?);
+
+    let receiver = lsp.pending.insert(id.clone());
+    if let Err(err) = lsp.send_req(req) {
+      lsp.pending.remove(&id);
+      return Err(err);
     }
+
+    Ok(RequestHandle::new(self.clone(), id, timeout, receiver))
   }
-  let size = size.ok_or_else(|| anyhow::anyhow!("Missing Content-Length header"))?;
-  let mut buf = buf.into_bytes();
-  buf.resize(size, 0);
-  reader.read_exact(&mut buf)?;
-  let buf = String::from_utf8(buf)?;
 
-  Ok(Some(buf))
-}
+  ///Sends a request and waits for its result. Error responses are returned as the `LSPError` of
+  ///the response, so callers can tell them apart by their code.
+  pub(crate) async fn request<T>(&self, params: T::Params) -> Result<T::Result, Error>
+  where
+    T: LSPRequestTrait,
+  {
+    let res = self
+      .send_req(LSPRequest::<T>::new(Some(params)))?
+      .response()
+      .await?
+      .ok_or(anyhow::anyhow!("No response to {}", T::METHOD))?;
 
-impl LSPData {
-  pub(crate) fn create() -> LSPClientBuilder {
-    LSPClientBuilder {
-      path: None,
-      args: None,
-      file_patterns: None,
-      workspace_folders: None,
-      capabilities: None,
-      not_handler: None,
+    res.result.map_err(Error::from)
+  }
+
+  pub(crate) fn cancel_req(&self, id: &NumberOrString) -> Result<(), Error> {
+    let mut lsp = self.lsp_client.lock().unwrap();
+    if !lsp.pending.cancel(id) {
+      return Ok(());
     }
+
+    let cancel: LSPNotification<Cancel> =
+      LSPNotification::new(Some(CancelParams { id: id.clone() }))?;
+    lsp.send_not(cancel)
   }
 
-  pub(crate) async fn send_req<T>(
-    &self,
-    req: LSPRequest<T>,
-  ) -> Result<Option<LSPResponse<T>>, Error>
+  pub(crate) fn send_not<T>(&self, not: LSPNotification<T>) -> Result<(), Error>
   where
-    T: LSPRequestTrait,
+    T: LSPNotificationTrait,
   {
     let mut lsp = self.lsp_client.lock().unwrap();
-    let id = req.get_id();
+    lsp.send_not(not)
+  }
+
+  ///Opens the document on the server, it is opened again whenever the server restarts
+  pub(crate) fn open_document(&self, text_document: TextDocumentItem) -> Result<(), Error> {
+    let mut lsp = self.lsp_client.lock().unwrap();
+    lsp
+      .documents
+      .insert(text_document.uri.clone(), text_document.clone());
+    if lsp.state != ServerState::Running || !self.sync().open_close {
+      return Ok(());
+    }
 
-    let res = PendingRequest {
-      id,
-      condition: Arc::new((Mutex::new(false), Condvar::new())),
-      response: None,
+    let open: LSPNotification<DidOpenTextDocument> =
+      LSPNotification::new(Some(DidOpenTextDocumentParams { text_document }))?;
+    lsp.send_not(open)
+  }
+
+  ///Sends `edits` in the order they were applied, or the full `text` if the server wants that
+  pub(crate) fn change_document(
+    &self,
+    uri: &Url,
+    version: i32,
+    edits: Vec<TextEdit>,
+    text: String,
+  ) -> Result<(), Error> {
+    let mut lsp = self.lsp_client.lock().unwrap();
+    let Some(document) = lsp.documents.get_mut(uri) else {
+      return Err(anyhow::anyhow!("Document {} is not open", uri));
+    };
+    document.version = version;
+    document.text = text;
+
+    let content_changes = match self.sync().change {
+      TextDocumentSyncKind::FULL => vec![TextDocumentContentChangeEvent {
+        range: None,
+        range_length: None,
+        text: document.text.clone(),
+      }],
+      TextDocumentSyncKind::INCREMENTAL => edits
+        .into_iter()
+        .map(|edit| TextDocumentContentChangeEvent {
+          range: Some(edit.range),
+          range_length: None,
+          text: edit.new_text,
+        })
+        .collect(),
+      _ => return Ok(()),
     };
+    if lsp.state != ServerState::Running {
+      return Ok(());
+    }
+
+    let change: LSPNotification<DidChangeTextDocument> =
+      LSPNotification::new(Some(DidChangeTextDocumentParams {
+        text_document: VersionedTextDocumentIdentifier {
+          uri: uri.clone(),
+          version,
+        },
+        content_changes,
+      }))?;
+    lsp.send_not(change)
+  }
 
-    lsp.pending.push(res);
-    lsp.send_req(req)?;
+  pub(crate) fn save_document(&self, uri: &Url) -> Result<(), Error> {
+    let mut lsp = self.lsp_client.lock().unwrap();
+    let Some(include_text) = self.sync().save else {
+      return Ok(());
+    };
+    let Some(document) = lsp.documents.get(uri) else {
+      return Err(anyhow::anyhow!("Document {} is not open", uri));
+    };
+    let text = include_text.then(|| document.text.clone());
+    if lsp.state != ServerState::Running {
+      return Ok(());
+    }
 
-    let (lock, cvar) = &*lsp.pending.last().unwrap().condition.clone();
-    let mut started = lock.lock().unwrap();
-    drop(lsp);
+    let save: LSPNotification<DidSaveTextDocument> =
+      LSPNotification::new(Some(DidSaveTextDocumentParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        text,
+      }))?;
+    lsp.send_not(save)
+  }
 
-    while !*started {
-      started = cvar.wait(started).unwrap();
+  pub(crate) fn close_document(&self, uri: Url) -> Result<(), Error> {
+    let mut lsp = self.lsp_client.lock().unwrap();
+    if lsp.documents.remove(&uri).is_none()
+      || lsp.state != ServerState::Running
+      || !self.sync().open_close
+    {
+      return Ok(());
     }
 
+    let close: LSPNotification<DidCloseTextDocument> =
+      LSPNotification::new(Some(DidCloseTextDocumentParams {
+        text_document: TextDocumentIdentifier { uri },
+      }))?;
+    lsp.send_not(close)
+  }
+
+  ///Uris of the documents currently open on the server
+  pub(crate) fn open_documents(&self) -> Vec<Url> {
     let lsp = self.lsp_client.lock().unwrap();
-    let res = lsp.pending.iter().find(|p| p.id == id).unwrap();
-    let response = res.response.clone().unwrap_or_default();
-    let response = LSPResponse::new(response.clone())?;
+    lsp.documents.keys().cloned().collect()
+  }
 
-    Ok(response)
+  pub(crate) fn document_version(&self, uri: &Url) -> Option<i32> {
+    let lsp = self.lsp_client.lock().unwrap();
+    lsp.documents.get(uri).map(|document| document.version)
   }
 
-  pub(crate) fn send_not<T>(&self, not: LSPNotification<T>) -> Result<(), Error>
+  ///Whether the server is responsible for the file at `uri`
+  pub(crate) fn matches(&self, uri: &Url) -> bool {
+    self
+      .file_patterns
+      .iter()
+      .any(|pattern| pattern.is_match(uri.as_str()))
+  }
+
+  fn sync(&self) -> SyncInfo {
+    self.lsp_info().map(|info| info.sync).unwrap_or_default()
+  }
+
+  ///Changes the trace level with `$/setTrace`, the output arrives as `$/logTrace`
+  pub(crate) fn set_trace(&self, value: TraceValue) -> Result<(), Error> {
+    let mut lsp = self.lsp_client.lock().unwrap();
+    lsp.trace = value;
+    if lsp.state != ServerState::Running {
+      return Ok(());
+    }
+
+    let set_trace: LSPNotification<SetTrace> =
+      LSPNotification::new(Some(SetTraceParams { value }))?;
+    lsp.send_not(set_trace)
+  }
+
+  pub(crate) fn traffic(&self) -> Vec<TrafficEntry> {
+    self.traffic.entries()
+  }
+
+  ///Replaces the handler for requests of type `T` sent by the server
+  pub(crate) fn on_request<T, F, Fut>(&self, handler: F)
+  where
+    T: LSPRequestTrait + 'static,
+    F: Fn(T::Params) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<T::Result, LSPError>> + Send + 'static,
+  {
+    self.handlers.on::<T, F, Fut>(handler);
+  }
+
+  ///Notifications of type `T` of this server
+  pub(crate) fn subscribe<T>(&self) -> Subscription<T>
   where
     T: LSPNotificationTrait,
   {
-    let mut lsp = self.lsp_client.lock().unwrap();
-    lsp.send_not(not)
+    let (subscriber, subscription) = Subscription::new();
+    self.notifications.add_subscriber(&subscriber);
+    subscription
+  }
+
+  ///Delivers the notifications of this server to a subscriber that other servers share
+  pub(crate) fn share_subscriber(&self, subscriber: &Subscriber) {
+    self.notifications.add_subscriber(subscriber);
   }
 }
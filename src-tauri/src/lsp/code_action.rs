@@ -0,0 +1,179 @@
+use anyhow::Error;
+use lsp_types::{
+  request::{CodeActionRequest, CodeActionResolveRequest, Request},
+  CodeAction, CodeActionContext, CodeActionKind, CodeActionOrCommand, CodeActionParams,
+  CodeActionTriggerKind, Diagnostic, Range, TextDocumentIdentifier, Url,
+};
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::file::workspace_edit::AnnotatedWorkspaceEdit;
+
+use super::client::LSPData;
+
+///Code actions of a range, kept so the chosen one can be resolved and applied by its id
+#[derive(Debug, Clone)]
+pub(crate) struct CodeActions {
+  uri: Url,
+  ///Commands sent instead of actions are turned into actions running them
+  actions: Vec<ServerCodeAction>,
+}
+
+///Action of the server with the annotations of the text edits of its edit
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ServerCodeAction {
+  pub(crate) action: CodeAction,
+  pub(crate) edit: Option<AnnotatedWorkspaceEdit>,
+}
+
+///`textDocument/codeAction` keeping the annotations of the edits
+enum AnnotatedCodeActionRequest {}
+
+impl Request for AnnotatedCodeActionRequest {
+  type Params = CodeActionParams;
+  type Result = Option<Vec<ServerCodeAction>>;
+  const METHOD: &'static str = CodeActionRequest::METHOD;
+}
+
+///`codeAction/resolve` keeping the annotations of the edit
+enum AnnotatedCodeActionResolveRequest {}
+
+impl Request for AnnotatedCodeActionResolveRequest {
+  type Params = CodeAction;
+  type Result = ServerCodeAction;
+  const METHOD: &'static str = CodeActionResolveRequest::METHOD;
+}
+
+///Action shown in the lightbulb menu, `id` is used to apply it
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct CodeActionEntry {
+  pub(crate) id: usize,
+  #[serde(flatten)]
+  pub(crate) action: CodeAction,
+}
+
+impl CodeActions {
+  ///Actions for `range` fixing `diagnostics`, only the kinds in `only` are kept if it is set
+  pub(crate) async fn request(
+    lsp: &LSPData,
+    uri: Url,
+    range: Range,
+    diagnostics: Vec<Diagnostic>,
+    only: Option<Vec<CodeActionKind>>,
+  ) -> Result<Self, Error> {
+    let response = lsp
+      .request::<AnnotatedCodeActionRequest>(CodeActionParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        range,
+        context: CodeActionContext {
+          diagnostics,
+          only: only.clone(),
+          trigger_kind: Some(CodeActionTriggerKind::INVOKED),
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+      })
+      .await?;
+
+    let actions = response
+      .unwrap_or_default()
+      .into_iter()
+      //servers are free to ignore `only`
+      .filter(|action| {
+        only
+          .as_ref()
+          .is_none_or(|only| only.iter().any(|kind| has_kind(&action.action, kind)))
+      })
+      .collect();
+
+    Ok(CodeActions { uri, actions })
+  }
+
+  pub(crate) fn uri(&self) -> &Url {
+    &self.uri
+  }
+
+  ///Preferred actions come first, the others keep the order of the server
+  pub(crate) fn entries(&self) -> Vec<CodeActionEntry> {
+    let mut entries: Vec<CodeActionEntry> = self
+      .actions
+      .iter()
+      .enumerate()
+      .map(|(id, action)| CodeActionEntry {
+        id,
+        action: action.action.clone(),
+      })
+      .collect();
+    entries.sort_by_key(|entry| entry.action.is_preferred != Some(true));
+    entries
+  }
+
+  ///Resolves the edit of the action if it has none yet and the server supports it
+  pub(crate) async fn resolve(
+    &mut self,
+    lsp: &LSPData,
+    id: usize,
+  ) -> Result<ServerCodeAction, Error> {
+    let action = self
+      .actions
+      .get_mut(id)
+      .ok_or(anyhow::anyhow!("No code action {}", id))?;
+    if let Some(disabled) = &action.action.disabled {
+      return Err(anyhow::anyhow!(
+        "{} is disabled: {}",
+        action.action.title,
+        disabled.reason
+      ));
+    }
+
+    let resolve = lsp.lsp_info().is_some_and(|info| info.code_action_resolve);
+    if action.edit.is_none() && resolve {
+      *action = lsp
+        .request::<AnnotatedCodeActionResolveRequest>(action.action.clone())
+        .await?;
+    }
+    Ok(action.clone())
+  }
+}
+
+impl<'de> Deserialize<'de> for ServerCodeAction {
+  fn deserialize<D: Deserializer<'de>>(des: D) -> Result<Self, D::Error> {
+    let value = Value::deserialize(des)?;
+    let edit = match value.get("edit").filter(|edit| !edit.is_null()) {
+      Some(edit) => Some(AnnotatedWorkspaceEdit::deserialize(edit).map_err(de::Error::custom)?),
+      None => None,
+    };
+
+    let action = match serde_json::from_value(value).map_err(de::Error::custom)? {
+      CodeActionOrCommand::CodeAction(action) => action,
+      CodeActionOrCommand::Command(command) => CodeAction {
+        title: command.title.clone(),
+        command: Some(command),
+        ..Default::default()
+      },
+    };
+    Ok(ServerCodeAction { action, edit })
+  }
+}
+
+impl Serialize for ServerCodeAction {
+  fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+    let mut value = serde_json::to_value(&self.action).map_err(ser::Error::custom)?;
+    if let (Value::Object(action), Some(edit)) = (&mut value, &self.edit) {
+      let edit = serde_json::to_value(edit).map_err(ser::Error::custom)?;
+      action.insert("edit".to_string(), edit);
+    }
+    value.serialize(ser)
+  }
+}
+
+///`refactor` covers `refactor.extract`, actions without a kind are never kept by a filter
+fn has_kind(action: &CodeAction, kind: &CodeActionKind) -> bool {
+  action.kind.as_ref().is_some_and(|action_kind| {
+    let action_kind = action_kind.as_str();
+    action_kind == kind.as_str()
+      || action_kind
+        .strip_prefix(kind.as_str())
+        .is_some_and(|rest| rest.starts_with('.'))
+  })
+}
@@ -0,0 +1,94 @@
+use anyhow::Error;
+use lsp_types::{
+  request::{CodeLensRefresh, CodeLensRequest, CodeLensResolve},
+  CodeLens, CodeLensParams, Command, Range, TextDocumentIdentifier,
+};
+use serde::Serialize;
+use tauri::Url;
+
+use super::{
+  client::LSPData,
+  document_store::{DocumentStore, Refresh},
+};
+
+///Emitted with the files whose code lenses are outdated, they have to be requested again
+pub(crate) const CODE_LENS_REFRESH_EVENT: &str = "lsp-code-lens-refresh";
+
+///Last lenses of every document, kept so they can be resolved once shown and run by their id
+pub(crate) type CodeLensStore = DocumentStore<Vec<CodeLens>, CodeLensRefresh>;
+
+impl Refresh for CodeLensRefresh {
+  const EVENT: &'static str = CODE_LENS_REFRESH_EVENT;
+}
+
+///Lens shown above a line, without a command until it is resolved
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct CodeLensEntry {
+  pub(crate) id: usize,
+  pub(crate) range: Range,
+  pub(crate) command: Option<Command>,
+}
+
+impl CodeLensStore {
+  ///Lenses of the document, they replace the stored lenses
+  pub(crate) async fn request(
+    &self,
+    lsp: &LSPData,
+    uri: &Url,
+  ) -> Result<Vec<CodeLensEntry>, Error> {
+    if lsp.lsp_info().and_then(|info| info.code_lens).is_none() {
+      return Ok(Vec::new());
+    }
+
+    let lenses = lsp
+      .request::<CodeLensRequest>(CodeLensParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+      })
+      .await?
+      .unwrap_or_default();
+
+    let entries = lenses
+      .iter()
+      .enumerate()
+      .map(|(id, lens)| entry(id, lens))
+      .collect();
+    self.insert(uri.clone(), lenses);
+    Ok(entries)
+  }
+
+  ///The lens with its command, lenses sent without one are resolved by the server
+  pub(crate) async fn resolve(
+    &self,
+    lsp: &LSPData,
+    uri: &Url,
+    id: usize,
+  ) -> Result<CodeLensEntry, Error> {
+    let lens = self
+      .update(uri, |lenses| lenses.get(id).cloned())
+      .flatten()
+      .ok_or(anyhow::anyhow!("No code lens {}", id))?;
+    let resolve = lsp.lsp_info().and_then(|info| info.code_lens) == Some(true);
+    if lens.command.is_some() || !resolve {
+      return Ok(entry(id, &lens));
+    }
+
+    let resolved = lsp.request::<CodeLensResolve>(lens.clone()).await?;
+    //the lenses may have been replaced while the request was running
+    self.update(uri, |lenses| {
+      if let Some(stored) = lenses.get_mut(id).filter(|stored| **stored == lens) {
+        *stored = resolved.clone();
+      }
+    });
+    Ok(entry(id, &resolved))
+  }
+}
+
+fn entry(id: usize, lens: &CodeLens) -> CodeLensEntry {
+  CodeLensEntry {
+    id,
+    range: lens.range,
+    command: lens.command.clone(),
+  }
+}
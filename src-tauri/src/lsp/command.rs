@@ -0,0 +1,45 @@
+use std::{
+  collections::HashMap,
+  fmt,
+  sync::{Arc, Mutex},
+};
+
+use anyhow::Error;
+use lsp_types::Command;
+use serde_json::Value;
+
+type CommandHandler = Arc<dyn Fn(Command) -> Result<Option<Value>, Error> + Send + Sync>;
+
+///Commands the editor runs itself instead of sending them to the server, like the
+///`rust-analyzer.runSingle` command of code lenses
+#[derive(Clone, Default)]
+pub(crate) struct CommandRegistry {
+  handlers: Arc<Mutex<HashMap<String, CommandHandler>>>,
+}
+
+impl CommandRegistry {
+  ///Replaces the handler of `command`
+  pub(crate) fn register<F>(&self, command: &str, handler: F)
+  where
+    F: Fn(Command) -> Result<Option<Value>, Error> + Send + Sync + 'static,
+  {
+    self
+      .handlers
+      .lock()
+      .unwrap()
+      .insert(command.to_string(), Arc::new(handler));
+  }
+
+  pub(crate) fn get(&self, command: &str) -> Option<CommandHandler> {
+    self.handlers.lock().unwrap().get(command).cloned()
+  }
+}
+
+impl fmt::Debug for CommandRegistry {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let commands: Vec<String> = self.handlers.lock().unwrap().keys().cloned().collect();
+    f.debug_struct("CommandRegistry")
+      .field("commands", &commands)
+      .finish()
+  }
+}
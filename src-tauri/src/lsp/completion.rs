@@ -0,0 +1,530 @@
+use anyhow::Error;
+use lsp_types::{
+  request::{Completion, ResolveCompletionItem},
+  CompletionContext, CompletionItem, CompletionParams, CompletionResponse, CompletionTextEdit,
+  CompletionTriggerKind, InsertTextFormat, Position, PositionEncodingKind, Range,
+  TextDocumentIdentifier, TextDocumentPositionParams, TextEdit,
+};
+use serde::Serialize;
+use tauri::Url;
+
+use super::{
+  client::LSPData,
+  encoding::{byte_offset, encoded_len},
+};
+
+///Items of the last completion, kept to resolve, accept and filter them while typing
+#[derive(Debug, Clone)]
+pub(crate) struct CompletionSession {
+  uri: Url,
+  ///Start of the word that is completed, typing inside it keeps the session
+  word_start: Position,
+  ///Set if typing has to ask the server again instead of filtering the items
+  is_incomplete: bool,
+  items: Vec<CompletionItem>,
+  resolved: Vec<bool>,
+  ///Positions of the server are counted in its encoding
+  encoding: Option<PositionEncodingKind>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct CompletionEntry {
+  ///Index used to resolve or accept the item
+  id: usize,
+  #[serde(flatten)]
+  item: CompletionItem,
+}
+
+///Edits of an accepted item and the ranges to select afterwards, `$0` comes last
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct AcceptedCompletion {
+  pub(crate) edits: Vec<TextEdit>,
+  pub(crate) tabstops: Vec<Vec<Range>>,
+}
+
+///Text of an expanded snippet with the byte ranges of every tabstop, ordered like they are visited
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Snippet {
+  pub(crate) text: String,
+  pub(crate) tabstops: Vec<Vec<(usize, usize)>>,
+}
+
+struct SnippetParser {
+  chars: Vec<char>,
+  pos: usize,
+  text: String,
+  ///Index, start and end of every tabstop in the order they were found
+  stops: Vec<(u32, usize, usize)>,
+}
+
+impl CompletionSession {
+  ///`trigger` is the character that was typed, `None` if the completion was invoked explicitly.
+  ///Returns `None` if there is nothing to complete.
+  pub(crate) async fn start(
+    lsp: &LSPData,
+    previous: Option<CompletionSession>,
+    uri: Url,
+    position: Position,
+    trigger: Option<&str>,
+    line: &str,
+  ) -> Result<Option<Self>, Error> {
+    let Some(info) = lsp.lsp_info().and_then(|info| info.completion_info) else {
+      return Ok(None);
+    };
+
+    let encoding = lsp.lsp_info().and_then(|info| info.position_encoding);
+    let word_start = word_start(line, position, encoding.as_ref());
+    let previous =
+      previous.filter(|session| session.uri == uri && session.word_start == word_start);
+    let (trigger_kind, trigger_character) = match trigger {
+      Some(trigger) if info.trigger_characters.iter().any(|c| c == trigger) => (
+        CompletionTriggerKind::TRIGGER_CHARACTER,
+        Some(trigger.to_string()),
+      ),
+      Some(trigger) if is_word(trigger) => match previous {
+        //complete lists are only filtered while typing
+        Some(session) if !session.is_incomplete => return Ok(Some(session)),
+        Some(_) => (
+          CompletionTriggerKind::TRIGGER_FOR_INCOMPLETE_COMPLETIONS,
+          None,
+        ),
+        None => (CompletionTriggerKind::INVOKED, None),
+      },
+      Some(_) => return Ok(None),
+      None => match previous {
+        Some(session) if session.is_incomplete => (
+          CompletionTriggerKind::TRIGGER_FOR_INCOMPLETE_COMPLETIONS,
+          None,
+        ),
+        _ => (CompletionTriggerKind::INVOKED, None),
+      },
+    };
+
+    let response = lsp
+      .request::<Completion>(CompletionParams {
+        text_document_position: TextDocumentPositionParams {
+          text_document: TextDocumentIdentifier { uri: uri.clone() },
+          position,
+        },
+        context: Some(CompletionContext {
+          trigger_kind,
+          trigger_character,
+        }),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+      })
+      .await?;
+    let (is_incomplete, items) = match response {
+      Some(CompletionResponse::Array(items)) => (false, items),
+      Some(CompletionResponse::List(list)) => (list.is_incomplete, list.items),
+      None => (false, Vec::new()),
+    };
+
+    Ok(Some(Self {
+      uri,
+      word_start,
+      is_incomplete,
+      resolved: vec![false; items.len()],
+      items,
+      encoding,
+    }))
+  }
+
+  pub(crate) fn uri(&self) -> &Url {
+    &self.uri
+  }
+
+  ///Items matching the word typed so far, ordered by their sort text
+  pub(crate) fn entries(&self, line: &str, position: Position) -> Vec<CompletionEntry> {
+    let encoding = self.encoding.as_ref();
+    let word = match position.line == self.word_start.line {
+      true => byte_offset(line, self.word_start.character, encoding)
+        .zip(byte_offset(line, position.character, encoding))
+        .and_then(|(start, end)| line.get(start..end))
+        .unwrap_or_default(),
+      false => "",
+    };
+
+    let mut entries: Vec<CompletionEntry> = self
+      .items
+      .iter()
+      .enumerate()
+      .filter(|(_, item)| matches_word(item.filter_text.as_ref().unwrap_or(&item.label), word))
+      .map(|(id, item)| CompletionEntry {
+        id,
+        item: item.clone(),
+      })
+      .collect();
+    entries.sort_by(|a, b| {
+      let key = |item: &CompletionItem| item.sort_text.clone().unwrap_or(item.label.clone());
+      key(&a.item).cmp(&key(&b.item))
+    });
+    entries
+  }
+
+  ///Fills in the details of an item, resolved items are only requested once
+  pub(crate) async fn resolve(
+    &mut self,
+    lsp: &LSPData,
+    id: usize,
+  ) -> Result<CompletionItem, Error> {
+    let item = self
+      .items
+      .get(id)
+      .ok_or(anyhow::anyhow!("Unknown completion item {}", id))?;
+    let resolve = lsp
+      .lsp_info()
+      .and_then(|info| info.completion_info)
+      .is_some_and(|info| info.resolve);
+    if self.resolved[id] || !resolve {
+      return Ok(item.clone());
+    }
+
+    let item = lsp.request::<ResolveCompletionItem>(item.clone()).await?;
+    self.items[id] = item.clone();
+    self.resolved[id] = true;
+    Ok(item)
+  }
+
+  ///Edits inserting the item with the cursor at `position`
+  pub(crate) async fn accept(
+    &mut self,
+    lsp: &LSPData,
+    id: usize,
+    position: Position,
+  ) -> Result<AcceptedCompletion, Error> {
+    let item = self.resolve(lsp, id).await?;
+
+    let (mut range, text) = match item.text_edit {
+      Some(CompletionTextEdit::Edit(edit)) => (edit.range, edit.new_text),
+      Some(CompletionTextEdit::InsertAndReplace(edit)) => (edit.replace, edit.new_text),
+      None => (
+        Range::new(self.word_start, position),
+        item.insert_text.unwrap_or(item.label),
+      ),
+    };
+    //characters typed after the completion was requested are replaced as well
+    if range.end.line == position.line && range.end < position {
+      range.end = position;
+    }
+
+    let snippet = match item.insert_text_format {
+      Some(InsertTextFormat::SNIPPET) => Snippet::parse(&text),
+      _ => Snippet::plain(text),
+    };
+
+    //the additional edits may not overlap the main edit, so the ones before it move it
+    let mut edits = item.additional_text_edits.unwrap_or_default();
+    edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start));
+    let start = edits
+      .iter()
+      .filter(|edit| edit.range.end <= range.start)
+      .fold(range.start, |position, edit| {
+        shift(position, edit, self.encoding.as_ref())
+      });
+
+    let tabstops = snippet
+      .tabstops
+      .iter()
+      .map(|ranges| {
+        ranges
+          .iter()
+          .map(|&(from, to)| {
+            Range::new(
+              position_in(start, &snippet.text, from, self.encoding.as_ref()),
+              position_in(start, &snippet.text, to, self.encoding.as_ref()),
+            )
+          })
+          .collect()
+      })
+      .collect();
+
+    edits.push(TextEdit::new(range, snippet.text));
+    Ok(AcceptedCompletion { edits, tabstops })
+  }
+}
+
+impl Snippet {
+  ///Text inserted as is, the cursor ends up behind it
+  pub(crate) fn plain(text: String) -> Self {
+    let end = text.len();
+    Snippet {
+      text,
+      tabstops: vec![vec![(end, end)]],
+    }
+  }
+
+  ///Expands tabstops, placeholders, choices and variables. Variables are not known to the editor,
+  ///so they are replaced with their default. Invalid constructs are kept as text.
+  pub(crate) fn parse(snippet: &str) -> Self {
+    let mut parser = SnippetParser {
+      chars: snippet.chars().collect(),
+      pos: 0,
+      text: String::new(),
+      stops: Vec::new(),
+    };
+    parser.parse(false);
+
+    let SnippetParser {
+      text, mut stops, ..
+    } = parser;
+    if !stops.iter().any(|(index, ..)| *index == 0) {
+      stops.push((0, text.len(), text.len()));
+    }
+
+    let mut indices: Vec<u32> = stops.iter().map(|(index, ..)| *index).collect();
+    indices.sort_by_key(|index| (*index == 0, *index));
+    indices.dedup();
+    let tabstops = indices
+      .into_iter()
+      .map(|index| {
+        stops
+          .iter()
+          .filter(|stop| stop.0 == index)
+          .map(|&(_, start, end)| (start, end))
+          .collect()
+      })
+      .collect();
+
+    Snippet { text, tabstops }
+  }
+}
+
+impl SnippetParser {
+  ///Parses until the end or the `}` closing a placeholder, returns if the `}` was found
+  fn parse(&mut self, nested: bool) -> bool {
+    while let Some(c) = self.next() {
+      match c {
+        '\\' => match self.peek() {
+          Some(escaped @ ('$' | '}' | '\\')) => {
+            self.pos += 1;
+            self.text.push(escaped);
+          }
+          _ => self.text.push('\\'),
+        },
+        '}' if nested => return true,
+        '$' => self.dollar(),
+        c => self.text.push(c),
+      }
+    }
+    false
+  }
+
+  fn dollar(&mut self) {
+    let (pos, text_len, stops_len) = (self.pos, self.text.len(), self.stops.len());
+    if self.construct() {
+      return;
+    }
+
+    //not a valid construct, the `$` is plain text
+    self.pos = pos;
+    self.text.truncate(text_len);
+    self.stops.truncate(stops_len);
+    self.text.push('$');
+  }
+
+  fn construct(&mut self) -> bool {
+    let start = self.text.len();
+    if let Some(index) = self.number() {
+      self.stops.push((index, start, start));
+      return true;
+    }
+    if self.name().is_some() {
+      return true;
+    }
+    if self.next() != Some('{') {
+      return false;
+    }
+
+    if let Some(index) = self.number() {
+      let found = match self.next() {
+        Some('}') => true,
+        Some(':') => self.parse(true),
+        Some('|') => match self.choices() {
+          Some(first) => {
+            self.text.push_str(&first);
+            true
+          }
+          None => false,
+        },
+        _ => false,
+      };
+      if found {
+        self.stops.push((index, start, self.text.len()));
+      }
+      return found;
+    }
+
+    if self.name().is_none() {
+      return false;
+    }
+    match self.next() {
+      Some('}') => true,
+      Some(':') => self.parse(true),
+      //transformations of unknown variables are empty
+      Some('/') => self.skip_transform(),
+      _ => false,
+    }
+  }
+
+  ///Returns the first choice of `a,b|}`
+  fn choices(&mut self) -> Option<String> {
+    let mut choices = vec![String::new()];
+    while let Some(c) = self.next() {
+      match c {
+        '\\' => match self.peek() {
+          Some(escaped @ ('$' | '}' | '\\' | ',' | '|')) => {
+            self.pos += 1;
+            choices.last_mut()?.push(escaped);
+          }
+          _ => choices.last_mut()?.push('\\'),
+        },
+        ',' => choices.push(String::new()),
+        '|' => return (self.next() == Some('}')).then(|| choices.swap_remove(0)),
+        c => choices.last_mut()?.push(c),
+      }
+    }
+    None
+  }
+
+  fn skip_transform(&mut self) -> bool {
+    while let Some(c) = self.next() {
+      match c {
+        '\\' => self.pos += 1,
+        '}' => return true,
+        _ => {}
+      }
+    }
+    false
+  }
+
+  fn number(&mut self) -> Option<u32> {
+    let start = self.pos;
+    while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+      self.pos += 1;
+    }
+    self.chars[start..self.pos]
+      .iter()
+      .collect::<String>()
+      .parse()
+      .ok()
+  }
+
+  fn name(&mut self) -> Option<String> {
+    let start = self.pos;
+    if !self
+      .peek()
+      .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+    {
+      return None;
+    }
+    while self
+      .peek()
+      .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+      self.pos += 1;
+    }
+    Some(self.chars[start..self.pos].iter().collect())
+  }
+
+  fn next(&mut self) -> Option<char> {
+    let c = self.peek()?;
+    self.pos += 1;
+    Some(c)
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.chars.get(self.pos).copied()
+  }
+}
+
+fn is_word(text: &str) -> bool {
+  text.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn word_start(line: &str, position: Position, encoding: Option<&PositionEncodingKind>) -> Position {
+  //positions past the end of the line complete at its end
+  let end = byte_offset(line, position.character, encoding).unwrap_or(line.len());
+  let start = line[..end]
+    .char_indices()
+    .rev()
+    .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+    .last()
+    .map_or(end, |(start, _)| start);
+  Position::new(position.line, encoded_len(&line[..start], encoding))
+}
+
+///The characters of `word` have to appear in order, ignoring case
+fn matches_word(text: &str, word: &str) -> bool {
+  let mut text = text.chars().flat_map(char::to_lowercase);
+  word
+    .chars()
+    .flat_map(char::to_lowercase)
+    .all(|c| text.any(|t| t == c))
+}
+
+///Where `position` ends up after applying `edit`, which ends before it
+fn shift(position: Position, edit: &TextEdit, encoding: Option<&PositionEncodingKind>) -> Position {
+  let Range { start, end } = edit.range;
+  let lines = edit.new_text.matches('\n').count() as u32;
+  let last_line = edit.new_text.rfind('\n').map_or(0, |i| i + 1);
+  let last_line = encoded_len(&edit.new_text[last_line..], encoding);
+
+  if end.line < position.line {
+    return Position::new(
+      position.line - (end.line - start.line) + lines,
+      position.character,
+    );
+  }
+  let character = match lines {
+    0 => start.character + last_line,
+    _ => last_line,
+  };
+  Position::new(
+    start.line + lines,
+    character + position.character - end.character,
+  )
+}
+
+///Position of the byte `offset` of `text` inserted at `start`
+fn position_in(
+  start: Position,
+  text: &str,
+  offset: usize,
+  encoding: Option<&PositionEncodingKind>,
+) -> Position {
+  let before = &text[..offset];
+  match before.rfind('\n') {
+    Some(newline) => Position::new(
+      start.line + before.matches('\n').count() as u32,
+      encoded_len(&before[newline + 1..], encoding),
+    ),
+    None => Position::new(start.line, start.character + encoded_len(before, encoding)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Snippet;
+
+  #[test]
+  fn snippets_are_expanded() {
+    let snippet =
+      Snippet::parse("fn ${1:name}(${2:a: ${3:u32}}) {\n\t$0\n} \\$1 $HOME${TM_LINE:1}${4|x,y|}$1");
+    assert_eq!(snippet.text, "fn name(a: u32) {\n\t\n} $1 1x");
+    assert_eq!(
+      snippet.tabstops,
+      vec![
+        vec![(3, 7), (27, 27)],
+        vec![(8, 14)],
+        vec![(11, 14)],
+        vec![(26, 27)],
+        vec![(19, 19)],
+      ]
+    );
+
+    let invalid = Snippet::parse("${1:a ${x");
+    assert_eq!(invalid.text, "${1:a ${x");
+    assert_eq!(invalid.tabstops, vec![vec![(9, 9)]]);
+  }
+}
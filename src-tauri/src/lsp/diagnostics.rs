@@ -0,0 +1,381 @@
+use std::{
+  collections::HashMap,
+  fmt,
+  sync::{Arc, Mutex, OnceLock},
+};
+
+use anyhow::Error;
+use async_std::task::{self, JoinHandle};
+use lsp_types::{
+  request::{DocumentDiagnosticRequest, WorkspaceDiagnosticRefresh, WorkspaceDiagnosticRequest},
+  Diagnostic, DiagnosticServerCancellationData, DiagnosticSeverity, DocumentDiagnosticParams,
+  DocumentDiagnosticReport, DocumentDiagnosticReportKind, DocumentDiagnosticReportResult,
+  PreviousResultId, PublishDiagnosticsParams, Range, TextDocumentIdentifier,
+  WorkspaceDiagnosticParams, WorkspaceDiagnosticReportResult, WorkspaceDocumentDiagnosticReport,
+};
+use serde::Serialize;
+use tauri::{AppHandle, Manager, Url};
+
+use super::{
+  client::LSPData,
+  request::LSPRequest,
+  utils::{file_name, ErrorCode},
+};
+
+pub(crate) const DIAGNOSTICS_EVENT: &str = "lsp-diagnostics";
+
+///Pulls retriggered after the server cancelled them before giving up
+const PULL_RETRIES: u32 = 3;
+
+///Diagnostics of every document, emitted to the frontend whenever they change
+#[derive(Clone, Default)]
+pub(crate) struct DiagnosticStore {
+  documents: Arc<Mutex<HashMap<Url, DocumentDiagnostics>>>,
+  ///Last pull of every document, cancelled when the document changes again before it finished
+  pulls: Arc<Mutex<HashMap<Url, JoinHandle<()>>>>,
+  app: Arc<OnceLock<AppHandle>>,
+}
+
+///Pushed and pulled diagnostics are kept apart as a server may use both
+#[derive(Debug, Clone, Default)]
+struct DocumentDiagnostics {
+  pushed: Vec<Diagnostic>,
+  pulled: Vec<Diagnostic>,
+  ///Sent as `previousResultId` with the next pull
+  result_id: Option<String>,
+  ///Version of the document the pulled diagnostics belong to
+  version: Option<i32>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FileDiagnostics {
+  pub(crate) file: String,
+  pub(crate) diagnostics: Vec<Diagnostic>,
+}
+
+impl DiagnosticStore {
+  pub(crate) fn set_app(&self, app: AppHandle) {
+    let _ = self.app.set(app);
+  }
+
+  pub(crate) fn publish(&self, params: PublishDiagnosticsParams) {
+    let mut documents = self.documents.lock().unwrap();
+    let document = documents.entry(params.uri.clone()).or_default();
+    document.pushed = params.diagnostics;
+
+    self.emit(&params.uri, document);
+  }
+
+  ///Drops the pulled diagnostics of a closed document, pushed ones are cleared by the server
+  pub(crate) fn clear_pulled(&self, uri: &Url) {
+    if let Some(pull) = self.pulls.lock().unwrap().remove(uri) {
+      task::spawn(pull.cancel());
+    }
+
+    let mut documents = self.documents.lock().unwrap();
+    let Some(document) = documents.get_mut(uri) else {
+      return;
+    };
+    *document = DocumentDiagnostics {
+      pushed: std::mem::take(&mut document.pushed),
+      ..Default::default()
+    };
+
+    self.emit(uri, document);
+  }
+
+  ///Diagnostics at least as severe as `severity`, missing severities count as errors
+  pub(crate) fn query(
+    &self,
+    file: Option<&Url>,
+    severity: Option<DiagnosticSeverity>,
+    source: Option<&str>,
+  ) -> Vec<FileDiagnostics> {
+    let documents = self.documents.lock().unwrap();
+    documents
+      .iter()
+      .filter(|(uri, _)| file.is_none_or(|file| file == *uri))
+      .map(|(uri, document)| {
+        let diagnostics = document
+          .all()
+          .into_iter()
+          .filter(|diagnostic| {
+            severity.is_none_or(|severity| {
+              diagnostic.severity.unwrap_or(DiagnosticSeverity::ERROR) <= severity
+            })
+          })
+          .filter(|diagnostic| {
+            source.is_none_or(|source| diagnostic.source.as_deref() == Some(source))
+          })
+          .collect();
+        FileDiagnostics {
+          file: file_name(uri),
+          diagnostics,
+        }
+      })
+      .filter(|file| !file.diagnostics.is_empty())
+      .collect()
+  }
+
+  ///Diagnostics of the document touching `range`, sent along with code action requests
+  pub(crate) fn in_range(&self, uri: &Url, range: Range) -> Vec<Diagnostic> {
+    let documents = self.documents.lock().unwrap();
+    documents
+      .get(uri)
+      .map(DocumentDiagnostics::all)
+      .unwrap_or_default()
+      .into_iter()
+      .filter(|diagnostic| {
+        diagnostic.range.start <= range.end && range.start <= diagnostic.range.end
+      })
+      .collect()
+  }
+
+  ///Pulls the diagnostics of the document in the background if the server supports it. A pull
+  ///of the document that is still running is cancelled first, its result would be outdated.
+  pub(crate) fn request_pull(&self, lsp: &LSPData, uri: &Url) {
+    let Some(info) = lsp.lsp_info().and_then(|info| info.diagnostic_info) else {
+      return;
+    };
+
+    let store = self.clone();
+    let lsp = lsp.clone();
+    let inter_file_dependencies = info.inter_file_dependencies;
+    let mut pulls = self.pulls.lock().unwrap();
+    let previous = pulls.remove(uri);
+    let document = uri.clone();
+    let pull = task::spawn(async move {
+      //dropping the pull drops its request, which sends `$/cancelRequest`
+      if let Some(previous) = previous {
+        previous.cancel().await;
+      }
+
+      let pulled = match inter_file_dependencies {
+        true => store.pull_all(&lsp).await,
+        false => store.pull_document(&lsp, &document).await,
+      };
+      if let Err(err) = pulled {
+        eprintln!("Error pulling diagnostics for {}: {}", document, err);
+      }
+    });
+    pulls.insert(uri.clone(), pull);
+  }
+
+  ///Pulls everything again whenever the server sends `workspace/diagnostic/refresh`
+  pub(crate) fn watch_refresh(&self, lsp: &LSPData) {
+    let store = self.clone();
+    let refreshed = lsp.clone();
+    lsp.on_request::<WorkspaceDiagnosticRefresh, _, _>(move |_| {
+      let store = store.clone();
+      let lsp = refreshed.clone();
+      task::spawn(async move {
+        if let Err(err) = store.pull_all(&lsp).await {
+          eprintln!("Error refreshing diagnostics: {}", err);
+        }
+      });
+      async { Ok(()) }
+    });
+  }
+
+  pub(crate) async fn pull_all(&self, lsp: &LSPData) -> Result<(), Error> {
+    for uri in lsp.open_documents() {
+      self.pull_document(lsp, &uri).await?;
+    }
+    self.pull_workspace(lsp).await
+  }
+
+  pub(crate) async fn pull_document(&self, lsp: &LSPData, uri: &Url) -> Result<(), Error> {
+    let Some(info) = lsp.lsp_info().and_then(|info| info.diagnostic_info) else {
+      return Ok(());
+    };
+    let version = lsp.document_version(uri);
+
+    for _ in 0..PULL_RETRIES {
+      let req: LSPRequest<DocumentDiagnosticRequest> =
+        LSPRequest::new(Some(DocumentDiagnosticParams {
+          text_document: TextDocumentIdentifier { uri: uri.clone() },
+          identifier: info.identifier.clone(),
+          previous_result_id: self.result_id(uri),
+          work_done_progress_params: Default::default(),
+          partial_result_params: Default::default(),
+        }));
+      let res = lsp
+        .send_req(req)?
+        .response()
+        .await?
+        .ok_or(anyhow::anyhow!("No diagnostic response"))?;
+
+      match res.result {
+        Ok(report) => {
+          self.apply_document_report(uri, version, report);
+          return Ok(());
+        }
+        Err(err) if retrigger(&err.code, err.data.as_ref()) => continue,
+        Err(err) => return Err(anyhow::anyhow!(err.message)),
+      }
+    }
+
+    Ok(())
+  }
+
+  pub(crate) async fn pull_workspace(&self, lsp: &LSPData) -> Result<(), Error> {
+    let Some(info) = lsp
+      .lsp_info()
+      .and_then(|info| info.diagnostic_info)
+      .filter(|info| info.workspace_diagnostics)
+    else {
+      return Ok(());
+    };
+
+    let previous_result_ids = self
+      .documents
+      .lock()
+      .unwrap()
+      .iter()
+      .filter(|(uri, _)| lsp.matches(uri))
+      .filter_map(|(uri, document)| {
+        Some(PreviousResultId {
+          uri: uri.clone(),
+          value: document.result_id.clone()?,
+        })
+      })
+      .collect();
+    let req: LSPRequest<WorkspaceDiagnosticRequest> =
+      LSPRequest::new(Some(WorkspaceDiagnosticParams {
+        identifier: info.identifier.clone(),
+        previous_result_ids,
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+      }));
+    let res = lsp
+      .send_req(req)?
+      .response()
+      .await?
+      .ok_or(anyhow::anyhow!("No diagnostic response"))?;
+
+    let items = match res.result.map_err(|err| anyhow::anyhow!(err.message))? {
+      WorkspaceDiagnosticReportResult::Report(report) => report.items,
+      WorkspaceDiagnosticReportResult::Partial(partial) => partial.items,
+    };
+    for item in items {
+      match item {
+        WorkspaceDocumentDiagnosticReport::Full(report) => self.set_pulled(
+          &report.uri,
+          report.version.map(|version| version as i32),
+          report.full_document_diagnostic_report.result_id,
+          Some(report.full_document_diagnostic_report.items),
+        ),
+        WorkspaceDocumentDiagnosticReport::Unchanged(report) => self.set_pulled(
+          &report.uri,
+          report.version.map(|version| version as i32),
+          Some(report.unchanged_document_diagnostic_report.result_id),
+          None,
+        ),
+      }
+    }
+
+    Ok(())
+  }
+
+  fn apply_document_report(
+    &self,
+    uri: &Url,
+    version: Option<i32>,
+    report: DocumentDiagnosticReportResult,
+  ) {
+    let related = match report {
+      DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report)) => {
+        let full = report.full_document_diagnostic_report;
+        self.set_pulled(uri, version, full.result_id, Some(full.items));
+        report.related_documents
+      }
+      DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Unchanged(report)) => {
+        let unchanged = report.unchanged_document_diagnostic_report;
+        self.set_pulled(uri, version, Some(unchanged.result_id), None);
+        report.related_documents
+      }
+      DocumentDiagnosticReportResult::Partial(partial) => partial.related_documents,
+    };
+
+    for (uri, report) in related.unwrap_or_default() {
+      match report {
+        DocumentDiagnosticReportKind::Full(full) => {
+          self.set_pulled(&uri, None, full.result_id, Some(full.items))
+        }
+        DocumentDiagnosticReportKind::Unchanged(unchanged) => {
+          self.set_pulled(&uri, None, Some(unchanged.result_id), None)
+        }
+      }
+    }
+  }
+
+  ///`None` diagnostics mean the previous ones are still valid
+  fn set_pulled(
+    &self,
+    uri: &Url,
+    version: Option<i32>,
+    result_id: Option<String>,
+    diagnostics: Option<Vec<Diagnostic>>,
+  ) {
+    let mut documents = self.documents.lock().unwrap();
+    let document = documents.entry(uri.clone()).or_default();
+    //an older pull finished after a newer one
+    if let (Some(current), Some(version)) = (document.version, version) {
+      if version < current {
+        return;
+      }
+    }
+
+    document.version = version.or(document.version);
+    document.result_id = result_id;
+    if let Some(diagnostics) = diagnostics {
+      document.pulled = diagnostics;
+      self.emit(uri, document);
+    }
+  }
+
+  fn result_id(&self, uri: &Url) -> Option<String> {
+    let documents = self.documents.lock().unwrap();
+    documents.get(uri)?.result_id.clone()
+  }
+
+  fn emit(&self, uri: &Url, document: &DocumentDiagnostics) {
+    let Some(app) = self.app.get() else {
+      return;
+    };
+
+    let payload = FileDiagnostics {
+      file: file_name(uri),
+      diagnostics: document.all(),
+    };
+    if let Err(err) = app.emit(DIAGNOSTICS_EVENT, payload) {
+      eprintln!("Error emitting diagnostics: {}", err);
+    }
+  }
+}
+
+impl DocumentDiagnostics {
+  fn all(&self) -> Vec<Diagnostic> {
+    self.pushed.iter().chain(&self.pulled).cloned().collect()
+  }
+}
+
+impl fmt::Debug for DiagnosticStore {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("DiagnosticStore")
+      .field("documents", &self.documents)
+      .finish()
+  }
+}
+
+///Cancelled pulls are retriggered unless the server said otherwise
+fn retrigger(code: &ErrorCode, data: Option<&serde_json::Value>) -> bool {
+  if *code != ErrorCode::ServerCancelled {
+    return false;
+  }
+
+  data
+    .and_then(|data| serde_json::from_value::<DiagnosticServerCancellationData>(data.clone()).ok())
+    .is_none_or(|data| data.retrigger_request)
+}
@@ -0,0 +1,104 @@
+use std::{
+  collections::HashMap,
+  fmt,
+  marker::PhantomData,
+  sync::{Arc, Mutex, OnceLock},
+};
+
+use lsp_types::request::Request;
+use tauri::{AppHandle, Manager, Url};
+
+use super::{client::LSPData, utils::file_name};
+
+///Request of the server telling that the stored values of every document are outdated
+pub(crate) trait Refresh: Request<Params = (), Result = ()> + 'static {
+  ///Emitted with the files of the server, their values have to be requested again
+  const EVENT: &'static str;
+}
+
+///Last value the server sent for every document, dropped with the document or when the server
+///asks for a refresh with `R`
+pub(crate) struct DocumentStore<T, R> {
+  documents: Arc<Mutex<HashMap<Url, T>>>,
+  app: Arc<OnceLock<AppHandle>>,
+  refresh: PhantomData<fn() -> R>,
+}
+
+impl<T: Clone + Send + 'static, R: Refresh> DocumentStore<T, R> {
+  pub(crate) fn set_app(&self, app: AppHandle) {
+    let _ = self.app.set(app);
+  }
+
+  pub(crate) fn get(&self, uri: &Url) -> Option<T> {
+    self.documents.lock().unwrap().get(uri).cloned()
+  }
+
+  pub(crate) fn insert(&self, uri: Url, value: T) {
+    self.documents.lock().unwrap().insert(uri, value);
+  }
+
+  ///Runs `update` on the value of the document, `None` if there is none
+  pub(crate) fn update<U>(&self, uri: &Url, update: impl FnOnce(&mut T) -> U) -> Option<U> {
+    self.documents.lock().unwrap().get_mut(uri).map(update)
+  }
+
+  pub(crate) fn clear(&self, uri: &Url) {
+    self.documents.lock().unwrap().remove(uri);
+  }
+
+  ///Drops the values of the server and tells the frontend to request them again whenever the
+  ///server sends `R`
+  pub(crate) fn watch_refresh(&self, lsp: &LSPData) {
+    let store = self.clone();
+    let refreshed = lsp.clone();
+    lsp.on_request::<R, _, _>(move |_| {
+      store.refresh(&refreshed);
+      async { Ok(()) }
+    });
+  }
+
+  fn refresh(&self, lsp: &LSPData) {
+    self
+      .documents
+      .lock()
+      .unwrap()
+      .retain(|uri, _| !lsp.matches(uri));
+
+    let Some(app) = self.app.get() else {
+      return;
+    };
+    let files: Vec<String> = lsp.open_documents().iter().map(file_name).collect();
+    if let Err(err) = app.emit(R::EVENT, files) {
+      eprintln!("Error emitting {}: {}", R::EVENT, err);
+    }
+  }
+}
+
+//derives would require `R` to implement the traits too
+impl<T, R> Clone for DocumentStore<T, R> {
+  fn clone(&self) -> Self {
+    DocumentStore {
+      documents: self.documents.clone(),
+      app: self.app.clone(),
+      refresh: PhantomData,
+    }
+  }
+}
+
+impl<T, R> Default for DocumentStore<T, R> {
+  fn default() -> Self {
+    DocumentStore {
+      documents: Default::default(),
+      app: Default::default(),
+      refresh: PhantomData,
+    }
+  }
+}
+
+impl<T: fmt::Debug, R> fmt::Debug for DocumentStore<T, R> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("DocumentStore")
+      .field("documents", &self.documents)
+      .finish()
+  }
+}
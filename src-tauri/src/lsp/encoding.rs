@@ -0,0 +1,32 @@
+use lsp_types::PositionEncodingKind;
+
+///Byte offset of `offset` given in code units of `encoding`, `None` if it is past the end of
+///`text` or inside of a character. Servers without an encoding use UTF-16.
+pub(crate) fn byte_offset(
+  text: &str,
+  offset: u32,
+  encoding: Option<&PositionEncodingKind>,
+) -> Option<usize> {
+  let offset = offset as usize;
+  let mut units = 0;
+  for (byte, c) in text.char_indices() {
+    if units >= offset {
+      return (units == offset).then_some(byte);
+    }
+    units += unit_len(c, encoding);
+  }
+  (units == offset).then_some(text.len())
+}
+
+///Length of `text` in code units of `encoding`
+pub(crate) fn encoded_len(text: &str, encoding: Option<&PositionEncodingKind>) -> u32 {
+  text.chars().map(|c| unit_len(c, encoding)).sum::<usize>() as u32
+}
+
+fn unit_len(c: char, encoding: Option<&PositionEncodingKind>) -> usize {
+  match encoding {
+    Some(encoding) if *encoding == PositionEncodingKind::UTF8 => c.len_utf8(),
+    Some(encoding) if *encoding == PositionEncodingKind::UTF32 => 1,
+    _ => c.len_utf16(),
+  }
+}
@@ -0,0 +1,134 @@
+use std::{
+  collections::HashMap,
+  fmt,
+  future::Future,
+  pin::Pin,
+  sync::{Arc, RwLock},
+};
+
+use lsp_types::{
+  request::{
+    ApplyWorkspaceEdit, CodeLensRefresh, InlayHintRefreshRequest, InlineValueRefreshRequest,
+    RegisterCapability, Request as LSPRequestTrait, SemanticTokensRefresh, ShowDocument,
+    ShowMessageRequest, UnregisterCapability, WorkDoneProgressCreate, WorkspaceConfiguration,
+    WorkspaceDiagnosticRefresh, WorkspaceFoldersRequest,
+  },
+  ApplyWorkspaceEditResponse, ShowDocumentResult, WorkspaceFolder,
+};
+use serde_json::Value;
+
+use super::{
+  request::RawLSPRequest,
+  response::RawLSPResponse,
+  utils::{ErrorCode, LSPError},
+};
+
+type HandlerFuture = Pin<Box<dyn Future<Output = Result<Value, LSPError>> + Send>>;
+type Handler = Arc<dyn Fn(Value) -> HandlerFuture + Send + Sync>;
+
+///Answers the requests a server sends to the client, keyed by method
+#[derive(Clone, Default)]
+pub(crate) struct RequestHandlers {
+  handlers: Arc<RwLock<HashMap<String, Handler>>>,
+}
+
+impl RequestHandlers {
+  ///Registers the handlers every server relies on so none of its requests stay unanswered
+  pub(crate) fn new(workspace_folders: Vec<WorkspaceFolder>, settings: Value) -> Self {
+    let handlers = RequestHandlers::default();
+
+    handlers.on::<WorkspaceConfiguration, _, _>(move |params| {
+      let items = params
+        .items
+        .iter()
+        .map(|item| configuration(&settings, item.section.as_deref()))
+        .collect();
+      async move { Ok(items) }
+    });
+    handlers.on::<WorkspaceFoldersRequest, _, _>(move |_| {
+      let folders = workspace_folders.clone();
+      async move { Ok(Some(folders)) }
+    });
+    handlers.on::<WorkDoneProgressCreate, _, _>(|_| async { Ok(()) });
+    //all capabilities are registered statically, dynamic ones are acknowledged and ignored
+    handlers.on::<RegisterCapability, _, _>(|_| async { Ok(()) });
+    handlers.on::<UnregisterCapability, _, _>(|_| async { Ok(()) });
+    handlers.on::<ApplyWorkspaceEdit, _, _>(|_| async {
+      Ok(ApplyWorkspaceEditResponse {
+        applied: false,
+        failure_reason: Some("Workspace edits are not supported yet".to_string()),
+        failed_change: None,
+      })
+    });
+    handlers.on::<ShowMessageRequest, _, _>(|_| async { Ok(None) });
+    handlers.on::<ShowDocument, _, _>(|_| async { Ok(ShowDocumentResult { success: false }) });
+    handlers.on::<SemanticTokensRefresh, _, _>(|_| async { Ok(()) });
+    handlers.on::<CodeLensRefresh, _, _>(|_| async { Ok(()) });
+    handlers.on::<InlayHintRefreshRequest, _, _>(|_| async { Ok(()) });
+    handlers.on::<InlineValueRefreshRequest, _, _>(|_| async { Ok(()) });
+    handlers.on::<WorkspaceDiagnosticRefresh, _, _>(|_| async { Ok(()) });
+
+    handlers
+  }
+
+  ///Replaces the handler for the method of `T`
+  pub(crate) fn on<T, F, Fut>(&self, handler: F)
+  where
+    T: LSPRequestTrait + 'static,
+    F: Fn(T::Params) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<T::Result, LSPError>> + Send + 'static,
+  {
+    let handler: Handler = Arc::new(move |params| {
+      let response = serde_json::from_value::<T::Params>(params).map(&handler);
+
+      Box::pin(async move {
+        let result = response
+          .map_err(|err| LSPError::new(ErrorCode::InvalidParams, err.to_string()))?
+          .await?;
+
+        serde_json::to_value(result)
+          .map_err(|err| LSPError::new(ErrorCode::InternalError, err.to_string()))
+      })
+    });
+
+    self
+      .handlers
+      .write()
+      .unwrap()
+      .insert(T::METHOD.to_string(), handler);
+  }
+
+  pub(crate) async fn handle(&self, req: RawLSPRequest) -> RawLSPResponse {
+    let handler = self.handlers.read().unwrap().get(&req.method).cloned();
+
+    let result = match handler {
+      Some(handler) => handler(req.params).await,
+      None => Err(LSPError::new(
+        ErrorCode::MethodNotFound,
+        format!("Unhandled method {}", req.method),
+      )),
+    };
+
+    RawLSPResponse::new(req.id, result)
+  }
+}
+
+impl fmt::Debug for RequestHandlers {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let handlers = self.handlers.read().unwrap();
+    f.debug_set().entries(handlers.keys()).finish()
+  }
+}
+
+///Looks up a dotted section like `rust-analyzer.cargo` in the settings
+fn configuration(settings: &Value, section: Option<&str>) -> Value {
+  let Some(section) = section else {
+    return settings.clone();
+  };
+
+  section
+    .split('.')
+    .try_fold(settings, |value, key| value.get(key))
+    .cloned()
+    .unwrap_or(Value::Null)
+}
@@ -0,0 +1,273 @@
+use anyhow::Error;
+use lsp_types::{
+  request::{
+    CallHierarchyIncomingCalls, CallHierarchyOutgoingCalls, CallHierarchyPrepare,
+    TypeHierarchyPrepare, TypeHierarchySubtypes, TypeHierarchySupertypes,
+  },
+  CallHierarchyIncomingCallsParams, CallHierarchyItem, CallHierarchyOutgoingCallsParams,
+  CallHierarchyPrepareParams, Position, Range, SymbolKind, SymbolTag, TextDocumentIdentifier,
+  TextDocumentPositionParams, TypeHierarchyPrepareParams, TypeHierarchySubtypesParams,
+  TypeHierarchySupertypesParams, Url,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+  client::LSPData,
+  symbol::{kind, tags},
+  utils::file_name,
+};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum HierarchyKind {
+  Call,
+  Type,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum HierarchyDirection {
+  ///Callers of a function
+  Incoming,
+  ///Functions a function calls
+  Outgoing,
+  Supertypes,
+  Subtypes,
+}
+
+///Items of the last prepared hierarchy, expanded lazily and addressed by their id
+#[derive(Debug, Clone)]
+pub(crate) struct Hierarchy {
+  lsp: LSPData,
+  kind: HierarchyKind,
+  ///Type hierarchy items have the same fields as call hierarchy items
+  nodes: Vec<CallHierarchyItem>,
+  expansions: Vec<Expansion>,
+}
+
+///Children of a node, valid as long as the document of the node has `version`
+#[derive(Debug, Clone)]
+struct Expansion {
+  id: usize,
+  direction: HierarchyDirection,
+  version: Option<i32>,
+  children: Vec<(usize, Vec<Range>)>,
+}
+
+///Node of a hierarchy explorer, `id` is used to expand it
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HierarchyEntry {
+  pub(crate) id: usize,
+  pub(crate) name: String,
+  pub(crate) kind: SymbolKind,
+  pub(crate) tags: Vec<SymbolTag>,
+  pub(crate) detail: Option<String>,
+  ///Path for files, the uri for anything else
+  pub(crate) file: String,
+  pub(crate) range: Range,
+  pub(crate) selection_range: Range,
+  ///Where the calls are made, in the file of the caller
+  pub(crate) call_ranges: Vec<Range>,
+}
+
+impl Hierarchy {
+  ///Items at `position` the hierarchy starts from, there may be several for overloads
+  pub(crate) async fn prepare(
+    lsp: &LSPData,
+    uri: Url,
+    position: Position,
+    kind: HierarchyKind,
+  ) -> Result<Self, Error> {
+    let text_document_position_params = TextDocumentPositionParams {
+      text_document: TextDocumentIdentifier { uri },
+      position,
+    };
+    let items = match kind {
+      HierarchyKind::Call => {
+        lsp
+          .request::<CallHierarchyPrepare>(CallHierarchyPrepareParams {
+            text_document_position_params,
+            work_done_progress_params: Default::default(),
+          })
+          .await?
+      }
+      HierarchyKind::Type => {
+        lsp
+          .request::<TypeHierarchyPrepare>(TypeHierarchyPrepareParams {
+            text_document_position_params,
+            work_done_progress_params: Default::default(),
+          })
+          .await?
+      }
+    };
+
+    Ok(Hierarchy {
+      lsp: lsp.clone(),
+      kind,
+      nodes: items.unwrap_or_default(),
+      expansions: Vec::new(),
+    })
+  }
+
+  pub(crate) fn roots(&self) -> Vec<HierarchyEntry> {
+    (0..self.nodes.len())
+      .map(|id| self.entry(id, Vec::new()))
+      .collect()
+  }
+
+  ///Children of the node in `direction`, requested again only once its document changed
+  pub(crate) async fn expand(
+    &mut self,
+    id: usize,
+    direction: HierarchyDirection,
+  ) -> Result<Vec<HierarchyEntry>, Error> {
+    let item = self
+      .nodes
+      .get(id)
+      .cloned()
+      .ok_or(anyhow::anyhow!("No hierarchy item {}", id))?;
+    let matches_kind = match direction {
+      HierarchyDirection::Incoming | HierarchyDirection::Outgoing => {
+        self.kind == HierarchyKind::Call
+      }
+      HierarchyDirection::Supertypes | HierarchyDirection::Subtypes => {
+        self.kind == HierarchyKind::Type
+      }
+    };
+    if !matches_kind {
+      return Err(anyhow::anyhow!(
+        "Cannot expand {:?} in a {:?} hierarchy",
+        direction,
+        self.kind
+      ));
+    }
+
+    //documents that are not open have no version, their children are always requested again
+    let version = self.lsp.document_version(&item.uri);
+    let cached = self
+      .expansions
+      .iter()
+      .position(|expansion| expansion.id == id && expansion.direction == direction);
+    let mut previous = Vec::new();
+    if let Some(index) = cached {
+      if version.is_some() && self.expansions[index].version == version {
+        return Ok(self.entries(&self.expansions[index].children));
+      }
+      previous = self.expansions.remove(index).children;
+    }
+
+    let children = self.request(item, direction).await?;
+    let children: Vec<(usize, Vec<Range>)> = children
+      .into_iter()
+      .map(|(child, ranges)| (self.insert(child, &mut previous), ranges))
+      .collect();
+    let entries = self.entries(&children);
+    self.expansions.push(Expansion {
+      id,
+      direction,
+      version,
+      children,
+    });
+    Ok(entries)
+  }
+
+  ///Id of the node, children of the previous expansion keep their id so the explorer stays
+  ///expanded and the nodes are not added again
+  fn insert(&mut self, child: CallHierarchyItem, previous: &mut Vec<(usize, Vec<Range>)>) -> usize {
+    let reused = previous.iter().position(|(id, _)| {
+      let node = &self.nodes[*id];
+      node.uri == child.uri && node.name == child.name && node.kind == child.kind
+    });
+    match reused {
+      Some(index) => {
+        let (id, _) = previous.remove(index);
+        self.nodes[id] = child;
+        id
+      }
+      None => {
+        self.nodes.push(child);
+        self.nodes.len() - 1
+      }
+    }
+  }
+
+  async fn request(
+    &self,
+    item: CallHierarchyItem,
+    direction: HierarchyDirection,
+  ) -> Result<Vec<(CallHierarchyItem, Vec<Range>)>, Error> {
+    Ok(match direction {
+      HierarchyDirection::Incoming => self
+        .lsp
+        .request::<CallHierarchyIncomingCalls>(CallHierarchyIncomingCallsParams {
+          item,
+          work_done_progress_params: Default::default(),
+          partial_result_params: Default::default(),
+        })
+        .await?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|call| (call.from, call.from_ranges))
+        .collect(),
+      HierarchyDirection::Outgoing => self
+        .lsp
+        .request::<CallHierarchyOutgoingCalls>(CallHierarchyOutgoingCallsParams {
+          item,
+          work_done_progress_params: Default::default(),
+          partial_result_params: Default::default(),
+        })
+        .await?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|call| (call.to, call.from_ranges))
+        .collect(),
+      HierarchyDirection::Supertypes => self
+        .lsp
+        .request::<TypeHierarchySupertypes>(TypeHierarchySupertypesParams {
+          item,
+          work_done_progress_params: Default::default(),
+          partial_result_params: Default::default(),
+        })
+        .await?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|item| (item, Vec::new()))
+        .collect(),
+      HierarchyDirection::Subtypes => self
+        .lsp
+        .request::<TypeHierarchySubtypes>(TypeHierarchySubtypesParams {
+          item,
+          work_done_progress_params: Default::default(),
+          partial_result_params: Default::default(),
+        })
+        .await?
+        .unwrap_or_default()
+        .into_iter()
+        .map(|item| (item, Vec::new()))
+        .collect(),
+    })
+  }
+
+  fn entries(&self, children: &[(usize, Vec<Range>)]) -> Vec<HierarchyEntry> {
+    children
+      .iter()
+      .map(|(id, ranges)| self.entry(*id, ranges.clone()))
+      .collect()
+  }
+
+  fn entry(&self, id: usize, call_ranges: Vec<Range>) -> HierarchyEntry {
+    let item = &self.nodes[id];
+    HierarchyEntry {
+      id,
+      name: item.name.clone(),
+      kind: kind(item.kind),
+      tags: tags(item.tags.clone(), None),
+      detail: item.detail.clone(),
+      file: file_name(&item.uri),
+      range: item.range,
+      selection_range: item.selection_range,
+      call_ranges,
+    }
+  }
+}
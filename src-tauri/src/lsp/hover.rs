@@ -0,0 +1,68 @@
+use lsp_types::{
+  Documentation, Hover, HoverContents, MarkedString, MarkupContent, MarkupKind, Range,
+};
+use serde::Serialize;
+
+use crate::file::{
+  markdown::{code_block, plaintext_to_html, to_html},
+  parser::ParsersManager,
+};
+
+///Hover of a position rendered as html
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HoverContent {
+  pub(crate) html: String,
+  ///Range of the hovered symbol
+  pub(crate) range: Option<Range>,
+}
+
+impl HoverContent {
+  ///`path` is the hovered file, its parser highlights code blocks without a language
+  pub(crate) fn new(hover: Hover, parsers: &ParsersManager, path: &str) -> Self {
+    let html = match hover.contents {
+      HoverContents::Markup(markup) => markup_to_html(&markup, parsers, path),
+      HoverContents::Scalar(marked) => marked_to_html(marked, parsers, path),
+      HoverContents::Array(marked) => marked
+        .into_iter()
+        .map(|marked| marked_to_html(marked, parsers, path))
+        .collect::<Vec<String>>()
+        .join("<hr>"),
+    };
+
+    HoverContent {
+      html,
+      range: hover.range,
+    }
+  }
+}
+
+pub(crate) fn markup_to_html(
+  markup: &MarkupContent,
+  parsers: &ParsersManager,
+  path: &str,
+) -> String {
+  match markup.kind {
+    MarkupKind::Markdown => to_html(&markup.value, parsers, path),
+    MarkupKind::PlainText => plaintext_to_html(&markup.value),
+  }
+}
+
+///Plain strings of documentation are plain text, unlike the ones of hovers
+pub(crate) fn documentation_to_html(
+  documentation: &Documentation,
+  parsers: &ParsersManager,
+  path: &str,
+) -> String {
+  match documentation {
+    Documentation::String(text) => plaintext_to_html(text),
+    Documentation::MarkupContent(markup) => markup_to_html(markup, parsers, path),
+  }
+}
+
+fn marked_to_html(marked: MarkedString, parsers: &ParsersManager, path: &str) -> String {
+  match marked {
+    MarkedString::String(markdown) => to_html(&markdown, parsers, path),
+    MarkedString::LanguageString(code) => code_block(&code.value, &code.language, parsers, path),
+  }
+}
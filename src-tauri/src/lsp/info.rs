@@ -1,17 +1,75 @@
 use anyhow::Error;
-use lsp_types::{request::Initialize, SemanticTokensOptions, SemanticTokensServerCapabilities};
+use lsp_types::{
+  request::Initialize, CodeActionProviderCapability, CompletionOptions, DiagnosticOptions,
+  DiagnosticServerCapabilities, FoldingRangeProviderCapability, InlayHintServerCapabilities,
+  LinkedEditingRangeServerCapabilities, OneOf, PositionEncodingKind, SemanticTokensFullOptions,
+  SemanticTokensOptions, SemanticTokensServerCapabilities, TextDocumentSyncCapability,
+  TextDocumentSyncKind, TextDocumentSyncSaveOptions,
+};
 
 use super::response::LSPResponse;
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub(crate) struct LSPInfo {
   pub(crate) semantic_token_info: Option<SemanticTokenInfo>,
+  pub(crate) sync: SyncInfo,
+  ///Set if the server supports pulling diagnostics
+  pub(crate) diagnostic_info: Option<DiagnosticInfo>,
+  pub(crate) completion_info: Option<CompletionInfo>,
+  ///Servers not choosing an encoding use UTF-16
+  pub(crate) position_encoding: Option<PositionEncodingKind>,
+  ///Set if the server checks with `textDocument/prepareRename` what can be renamed
+  pub(crate) prepare_rename: bool,
+  ///Set if code actions can be completed lazily with `codeAction/resolve`
+  pub(crate) code_action_resolve: bool,
+  ///Characters after which the server formats the code while typing
+  pub(crate) on_type_formatting_triggers: Vec<String>,
+  ///Set if the server provides inlay hints, true if they can be resolved with `inlayHint/resolve`
+  pub(crate) inlay_hint: Option<bool>,
+  pub(crate) folding_range: bool,
+  pub(crate) document_symbol: bool,
+  ///Set if the server searches workspace symbols, true if their ranges can be resolved lazily
+  ///with `workspaceSymbol/resolve`
+  pub(crate) workspace_symbol: Option<bool>,
+  ///Set if the server provides code lenses, true if their commands can be resolved lazily with
+  ///`codeLens/resolve`
+  pub(crate) code_lens: Option<bool>,
+  pub(crate) document_highlight: bool,
+  pub(crate) linked_editing_range: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CompletionInfo {
+  ///Characters that start a completion when typed
+  pub(crate) trigger_characters: Vec<String>,
+  ///Set if items can be completed lazily with `completionItem/resolve`
+  pub(crate) resolve: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DiagnosticInfo {
+  pub(crate) identifier: Option<String>,
+  ///A change in one document can change the diagnostics of others
+  pub(crate) inter_file_dependencies: bool,
+  pub(crate) workspace_diagnostics: bool,
+}
+
+///Which document notifications the server wants
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SyncInfo {
+  pub(crate) open_close: bool,
+  pub(crate) change: TextDocumentSyncKind,
+  ///Set if `didSave` should be sent, true if it has to include the text
+  pub(crate) save: Option<bool>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub(crate) struct SemanticTokenInfo {
   token_types: Vec<String>,
   token_modifiers: Vec<String>,
+  ///Set if only the changes since the last result can be requested
+  pub(crate) delta: bool,
+  pub(crate) range: bool,
 }
 
 impl LSPInfo {
@@ -29,12 +87,164 @@ impl LSPInfo {
       None => None,
     };
 
+    let sync = match server_capabilities.text_document_sync {
+      Some(sync) => SyncInfo::new(sync),
+      None => SyncInfo::default(),
+    };
+
+    let diagnostic_info =
+      server_capabilities
+        .diagnostic_provider
+        .map(|diagnostic| match diagnostic {
+          DiagnosticServerCapabilities::Options(options) => DiagnosticInfo::new(options),
+          DiagnosticServerCapabilities::RegistrationOptions(reg) => {
+            DiagnosticInfo::new(reg.diagnostic_options)
+          }
+        });
+
+    let completion_info = server_capabilities
+      .completion_provider
+      .map(CompletionInfo::new);
+
+    let prepare_rename = match server_capabilities.rename_provider {
+      Some(OneOf::Right(options)) => options.prepare_provider.unwrap_or(false),
+      _ => false,
+    };
+
+    let code_action_resolve = match server_capabilities.code_action_provider {
+      Some(CodeActionProviderCapability::Options(options)) => {
+        options.resolve_provider.unwrap_or(false)
+      }
+      _ => false,
+    };
+
+    let on_type_formatting_triggers = server_capabilities
+      .document_on_type_formatting_provider
+      .map(|options| {
+        let more = options.more_trigger_character.unwrap_or_default();
+        [vec![options.first_trigger_character], more].concat()
+      })
+      .unwrap_or_default();
+
+    let inlay_hint = server_capabilities
+      .inlay_hint_provider
+      .and_then(|provider| match provider {
+        OneOf::Left(supported) => supported.then_some(false),
+        OneOf::Right(InlayHintServerCapabilities::Options(options)) => {
+          Some(options.resolve_provider.unwrap_or(false))
+        }
+        OneOf::Right(InlayHintServerCapabilities::RegistrationOptions(reg)) => {
+          Some(reg.inlay_hint_options.resolve_provider.unwrap_or(false))
+        }
+      });
+
+    let folding_range = match server_capabilities.folding_range_provider {
+      Some(FoldingRangeProviderCapability::Simple(supported)) => supported,
+      Some(_) => true,
+      None => false,
+    };
+
+    let document_symbol = match server_capabilities.document_symbol_provider {
+      Some(OneOf::Left(supported)) => supported,
+      Some(OneOf::Right(_)) => true,
+      None => false,
+    };
+
+    let workspace_symbol = server_capabilities
+      .workspace_symbol_provider
+      .and_then(|provider| match provider {
+        OneOf::Left(supported) => supported.then_some(false),
+        OneOf::Right(options) => Some(options.resolve_provider.unwrap_or(false)),
+      });
+
+    let document_highlight = match server_capabilities.document_highlight_provider {
+      Some(OneOf::Left(supported)) => supported,
+      Some(OneOf::Right(_)) => true,
+      None => false,
+    };
+
+    let linked_editing_range = match server_capabilities.linked_editing_range_provider {
+      Some(LinkedEditingRangeServerCapabilities::Simple(supported)) => supported,
+      Some(_) => true,
+      None => false,
+    };
+
+    let code_lens = server_capabilities
+      .code_lens_provider
+      .map(|options| options.resolve_provider.unwrap_or(false));
+
     Ok(Self {
       semantic_token_info,
+      sync,
+      diagnostic_info,
+      completion_info,
+      position_encoding: server_capabilities.position_encoding,
+      prepare_rename,
+      code_action_resolve,
+      on_type_formatting_triggers,
+      inlay_hint,
+      folding_range,
+      document_symbol,
+      workspace_symbol,
+      code_lens,
+      document_highlight,
+      linked_editing_range,
     })
   }
 }
 
+impl SyncInfo {
+  pub(crate) fn new(sync: TextDocumentSyncCapability) -> Self {
+    match sync {
+      //a plain kind means open, close and save without text are wanted as well
+      TextDocumentSyncCapability::Kind(kind) => Self {
+        open_close: kind != TextDocumentSyncKind::NONE,
+        change: kind,
+        save: (kind != TextDocumentSyncKind::NONE).then_some(false),
+      },
+      TextDocumentSyncCapability::Options(options) => Self {
+        open_close: options.open_close.unwrap_or(false),
+        change: options.change.unwrap_or(TextDocumentSyncKind::NONE),
+        save: options.save.and_then(|save| match save {
+          TextDocumentSyncSaveOptions::Supported(supported) => supported.then_some(false),
+          TextDocumentSyncSaveOptions::SaveOptions(options) => {
+            Some(options.include_text.unwrap_or(false))
+          }
+        }),
+      },
+    }
+  }
+}
+
+impl Default for SyncInfo {
+  fn default() -> Self {
+    Self {
+      open_close: false,
+      change: TextDocumentSyncKind::NONE,
+      save: None,
+    }
+  }
+}
+
+impl DiagnosticInfo {
+  pub(crate) fn new(options: DiagnosticOptions) -> Self {
+    Self {
+      identifier: options.identifier,
+      inter_file_dependencies: options.inter_file_dependencies,
+      workspace_diagnostics: options.workspace_diagnostics,
+    }
+  }
+}
+
+impl CompletionInfo {
+  pub(crate) fn new(options: CompletionOptions) -> Self {
+    Self {
+      trigger_characters: options.trigger_characters.unwrap_or_default(),
+      resolve: options.resolve_provider.unwrap_or(false),
+    }
+  }
+}
+
 impl SemanticTokenInfo {
   pub(crate) fn new(options: SemanticTokensOptions) -> Self {
     Self {
@@ -50,6 +260,11 @@ impl SemanticTokenInfo {
         .iter()
         .map(|s| s.as_str().to_string())
         .collect(),
+      delta: matches!(
+        options.full,
+        Some(SemanticTokensFullOptions::Delta { delta: Some(true) })
+      ),
+      range: options.range.unwrap_or(false),
     }
   }
 
@@ -0,0 +1,149 @@
+use anyhow::Error;
+use lsp_types::{
+  request::{InlayHintRefreshRequest, InlayHintRequest, InlayHintResolveRequest},
+  InlayHint, InlayHintKind, InlayHintLabel, InlayHintParams, Position, Range,
+  TextDocumentIdentifier,
+};
+use tauri::Url;
+
+use crate::file::token::Token;
+
+use super::{
+  client::LSPData,
+  document_store::{DocumentStore, Refresh},
+};
+
+///Emitted with the files whose hints are outdated, their highlighting has to be requested again
+pub(crate) const INLAY_HINT_REFRESH_EVENT: &str = "lsp-inlay-hint-refresh";
+
+///Last hints of every document, kept so they can be resolved when the frontend shows one
+pub(crate) type InlayHintStore = DocumentStore<Vec<StoredHint>, InlayHintRefreshRequest>;
+
+#[derive(Debug, Clone)]
+pub(crate) struct StoredHint {
+  hint: InlayHint,
+  resolved: bool,
+}
+
+impl Refresh for InlayHintRefreshRequest {
+  const EVENT: &'static str = INLAY_HINT_REFRESH_EVENT;
+}
+
+impl InlayHintStore {
+  ///Hints of `range` as virtual tokens, they replace the stored hints of the document
+  pub(crate) async fn request(
+    &self,
+    lsp: &LSPData,
+    uri: &Url,
+    range: Range,
+  ) -> Result<Vec<(u32, Token)>, Error> {
+    if lsp.lsp_info().and_then(|info| info.inlay_hint).is_none() {
+      return Ok(Vec::new());
+    }
+
+    let hints = lsp
+      .request::<InlayHintRequest>(InlayHintParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        range,
+        work_done_progress_params: Default::default(),
+      })
+      .await?
+      .unwrap_or_default();
+
+    let tokens = hints
+      .iter()
+      .flat_map(|hint| {
+        let (line, tokens) = virtual_tokens(hint);
+        tokens.into_iter().map(move |token| (line, token))
+      })
+      .collect();
+    let hints = hints
+      .into_iter()
+      .map(|hint| StoredHint {
+        hint,
+        resolved: false,
+      })
+      .collect();
+    self.insert(uri.clone(), hints);
+
+    Ok(tokens)
+  }
+
+  ///The hint shown at `position` with its tooltips and locations, if the server resolves them
+  pub(crate) async fn resolve(
+    &self,
+    lsp: &LSPData,
+    uri: &Url,
+    position: Position,
+  ) -> Result<Option<InlayHint>, Error> {
+    let Some(stored) = self.find(uri, position) else {
+      return Ok(None);
+    };
+    let resolve = lsp.lsp_info().and_then(|info| info.inlay_hint) == Some(true);
+    if stored.resolved || !resolve {
+      return Ok(Some(stored.hint));
+    }
+
+    let hint = lsp
+      .request::<InlayHintResolveRequest>(stored.hint.clone())
+      .await?;
+    //the hints may have been replaced while the request was running
+    self.update(uri, |hints| {
+      if let Some(replaced) = hints.iter_mut().find(|other| other.hint == stored.hint) {
+        replaced.hint = hint.clone();
+        replaced.resolved = true;
+      }
+    });
+    Ok(Some(hint))
+  }
+
+  fn find(&self, uri: &Url, position: Position) -> Option<StoredHint> {
+    self
+      .update(uri, |hints| {
+        hints
+          .iter()
+          .find(|stored| stored.hint.position == position)
+          .cloned()
+      })
+      .flatten()
+  }
+}
+
+///Line of the hint and its label as virtual tokens, one for each part of the label. Their start
+///is the character of the hint, in code units of the server. Padding is added to the text of the
+///first and last token.
+fn virtual_tokens(hint: &InlayHint) -> (u32, Vec<Token>) {
+  let type_ = match hint.kind {
+    Some(InlayHintKind::TYPE) => "inlay_hint.type",
+    Some(InlayHintKind::PARAMETER) => "inlay_hint.parameter",
+    _ => "inlay_hint",
+  };
+  let mut parts: Vec<String> = match &hint.label {
+    InlayHintLabel::String(label) => vec![label.clone()],
+    InlayHintLabel::LabelParts(parts) => parts.iter().map(|part| part.value.clone()).collect(),
+  };
+
+  if hint.padding_left == Some(true) {
+    if let Some(first) = parts.first_mut() {
+      first.insert(0, ' ');
+    }
+  }
+  if hint.padding_right == Some(true) {
+    if let Some(last) = parts.last_mut() {
+      last.push(' ');
+    }
+  }
+
+  let tokens = parts
+    .into_iter()
+    .filter(|part| !part.is_empty())
+    .map(|part| Token {
+      start: hint.position.character,
+      token: part,
+      type_: type_.to_string(),
+      modifiers: None,
+      virtual_: true,
+    })
+    .collect();
+  (hint.position.line, tokens)
+}
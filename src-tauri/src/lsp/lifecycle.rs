@@ -0,0 +1,135 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Error;
+use lsp_types::{notification::Exit, request::Shutdown};
+use serde::Serialize;
+
+use super::{
+  client::LSPData, notification::LSPNotification, request::LSPRequest, transport::Connection,
+};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum ServerState {
+  Starting,
+  Running,
+  Crashed,
+  Stopped,
+}
+
+const RESTART_ATTEMPTS: u32 = 5;
+const RESTART_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(30);
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl LSPData {
+  pub(crate) fn state(&self) -> ServerState {
+    self.lsp_client.lock().unwrap().state
+  }
+
+  ///Called by the reader once the connection made as `generation` closed
+  pub(super) fn on_exit(&self, generation: u32) {
+    let mut lsp = self.lsp_client.lock().unwrap();
+    if lsp.generation != generation {
+      return;
+    }
+
+    lsp.pending.clear();
+    self.traffic.clear_in_flight();
+    if lsp.state == ServerState::Stopped {
+      return;
+    }
+
+    //the server may have closed stdout without exiting
+    let name = self.config.transport.name();
+    match lsp.endpoint.close() {
+      Ok(Some(status)) => eprintln!("{} exited with {}", name, status),
+      Ok(None) => eprintln!("{} closed the connection", name),
+      Err(err) => eprintln!("{} exited: {}", name, err),
+    }
+
+    //a failed start is retried by the restart loop that started it
+    let restart = lsp.state == ServerState::Running;
+    lsp.state = ServerState::Crashed;
+    drop(lsp);
+
+    if restart {
+      let lsp = self.clone();
+      async_std::task::spawn(async move { lsp.restart().await });
+    }
+  }
+
+  async fn restart(&self) {
+    let mut backoff = RESTART_BACKOFF;
+    for attempt in 1..=RESTART_ATTEMPTS {
+      async_std::task::sleep(backoff).await;
+      if self.state() == ServerState::Stopped {
+        return;
+      }
+
+      match self.reconnect().await {
+        Ok(()) => return,
+        Err(err) => {
+          eprintln!(
+            "Restart {} of {} for {} failed: {}",
+            attempt,
+            RESTART_ATTEMPTS,
+            self.config.transport.name(),
+            err
+          );
+          let _ = self.lsp_client.lock().unwrap().endpoint.close();
+        }
+      }
+
+      backoff = (backoff * 2).min(MAX_RESTART_BACKOFF);
+    }
+  }
+
+  async fn reconnect(&self) -> Result<(), Error> {
+    let Connection {
+      writer,
+      reader,
+      endpoint,
+    } = self.config.transport.connect().await?;
+
+    let generation = self.lsp_client.lock().unwrap().attach(writer, endpoint);
+    self.listen(reader, generation);
+    self.initialize().await?;
+
+    Ok(())
+  }
+
+  ///Asks the server to shut down and kills it if it does not exit in time, sockets are only
+  ///closed
+  pub(crate) async fn shutdown(&self) -> Result<(), Error> {
+    let req = match self.state() {
+      ServerState::Running if !self.config.transport.is_socket() => {
+        let shutdown: LSPRequest<Shutdown> = LSPRequest::new(None).with_timeout(SHUTDOWN_TIMEOUT);
+        Some(self.send_req(shutdown)?)
+      }
+      _ => None,
+    };
+    //set after sending so the request is not refused but the exit is not seen as a crash
+    self.lsp_client.lock().unwrap().state = ServerState::Stopped;
+
+    if let Some(req) = req {
+      if req.response().await.is_ok() {
+        let exit: LSPNotification<Exit> = LSPNotification::new(None)?;
+        self.send_not(exit)?;
+      }
+    }
+
+    let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+    loop {
+      {
+        let mut lsp = self.lsp_client.lock().unwrap();
+        if lsp.endpoint.has_exited()? || Instant::now() >= deadline {
+          lsp.endpoint.close()?;
+          return Ok(());
+        }
+      }
+
+      async_std::task::sleep(Duration::from_millis(50)).await;
+    }
+  }
+}
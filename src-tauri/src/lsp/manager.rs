@@ -1,80 +1,850 @@
+use std::future::Future;
+
 use anyhow::Error;
 use lsp_types::{
-  request::{Request as LSPRequestTrait, SemanticTokensFullRequest},
-  SemanticTokensParams, SemanticTokensResult,
+  notification::{Notification as LSPNotificationTrait, PublishDiagnostics},
+  request::{
+    DocumentHighlightRequest, ExecuteCommand, FoldingRangeRequest, Formatting, GotoDeclaration,
+    GotoDefinition, GotoImplementation, GotoTypeDefinition, HoverRequest, LinkedEditingRange,
+    OnTypeFormatting, PrepareRenameRequest, RangeFormatting, References,
+    Request as LSPRequestTrait, SignatureHelpRequest,
+  },
+  CodeActionKind, Command, CompletionItem, DiagnosticSeverity, DocumentFormattingParams,
+  DocumentHighlight, DocumentHighlightParams, DocumentOnTypeFormattingParams,
+  DocumentRangeFormattingParams, ExecuteCommandParams, FoldingRange, FoldingRangeParams,
+  FormattingOptions, GotoDefinitionParams, HoverParams, InlayHint, LinkedEditingRangeParams,
+  LinkedEditingRanges, Position, PositionEncodingKind, Range, ReferenceContext, ReferenceParams,
+  RenameParams, SignatureHelpContext, SignatureHelpParams, SignatureHelpTriggerKind,
+  TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams, TextEdit, TraceValue,
 };
-use tauri::Url;
+use serde_json::Value;
+use tauri::{AppHandle, Manager, Url};
 
-use crate::file::{parser::get_highlighting_name, token::Token};
+use crate::file::{
+  parser::{get_highlighting_name, ParsersManager},
+  token::Token,
+  workspace_edit::AnnotatedWorkspaceEdit,
+};
 
-use super::{client::LSPData, request::LSPRequest, response::LSPResponse};
+use super::{
+  client::LSPData,
+  code_action::{CodeActionEntry, CodeActions, ServerCodeAction},
+  code_lens::{CodeLensEntry, CodeLensStore},
+  command::CommandRegistry,
+  completion::{AcceptedCompletion, CompletionEntry, CompletionSession},
+  diagnostics::{DiagnosticStore, FileDiagnostics},
+  hierarchy::{Hierarchy, HierarchyDirection, HierarchyEntry, HierarchyKind},
+  hover::HoverContent,
+  inlay_hint::InlayHintStore,
+  lifecycle::ServerState,
+  navigation::{NavigationKind, NavigationTarget},
+  rename::{AnnotatedRename, RenameTarget},
+  semantic_tokens::SemanticTokenStore,
+  signature::SignatureHelpContent,
+  subscription::{Subscriber, Subscription},
+  symbol::{OutlineSymbol, WorkspaceSymbolEntry, WorkspaceSymbols},
+  trace::TrafficEntry,
+};
 
 #[derive(Debug, Clone)]
 pub(crate) struct LSPManager {
   lsps: Vec<LSPData>,
+  subscriptions: Vec<Subscriber>,
+  diagnostics: DiagnosticStore,
+  inlay_hints: InlayHintStore,
+  semantic_tokens: SemanticTokenStore,
+  code_lenses: CodeLensStore,
+  commands: CommandRegistry,
+  completion: Option<CompletionSession>,
+  code_actions: Option<CodeActions>,
+  workspace_symbols: Option<WorkspaceSymbols>,
+  hierarchy: Option<Hierarchy>,
 }
 
 impl LSPManager {
   pub(crate) fn new() -> Self {
-    LSPManager { lsps: Vec::new() }
+    LSPManager {
+      lsps: Vec::new(),
+      subscriptions: Vec::new(),
+      diagnostics: DiagnosticStore::default(),
+      inlay_hints: InlayHintStore::default(),
+      semantic_tokens: SemanticTokenStore::default(),
+      code_lenses: CodeLensStore::default(),
+      commands: CommandRegistry::default(),
+      completion: None,
+      code_actions: None,
+      workspace_symbols: None,
+      hierarchy: None,
+    }
   }
 
   pub(crate) fn add_lsp(&mut self, lsp: LSPData) {
+    for subscriber in &self.subscriptions {
+      lsp.share_subscriber(subscriber);
+    }
+    self.diagnostics.watch_refresh(&lsp);
+    self.inlay_hints.watch_refresh(&lsp);
+    self.semantic_tokens.watch_refresh(&lsp);
+    self.code_lenses.watch_refresh(&lsp);
     self.lsps.push(lsp);
   }
 
-  pub(crate) async fn send_req<T>(
+  ///Subscribes to a notification of every server, including the ones added later
+  pub(crate) fn subscribe<T>(&mut self) -> Subscription<T>
+  where
+    T: LSPNotificationTrait,
+  {
+    let (subscriber, subscription) = Subscription::new();
+    for lsp in &self.lsps {
+      lsp.share_subscriber(&subscriber);
+    }
+    self.subscriptions.push(subscriber);
+    subscription
+  }
+
+  ///Emits every notification of type `T` as event to the frontend
+  pub(crate) fn forward<T>(&mut self, app: AppHandle, event: &'static str)
+  where
+    T: LSPNotificationTrait + 'static,
+    T::Params: Clone,
+  {
+    let subscription = self.subscribe::<T>();
+    async_std::task::spawn(async move {
+      while let Some(params) = subscription.recv().await {
+        if let Err(err) = app.emit(event, params) {
+          eprintln!("Error forwarding {} notification: {}", T::METHOD, err);
+        }
+      }
+    });
+  }
+
+  ///Stores the diagnostics servers push
+  pub(crate) fn track_diagnostics(&mut self) {
+    let subscription = self.subscribe::<PublishDiagnostics>();
+    let diagnostics = self.diagnostics.clone();
+    async_std::task::spawn(async move {
+      while let Some(params) = subscription.recv().await {
+        diagnostics.publish(params);
+      }
+    });
+  }
+
+  ///Emits every change of the stored diagnostics as `lsp-diagnostics` event
+  pub(crate) fn emit_diagnostics(&self, app: AppHandle) {
+    self.diagnostics.set_app(app);
+  }
+
+  ///Emits the files whose inlay hints a server refreshed as `lsp-inlay-hint-refresh` event
+  pub(crate) fn emit_inlay_hint_refresh(&self, app: AppHandle) {
+    self.inlay_hints.set_app(app);
+  }
+
+  ///Emits the files whose semantic tokens a server refreshed as `lsp-semantic-tokens-refresh`
+  ///event
+  pub(crate) fn emit_semantic_tokens_refresh(&self, app: AppHandle) {
+    self.semantic_tokens.set_app(app);
+  }
+
+  ///Emits the files whose code lenses a server refreshed as `lsp-code-lens-refresh` event
+  pub(crate) fn emit_code_lens_refresh(&self, app: AppHandle) {
+    self.code_lenses.set_app(app);
+  }
+
+  ///Runs `command` in the editor instead of sending it to the server
+  pub(crate) fn register_command<F>(&self, command: &str, handler: F)
+  where
+    F: Fn(Command) -> Result<Option<Value>, Error> + Send + Sync + 'static,
+  {
+    self.commands.register(command, handler);
+  }
+
+  pub(crate) fn get_diagnostics(
     &self,
-    req: LSPRequest<T>,
-    path: Url,
-  ) -> Result<Option<LSPResponse<T>>, Error>
+    path: Option<&str>,
+    severity: Option<DiagnosticSeverity>,
+    source: Option<&str>,
+  ) -> Result<Vec<FileDiagnostics>, Error> {
+    let uri = path.map(file_uri).transpose()?;
+    Ok(self.diagnostics.query(uri.as_ref(), severity, source))
+  }
+
+  ///Sends the request to the server of `path` and waits for its result, see `LSPData::request`
+  pub(crate) async fn request<T>(&self, path: &Url, params: T::Params) -> Result<T::Result, Error>
   where
     T: LSPRequestTrait,
   {
-    let lsp = self.get_lsp(&path).ok_or(anyhow::anyhow!("no lsp client found for file"))?;
+    let lsp = self
+      .get_lsp(path)
+      .ok_or(anyhow::anyhow!("no lsp client found for file"))?;
+    lsp.request::<T>(params).await
+  }
 
-    lsp.send_req(req).await
+  ///Opens the file on its server, files no server is responsible for are ignored
+  pub(crate) fn open_document(&self, path: &str, version: i32, text: String) -> Result<(), Error> {
+    let uri = file_uri(path)?;
+    let Some(lsp) = self.get_lsp(&uri) else {
+      return Ok(());
+    };
+
+    let language_id = lsp.config.language_id.clone().unwrap_or_else(|| {
+      std::path::Path::new(path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_string())
+        .unwrap_or_default()
+    });
+    lsp.open_document(TextDocumentItem {
+      uri: uri.clone(),
+      language_id,
+      version,
+      text,
+    })?;
+    self.diagnostics.request_pull(lsp, &uri);
+    Ok(())
   }
 
-  fn get_lsp(&self, path: &Url) -> Option<&LSPData> {
-    self.lsps.iter().find(|lsp| {
-      lsp
-        .file_patterns
-        .iter()
-        .any(|pattern| pattern.is_match(path.as_str()))
+  pub(crate) fn change_document(
+    &self,
+    path: &str,
+    version: i32,
+    edits: Vec<TextEdit>,
+    text: String,
+  ) -> Result<(), Error> {
+    let uri = file_uri(path)?;
+    let Some(lsp) = self.get_lsp(&uri) else {
+      return Ok(());
+    };
+
+    lsp.change_document(&uri, version, edits, text)?;
+    self.diagnostics.request_pull(lsp, &uri);
+    Ok(())
+  }
+
+  pub(crate) fn save_document(&self, path: &str) -> Result<(), Error> {
+    let uri = file_uri(path)?;
+    match self.get_lsp(&uri) {
+      Some(lsp) => lsp.save_document(&uri),
+      None => Ok(()),
+    }
+  }
+
+  pub(crate) fn close_document(&self, path: &str) -> Result<(), Error> {
+    let uri = file_uri(path)?;
+    self.diagnostics.clear_pulled(&uri);
+    self.inlay_hints.clear(&uri);
+    self.semantic_tokens.clear(&uri);
+    self.code_lenses.clear(&uri);
+    match self.get_lsp(&uri) {
+      Some(lsp) => lsp.close_document(uri),
+      None => Ok(()),
+    }
+  }
+
+  ///Completes the word at `position`, `trigger` is the character that was just typed.
+  ///`line` is the content of the line at `position`.
+  pub(crate) async fn complete(
+    &mut self,
+    path: &str,
+    position: Position,
+    trigger: Option<&str>,
+    line: &str,
+  ) -> Result<Vec<CompletionEntry>, Error> {
+    let uri = file_uri(path)?;
+    let previous = self.completion.take();
+    let Some(lsp) = self.get_lsp(&uri) else {
+      return Ok(Vec::new());
+    };
+
+    let session = CompletionSession::start(lsp, previous, uri, position, trigger, line).await?;
+    let entries = session
+      .as_ref()
+      .map(|session| session.entries(line, position))
+      .unwrap_or_default();
+    self.completion = session;
+    Ok(entries)
+  }
+
+  pub(crate) async fn resolve_completion(
+    &mut self,
+    path: &str,
+    id: usize,
+  ) -> Result<CompletionItem, Error> {
+    let (lsp, session) = self.completion_session(path)?;
+    session.resolve(&lsp, id).await
+  }
+
+  ///Ends the completion and returns the edits inserting the item
+  pub(crate) async fn accept_completion(
+    &mut self,
+    path: &str,
+    id: usize,
+    position: Position,
+  ) -> Result<AcceptedCompletion, Error> {
+    let (lsp, session) = self.completion_session(path)?;
+    let accepted = session.accept(&lsp, id, position).await;
+    self.completion = None;
+    accepted
+  }
+
+  fn completion_session(&mut self, path: &str) -> Result<(LSPData, &mut CompletionSession), Error> {
+    let uri = file_uri(path)?;
+    let lsp = self
+      .get_lsp(&uri)
+      .cloned()
+      .ok_or(anyhow::anyhow!("no lsp client found for file"))?;
+    let session = self
+      .completion
+      .as_mut()
+      .filter(|session| session.uri() == &uri)
+      .ok_or(anyhow::anyhow!("No completion for {}", path))?;
+    Ok((lsp, session))
+  }
+
+  ///Code actions for `range` with the diagnostics touching it, `only` filters them by kind
+  pub(crate) async fn code_actions(
+    &mut self,
+    path: &str,
+    range: Range,
+    only: Option<Vec<CodeActionKind>>,
+  ) -> Result<Vec<CodeActionEntry>, Error> {
+    let uri = file_uri(path)?;
+    self.code_actions = None;
+    let Some(lsp) = self.get_lsp(&uri) else {
+      return Ok(Vec::new());
+    };
+
+    let diagnostics = self.diagnostics.in_range(&uri, range);
+    let actions = CodeActions::request(lsp, uri, range, diagnostics, only).await?;
+    let entries = actions.entries();
+    self.code_actions = Some(actions);
+    Ok(entries)
+  }
+
+  ///The action with its edit, it still has to be applied and its command executed
+  pub(crate) async fn resolve_code_action(
+    &mut self,
+    path: &str,
+    id: usize,
+  ) -> Result<ServerCodeAction, Error> {
+    let uri = file_uri(path)?;
+    let lsp = self
+      .get_lsp(&uri)
+      .cloned()
+      .ok_or(anyhow::anyhow!("no lsp client found for file"))?;
+    let actions = self
+      .code_actions
+      .as_mut()
+      .filter(|actions| actions.uri() == &uri)
+      .ok_or(anyhow::anyhow!("No code actions for {}", path))?;
+    actions.resolve(&lsp, id).await
+  }
+
+  ///Runs the command in the editor if it was registered, or else on the server of `path`. The
+  ///returned future does not borrow the manager, so it can be awaited without its lock while the
+  ///server asks for edits to be applied.
+  pub(crate) fn execute_command(
+    &self,
+    path: &str,
+    command: Command,
+  ) -> Result<impl Future<Output = Result<Option<Value>, Error>>, Error> {
+    let uri = file_uri(path)?;
+    let handler = self.commands.get(&command.command);
+    let lsp = self.get_lsp(&uri).cloned();
+
+    Ok(async move {
+      match (handler, lsp) {
+        (Some(handler), _) => handler(command),
+        (None, Some(lsp)) => {
+          lsp
+            .request::<ExecuteCommand>(ExecuteCommandParams {
+              command: command.command,
+              arguments: command.arguments.unwrap_or_default(),
+              work_done_progress_params: Default::default(),
+            })
+            .await
+        }
+        (None, None) => Err(anyhow::anyhow!("no lsp client found for file")),
+      }
     })
   }
 
-  pub(crate) async fn get_semantic_tokens(
+  ///Edits formatting the file, or only `range` of it if set
+  pub(crate) async fn format(
     &self,
     path: &str,
-    content: &[String],
-  ) -> Option<Vec<Vec<Token>>> {
-    let uri = Url::parse(&format!("file://{}", path)).ok()?;
-    let test = LSPRequest::<SemanticTokensFullRequest>::new(Some(SemanticTokensParams {
-      text_document: lsp_types::TextDocumentIdentifier { uri: uri.clone() },
-      partial_result_params: Default::default(),
+    range: Option<Range>,
+    options: FormattingOptions,
+  ) -> Result<Vec<TextEdit>, Error> {
+    let uri = file_uri(path)?;
+    let Some(lsp) = self.get_lsp(&uri) else {
+      return Ok(Vec::new());
+    };
+
+    let text_document = TextDocumentIdentifier { uri };
+    let edits = match range {
+      Some(range) => {
+        lsp
+          .request::<RangeFormatting>(DocumentRangeFormattingParams {
+            text_document,
+            range,
+            options,
+            work_done_progress_params: Default::default(),
+          })
+          .await?
+      }
+      None => {
+        lsp
+          .request::<Formatting>(DocumentFormattingParams {
+            text_document,
+            options,
+            work_done_progress_params: Default::default(),
+          })
+          .await?
+      }
+    };
+    Ok(edits.unwrap_or_default())
+  }
+
+  ///Edits formatting the code at `position` after `typed` was typed there, no edits are requested
+  ///for characters the server does not format on
+  pub(crate) async fn format_on_type(
+    &self,
+    path: &str,
+    position: Position,
+    typed: &str,
+    options: FormattingOptions,
+  ) -> Result<Vec<TextEdit>, Error> {
+    let uri = file_uri(path)?;
+    let Some(lsp) = self.get_lsp(&uri) else {
+      return Ok(Vec::new());
+    };
+    let triggers = lsp
+      .lsp_info()
+      .map(|info| info.on_type_formatting_triggers)
+      .unwrap_or_default();
+    if !triggers.iter().any(|trigger| trigger == typed) {
+      return Ok(Vec::new());
+    }
+
+    let edits = lsp
+      .request::<OnTypeFormatting>(DocumentOnTypeFormattingParams {
+        text_document_position: TextDocumentPositionParams {
+          text_document: TextDocumentIdentifier { uri },
+          position,
+        },
+        ch: typed.to_string(),
+        options,
+      })
+      .await?;
+    Ok(edits.unwrap_or_default())
+  }
+
+  ///Folding ranges of the server, empty if it has none
+  pub(crate) async fn folding_ranges(&self, path: &str) -> Result<Vec<FoldingRange>, Error> {
+    let uri = file_uri(path)?;
+    let Some(lsp) = self.get_lsp(&uri) else {
+      return Ok(Vec::new());
+    };
+    if !lsp.lsp_info().is_some_and(|info| info.folding_range) {
+      return Ok(Vec::new());
+    }
+
+    let ranges = lsp
+      .request::<FoldingRangeRequest>(FoldingRangeParams {
+        text_document: TextDocumentIdentifier { uri },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+      })
+      .await?;
+    Ok(ranges.unwrap_or_default())
+  }
+
+  ///Occurrences of the symbol at `position` in the file, marked as read or write if the server
+  ///knows it
+  pub(crate) async fn document_highlights(
+    &self,
+    path: &str,
+    position: Position,
+  ) -> Result<Vec<DocumentHighlight>, Error> {
+    let uri = file_uri(path)?;
+    let Some(lsp) = self.get_lsp(&uri) else {
+      return Ok(Vec::new());
+    };
+    if !lsp.lsp_info().is_some_and(|info| info.document_highlight) {
+      return Ok(Vec::new());
+    }
+
+    let highlights = lsp
+      .request::<DocumentHighlightRequest>(DocumentHighlightParams {
+        text_document_position_params: TextDocumentPositionParams {
+          text_document: TextDocumentIdentifier { uri },
+          position,
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+      })
+      .await?;
+    Ok(highlights.unwrap_or_default())
+  }
+
+  ///Ranges that have to be edited together with the one at `position`, like the opening and
+  ///closing tag of an HTML element
+  pub(crate) async fn linked_editing_ranges(
+    &self,
+    path: &str,
+    position: Position,
+  ) -> Result<Option<LinkedEditingRanges>, Error> {
+    let uri = file_uri(path)?;
+    let Some(lsp) = self.get_lsp(&uri) else {
+      return Ok(None);
+    };
+    if !lsp.lsp_info().is_some_and(|info| info.linked_editing_range) {
+      return Ok(None);
+    }
+
+    lsp
+      .request::<LinkedEditingRange>(LinkedEditingRangeParams {
+        text_document_position_params: TextDocumentPositionParams {
+          text_document: TextDocumentIdentifier { uri },
+          position,
+        },
+        work_done_progress_params: Default::default(),
+      })
+      .await
+  }
+
+  ///Inlay hints of `range` as virtual tokens together with the line they are shown in
+  pub(crate) async fn inlay_hints(
+    &self,
+    path: &str,
+    range: Range,
+  ) -> Result<Vec<(u32, Token)>, Error> {
+    let uri = file_uri(path)?;
+    let Some(lsp) = self.get_lsp(&uri) else {
+      return Ok(Vec::new());
+    };
+
+    self.inlay_hints.request(lsp, &uri, range).await
+  }
+
+  ///The hint shown at `position`, resolved so its tooltips and label locations are set
+  pub(crate) async fn resolve_inlay_hint(
+    &self,
+    path: &str,
+    position: Position,
+  ) -> Result<Option<InlayHint>, Error> {
+    let uri = file_uri(path)?;
+    let Some(lsp) = self.get_lsp(&uri) else {
+      return Ok(None);
+    };
+
+    self.inlay_hints.resolve(lsp, &uri, position).await
+  }
+
+  ///Lenses of the file, their commands may be missing until they are resolved
+  pub(crate) async fn code_lenses(&self, path: &str) -> Result<Vec<CodeLensEntry>, Error> {
+    let uri = file_uri(path)?;
+    let Some(lsp) = self.get_lsp(&uri) else {
+      return Ok(Vec::new());
+    };
+
+    self.code_lenses.request(lsp, &uri).await
+  }
+
+  ///The lens with its command, like the number of references once it was counted
+  pub(crate) async fn resolve_code_lens(
+    &self,
+    path: &str,
+    id: usize,
+  ) -> Result<CodeLensEntry, Error> {
+    let uri = file_uri(path)?;
+    let lsp = self
+      .get_lsp(&uri)
+      .ok_or(anyhow::anyhow!("no lsp client found for file"))?;
+
+    self.code_lenses.resolve(lsp, &uri, id).await
+  }
+
+  pub(crate) async fn hover(
+    &self,
+    path: &str,
+    position: Position,
+    parsers: &ParsersManager,
+  ) -> Result<Option<HoverContent>, Error> {
+    let uri = file_uri(path)?;
+    let Some(lsp) = self.get_lsp(&uri) else {
+      return Ok(None);
+    };
+
+    let hover = lsp
+      .request::<HoverRequest>(HoverParams {
+        text_document_position_params: TextDocumentPositionParams {
+          text_document: TextDocumentIdentifier { uri },
+          position,
+        },
+        work_done_progress_params: Default::default(),
+      })
+      .await?;
+    Ok(hover.map(|hover| HoverContent::new(hover, parsers, path)))
+  }
+
+  ///`trigger` is the character that was just typed, `None` if the help was invoked explicitly
+  pub(crate) async fn signature_help(
+    &self,
+    path: &str,
+    position: Position,
+    trigger: Option<String>,
+    parsers: &ParsersManager,
+  ) -> Result<Option<SignatureHelpContent>, Error> {
+    let uri = file_uri(path)?;
+    let Some(lsp) = self.get_lsp(&uri) else {
+      return Ok(None);
+    };
+
+    let trigger_kind = match trigger {
+      Some(_) => SignatureHelpTriggerKind::TRIGGER_CHARACTER,
+      None => SignatureHelpTriggerKind::INVOKED,
+    };
+    let help = lsp
+      .request::<SignatureHelpRequest>(SignatureHelpParams {
+        context: Some(SignatureHelpContext {
+          trigger_kind,
+          trigger_character: trigger,
+          is_retrigger: false,
+          active_signature_help: None,
+        }),
+        text_document_position_params: TextDocumentPositionParams {
+          text_document: TextDocumentIdentifier { uri },
+          position,
+        },
+        work_done_progress_params: Default::default(),
+      })
+      .await?;
+
+    let encoding = lsp.lsp_info().and_then(|info| info.position_encoding);
+    Ok(help.map(|help| SignatureHelpContent::new(help, encoding.as_ref(), parsers, path)))
+  }
+
+  pub(crate) async fn navigate(
+    &self,
+    path: &str,
+    position: Position,
+    kind: NavigationKind,
+  ) -> Result<Vec<NavigationTarget>, Error> {
+    let uri = file_uri(path)?;
+    let position_params = TextDocumentPositionParams {
+      text_document: TextDocumentIdentifier { uri: uri.clone() },
+      position,
+    };
+    let goto = || GotoDefinitionParams {
+      text_document_position_params: position_params.clone(),
       work_done_progress_params: Default::default(),
-    }));
+      partial_result_params: Default::default(),
+    };
 
-    let res = self
-      .send_req(test, uri.clone())
+    let response = match kind {
+      NavigationKind::Definition => self.request::<GotoDefinition>(&uri, goto()).await?,
+      NavigationKind::Declaration => self.request::<GotoDeclaration>(&uri, goto()).await?,
+      NavigationKind::TypeDefinition => self.request::<GotoTypeDefinition>(&uri, goto()).await?,
+      NavigationKind::Implementation => self.request::<GotoImplementation>(&uri, goto()).await?,
+      NavigationKind::References => {
+        let params = ReferenceParams {
+          text_document_position: position_params.clone(),
+          context: ReferenceContext {
+            include_declaration: true,
+          },
+          work_done_progress_params: Default::default(),
+          partial_result_params: Default::default(),
+        };
+        let locations = self.request::<References>(&uri, params).await?;
+        return Ok(NavigationTarget::from_locations(
+          locations.unwrap_or_default(),
+        ));
+      }
+    };
+
+    Ok(NavigationTarget::from_response(response))
+  }
+
+  ///What a rename at `position` replaces, `line` is the content of the line at `position`
+  pub(crate) async fn prepare_rename(
+    &self,
+    path: &str,
+    position: Position,
+    line: &str,
+  ) -> Result<Option<RenameTarget>, Error> {
+    let uri = file_uri(path)?;
+    let Some(lsp) = self.get_lsp(&uri) else {
+      return Ok(None);
+    };
+    let encoding = lsp.lsp_info().and_then(|info| info.position_encoding);
+    let prepare = lsp.lsp_info().map(|info| info.prepare_rename);
+    if prepare != Some(true) {
+      return Ok(RenameTarget::identifier(position, line, encoding.as_ref()));
+    }
+
+    let response = lsp
+      .request::<PrepareRenameRequest>(TextDocumentPositionParams {
+        text_document: TextDocumentIdentifier { uri },
+        position,
+      })
+      .await?;
+    let target = RenameTarget::new(response, position, line, encoding.as_ref());
+    Ok(target)
+  }
+
+  ///Edit renaming the symbol at `position`, it still has to be applied
+  pub(crate) async fn rename(
+    &self,
+    path: &str,
+    position: Position,
+    new_name: String,
+  ) -> Result<Option<AnnotatedWorkspaceEdit>, Error> {
+    let uri = file_uri(path)?;
+    let lsp = self
+      .get_lsp(&uri)
+      .ok_or(anyhow::anyhow!("no lsp client found for file"))?;
+
+    lsp
+      .request::<AnnotatedRename>(RenameParams {
+        text_document_position: TextDocumentPositionParams {
+          text_document: TextDocumentIdentifier { uri },
+          position,
+        },
+        new_name,
+        work_done_progress_params: Default::default(),
+      })
+      .await
+  }
+
+  ///Symbols of the file nested like they are declared, only the ones matching `query` if it is set
+  pub(crate) async fn outline(
+    &self,
+    path: &str,
+    query: Option<&str>,
+  ) -> Result<Vec<OutlineSymbol>, Error> {
+    let uri = file_uri(path)?;
+    let Some(lsp) = self.get_lsp(&uri) else {
+      return Ok(Vec::new());
+    };
+
+    let symbols = OutlineSymbol::request(lsp, uri).await?;
+    Ok(match query {
+      Some(query) => OutlineSymbol::search(symbols, query),
+      None => symbols,
+    })
+  }
+
+  ///Symbols of every server matching `query`, the best matches first
+  pub(crate) async fn workspace_symbols(
+    &mut self,
+    query: &str,
+  ) -> Result<Vec<WorkspaceSymbolEntry>, Error> {
+    self.workspace_symbols = None;
+    let symbols = WorkspaceSymbols::request(&self.lsps, query).await?;
+    let entries = symbols.entries(query);
+    self.workspace_symbols = Some(symbols);
+    Ok(entries)
+  }
+
+  pub(crate) async fn resolve_workspace_symbol(
+    &mut self,
+    id: usize,
+  ) -> Result<NavigationTarget, Error> {
+    self
+      .workspace_symbols
+      .as_mut()
+      .ok_or(anyhow::anyhow!("No workspace symbols"))?
+      .resolve(id)
       .await
-      .ok()??
-      .result
-      .ok()??;
+  }
 
-    let data = match res {
-      SemanticTokensResult::Partial(partial) => partial.data,
-      SemanticTokensResult::Tokens(full) => full.data,
+  ///Starts a call or type hierarchy at `position`, its items are expanded with `expand_hierarchy`
+  pub(crate) async fn prepare_hierarchy(
+    &mut self,
+    path: &str,
+    position: Position,
+    kind: HierarchyKind,
+  ) -> Result<Vec<HierarchyEntry>, Error> {
+    let uri = file_uri(path)?;
+    self.hierarchy = None;
+    let Some(lsp) = self.get_lsp(&uri) else {
+      return Ok(Vec::new());
     };
 
-    let token_info = self
-      .get_lsp(&uri)?
-      .lsp_info
-      .as_ref()?
-      .semantic_token_info
-      .as_ref()?;
+    let hierarchy = Hierarchy::prepare(lsp, uri, position, kind).await?;
+    let roots = hierarchy.roots();
+    self.hierarchy = Some(hierarchy);
+    Ok(roots)
+  }
+
+  pub(crate) async fn expand_hierarchy(
+    &mut self,
+    id: usize,
+    direction: HierarchyDirection,
+  ) -> Result<Vec<HierarchyEntry>, Error> {
+    self
+      .hierarchy
+      .as_mut()
+      .ok_or(anyhow::anyhow!("No hierarchy prepared"))?
+      .expand(id, direction)
+      .await
+  }
+
+  pub(crate) fn get_state(&self, path: &Url) -> Option<ServerState> {
+    self.get_lsp(path).map(|lsp| lsp.state())
+  }
+
+  pub(crate) fn get_traffic(&self, path: &Url) -> Option<Vec<TrafficEntry>> {
+    self.get_lsp(path).map(|lsp| lsp.traffic())
+  }
+
+  pub(crate) fn set_trace(&self, path: &Url, value: TraceValue) -> Result<(), Error> {
+    self
+      .get_lsp(path)
+      .ok_or(anyhow::anyhow!("no lsp client found for file"))?
+      .set_trace(value)
+  }
+
+  pub(crate) async fn shutdown(&self) {
+    for lsp in &self.lsps {
+      if let Err(err) = lsp.shutdown().await {
+        eprintln!("Error shutting down language server: {}", err);
+      }
+    }
+  }
+
+  ///Encoding of the positions exchanged with the server of the file, `None` means UTF-16
+  pub(crate) fn position_encoding(&self, path: &str) -> Option<PositionEncodingKind> {
+    let uri = Url::from_file_path(path).ok()?;
+    self.get_lsp(&uri)?.lsp_info()?.position_encoding
+  }
+
+  fn get_lsp(&self, path: &Url) -> Option<&LSPData> {
+    self.lsps.iter().find(|lsp| lsp.matches(path))
+  }
+
+  ///Tokens of `viewport` or of the whole document, split into lines
+  pub(crate) async fn get_semantic_tokens(
+    &self,
+    path: &str,
+    content: &[String],
+    viewport: Option<Range>,
+  ) -> Option<Vec<Vec<Token>>> {
+    let uri = Url::from_file_path(path).ok()?;
+    let lsp = self.get_lsp(&uri)?;
+    let token_info = lsp.lsp_info()?.semantic_token_info?;
+
+    let data = match viewport {
+      Some(range) => self.semantic_tokens.range(lsp, &uri, range).await,
+      None => self.semantic_tokens.full(lsp, &uri).await,
+    };
+    let data = match data {
+      Ok(data) => data,
+      Err(err) => {
+        eprintln!("Error requesting semantic tokens for {}: {}", path, err);
+        return None;
+      }
+    };
 
     let mut tokens = vec![Vec::new()];
     let mut current_token = 0;
@@ -93,11 +863,13 @@ impl LSPManager {
       current_token += token.delta_start;
       let new_token = Token {
         start: current_token,
-        token: content[current_line]
-          [current_token as usize..(current_token + token.length) as usize]
+        token: content
+          .get(current_line)?
+          .get(current_token as usize..(current_token + token.length) as usize)?
           .to_string(),
         type_: token_type,
         modifiers: Some(token_modifiers),
+        ..Default::default()
       };
 
       tokens.last_mut()?.push(new_token);
@@ -106,3 +878,7 @@ impl LSPManager {
     Some(tokens)
   }
 }
+
+fn file_uri(path: &str) -> Result<Url, Error> {
+  Url::from_file_path(path).map_err(|_| anyhow::anyhow!("Invalid file path {}", path))
+}
@@ -0,0 +1,82 @@
+use std::path::Path;
+
+use lsp_types::{GotoDefinitionResponse, Location, LocationLink, Range};
+use serde::{Deserialize, Serialize};
+
+use super::utils::file_name;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum NavigationKind {
+  Definition,
+  Declaration,
+  TypeDefinition,
+  Implementation,
+  ///Every reference including the declaration
+  References,
+}
+
+///Location to jump to or to show in a peek list
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct NavigationTarget {
+  ///Path for files, the uri for anything else
+  pub(crate) file: String,
+  ///Whole target, for a definition including its body
+  pub(crate) range: Range,
+  ///Part of the target to select, like the name of a definition
+  pub(crate) selection_range: Range,
+  ///Range of the symbol the navigation started from if the server sent it
+  pub(crate) origin_range: Option<Range>,
+}
+
+impl NavigationTarget {
+  ///Locations, arrays and links of every goto request become one list without duplicates
+  pub(crate) fn from_response(response: Option<GotoDefinitionResponse>) -> Vec<Self> {
+    match response {
+      Some(GotoDefinitionResponse::Scalar(location)) => vec![Self::from_location(location)],
+      Some(GotoDefinitionResponse::Array(locations)) => Self::from_locations(locations),
+      Some(GotoDefinitionResponse::Link(links)) => {
+        dedup(links.into_iter().map(Self::from_link).collect())
+      }
+      None => Vec::new(),
+    }
+  }
+
+  pub(crate) fn from_locations(locations: Vec<Location>) -> Vec<Self> {
+    dedup(locations.into_iter().map(Self::from_location).collect())
+  }
+
+  ///Opening targets only makes sense for files on disk
+  pub(crate) fn is_file(&self) -> bool {
+    Path::new(&self.file).is_absolute()
+  }
+
+  fn from_location(location: Location) -> Self {
+    NavigationTarget {
+      file: file_name(&location.uri),
+      range: location.range,
+      selection_range: location.range,
+      origin_range: None,
+    }
+  }
+
+  fn from_link(link: LocationLink) -> Self {
+    NavigationTarget {
+      file: file_name(&link.target_uri),
+      range: link.target_range,
+      selection_range: link.target_selection_range,
+      origin_range: link.origin_selection_range,
+    }
+  }
+}
+
+fn dedup(targets: Vec<NavigationTarget>) -> Vec<NavigationTarget> {
+  let mut unique: Vec<NavigationTarget> = Vec::with_capacity(targets.len());
+  for target in targets {
+    if !unique.contains(&target) {
+      unique.push(target);
+    }
+  }
+  unique
+}
@@ -1,6 +1,7 @@
 use anyhow::Error;
 use lsp_types::notification::Notification as LSPNotificationTrait;
 use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
 
 use super::utils::Version;
 
@@ -14,11 +15,11 @@ where
   params: Option<T::Params>,
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub(crate) struct RawLSPNotification {
   jsonrpc: Version,
   pub method: String,
-  params: String,
+  pub params: Value,
 }
 
 impl<T: LSPNotificationTrait> LSPNotification<T> {
@@ -37,26 +38,15 @@ impl<T: LSPNotificationTrait> LSPNotification<T> {
   }
 }
 
-impl RawLSPNotification {
-  pub(crate) fn parse<T: LSPNotificationTrait>(&self) -> Result<LSPNotification<T>, Error> {
-    let params = serde_json::from_str(&self.params)?;
-    Ok(LSPNotification {
-      jsonrpc: self.jsonrpc,
-      method: self.method.clone(),
-      params: Some(params),
-    })
-  }
-}
-
 impl<'de> Deserialize<'de> for RawLSPNotification {
   fn deserialize<D>(deserializer: D) -> Result<RawLSPNotification, D::Error>
   where
     D: Deserializer<'de>,
   {
-    let value: serde_json::Value = Deserialize::deserialize(deserializer)?;
+    let mut value: Value = Deserialize::deserialize(deserializer)?;
     let jsonrpc = Version::V2;
     let method = value["method"].as_str().unwrap_or_default().to_string();
-    let params = value["params"].to_string();
+    let params = value["params"].take();
 
     Ok(RawLSPNotification {
       jsonrpc,
@@ -64,5 +54,4 @@ impl<'de> Deserialize<'de> for RawLSPNotification {
       params,
     })
   }
-    
-}
\ No newline at end of file
+}
@@ -0,0 +1,118 @@
+use std::{
+  fs::{create_dir_all, File, OpenOptions},
+  io::{BufRead, BufReader, Write},
+  path::{Path, PathBuf},
+  process::{Child, ChildStderr, ChildStdin, ChildStdout, Command, Stdio},
+  thread,
+};
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+use anyhow::Error;
+
+///Prevents a console window from popping up for every language server.
+#[cfg(target_os = "windows")]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) struct SpawnOptions {
+  pub(crate) path: String,
+  pub(crate) args: Vec<String>,
+  pub(crate) cwd: Option<PathBuf>,
+  pub(crate) env: Vec<(String, String)>,
+  pub(crate) stderr_log: PathBuf,
+}
+
+#[derive(Debug)]
+pub(crate) struct LSPProcess {
+  pub(crate) child: Child,
+  pub(crate) stdin: ChildStdin,
+  pub(crate) stdout: ChildStdout,
+}
+
+impl LSPProcess {
+  pub(crate) fn spawn(options: &SpawnOptions) -> Result<Self, Error> {
+    let mut command = Command::new(&options.path);
+    command
+      .args(&options.args)
+      .envs(options.env.iter().map(|(key, value)| (key, value)))
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped());
+
+    if let Some(cwd) = &options.cwd {
+      command.current_dir(cwd);
+    }
+
+    #[cfg(target_os = "windows")]
+    command.creation_flags(CREATE_NO_WINDOW);
+
+    let log = open_log(&options.stderr_log)?;
+    let mut child = command
+      .spawn()
+      .map_err(|err| anyhow::anyhow!("failed to spawn {}: {}", options.path, err))?;
+
+    let stdin = child
+      .stdin
+      .take()
+      .ok_or(anyhow::anyhow!("failed to open stdin"))?;
+
+    let stdout = child
+      .stdout
+      .take()
+      .ok_or(anyhow::anyhow!("failed to open stdout"))?;
+
+    let stderr = child
+      .stderr
+      .take()
+      .ok_or(anyhow::anyhow!("failed to open stderr"))?;
+
+    capture_stderr(stderr, log);
+
+    Ok(LSPProcess {
+      child,
+      stdin,
+      stdout,
+    })
+  }
+}
+
+///Default location of the stderr log for a server binary if none was configured
+pub(crate) fn default_stderr_log(path: &str) -> PathBuf {
+  let name = Path::new(path)
+    .file_stem()
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or_else(|| "lsp".to_string());
+
+  std::env::temp_dir()
+    .join("codeforge")
+    .join("lsp")
+    .join(format!("{}.log", name))
+}
+
+fn open_log(path: &Path) -> Result<File, Error> {
+  if let Some(parent) = path.parent() {
+    create_dir_all(parent)?;
+  }
+
+  let log = OpenOptions::new().create(true).append(true).open(path)?;
+  Ok(log)
+}
+
+fn capture_stderr(stderr: ChildStderr, mut log: File) {
+  thread::spawn(move || {
+    let mut reader = BufReader::new(stderr);
+    let mut line = Vec::new();
+
+    //keep draining even if the log is not writable, otherwise the server blocks on a full pipe
+    while let Ok(size) = reader.read_until(b'\n', &mut line) {
+      if size == 0 {
+        break;
+      }
+      let _ = log.write_all(&line);
+      let _ = log.flush();
+      line.clear();
+    }
+  });
+}
@@ -0,0 +1,120 @@
+use lsp_types::{
+  request::{Rename, Request},
+  Position, PositionEncodingKind, PrepareRenameResponse, Range, RenameParams,
+};
+use serde::Serialize;
+
+use crate::file::workspace_edit::AnnotatedWorkspaceEdit;
+
+use super::encoding::{byte_offset, encoded_len};
+
+///`textDocument/rename` with the annotations of the text edits of the result
+pub(crate) enum AnnotatedRename {}
+
+impl Request for AnnotatedRename {
+  type Params = RenameParams;
+  type Result = Option<AnnotatedWorkspaceEdit>;
+  const METHOD: &'static str = Rename::METHOD;
+}
+
+///What a rename replaces, shown before the new name is entered
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RenameTarget {
+  pub(crate) range: Range,
+  ///Text the new name starts as
+  pub(crate) placeholder: String,
+}
+
+impl RenameTarget {
+  ///`line` is the content of the line at `position`, `None` if nothing can be renamed there.
+  ///Columns are counted in `encoding`.
+  pub(crate) fn new(
+    response: Option<PrepareRenameResponse>,
+    position: Position,
+    line: &str,
+    encoding: Option<&PositionEncodingKind>,
+  ) -> Option<Self> {
+    match response? {
+      PrepareRenameResponse::Range(range) => Some(RenameTarget {
+        placeholder: text_in(line, range, encoding),
+        range,
+      }),
+      PrepareRenameResponse::RangeWithPlaceholder { range, placeholder } => {
+        Some(RenameTarget { range, placeholder })
+      }
+      //the server leaves it to the client to find the identifier
+      PrepareRenameResponse::DefaultBehavior { default_behavior } => default_behavior
+        .then(|| Self::identifier(position, line, encoding))
+        .flatten(),
+    }
+  }
+
+  ///The identifier around `position`, used for servers that cannot prepare a rename
+  pub(crate) fn identifier(
+    position: Position,
+    line: &str,
+    encoding: Option<&PositionEncodingKind>,
+  ) -> Option<Self> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let character = byte_offset(line, position.character, encoding).unwrap_or(line.len());
+    let start = line[..character]
+      .char_indices()
+      .rev()
+      .take_while(|(_, c)| is_word(*c))
+      .last()
+      .map_or(character, |(start, _)| start);
+    let end = character
+      + line[character..]
+        .find(|c| !is_word(c))
+        .unwrap_or(line.len() - character);
+    if start == end {
+      return None;
+    }
+
+    Some(RenameTarget {
+      range: Range::new(
+        Position::new(position.line, encoded_len(&line[..start], encoding)),
+        Position::new(position.line, encoded_len(&line[..end], encoding)),
+      ),
+      placeholder: line[start..end].to_string(),
+    })
+  }
+}
+
+///Text of the range on `line`, ranges over several lines are cut at its end
+fn text_in(line: &str, range: Range, encoding: Option<&PositionEncodingKind>) -> String {
+  let start = byte_offset(line, range.start.character, encoding);
+  let end = match range.end.line == range.start.line {
+    true => byte_offset(line, range.end.character, encoding),
+    false => Some(line.len()),
+  };
+  start
+    .zip(end)
+    .and_then(|(start, end)| line.get(start..end))
+    .unwrap_or_default()
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+  use lsp_types::{Position, PrepareRenameResponse, Range};
+
+  use super::RenameTarget;
+
+  #[test]
+  fn targets_are_found_in_utf16_columns() {
+    //`é` is one UTF-16 code unit but two bytes
+    let line = "let é = café;";
+    let target = RenameTarget::identifier(Position::new(0, 9), line, None).unwrap();
+    assert_eq!(target.placeholder, "café");
+    assert_eq!(
+      target.range,
+      Range::new(Position::new(0, 8), Position::new(0, 12))
+    );
+
+    let range = Range::new(Position::new(0, 4), Position::new(0, 5));
+    let response = PrepareRenameResponse::Range(range);
+    let target = RenameTarget::new(Some(response), Position::new(0, 4), line, None).unwrap();
+    assert_eq!(target.placeholder, "é");
+  }
+}
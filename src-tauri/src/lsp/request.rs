@@ -1,11 +1,21 @@
-use std::sync::{Arc, Condvar, Mutex};
+use std::{
+  collections::HashMap,
+  marker::PhantomData,
+  sync::atomic::{AtomicI32, Ordering},
+  time::Duration,
+};
 
 use anyhow::Error;
-use lsp_types::request::Request as LSPRequestTrait;
-use rand::random;
-use serde::Serialize;
+use async_std::channel::{bounded, Receiver, Sender};
+use lsp_types::{request::Request as LSPRequestTrait, NumberOrString};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use super::utils::Version;
+use super::{
+  client::LSPData,
+  response::LSPResponse,
+  utils::{ErrorCode, Version},
+};
 
 #[derive(Serialize, Debug, Clone, PartialEq, Eq)]
 pub(crate) struct LSPRequest<T>
@@ -15,30 +25,71 @@ where
   jsonrpc: Version,
   method: String,
   params: Option<T::Params>,
-  id: i32,
+  id: NumberOrString,
+  #[serde(skip)]
+  timeout: Duration,
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct PendingRequest {
-  pub id: i32,
-  pub condition: Arc<(Mutex<bool>, Condvar)>,
-  pub response: Option<String>,
+///Request sent by the server to the client
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct RawLSPRequest {
+  #[serde(rename = "jsonrpc")]
+  _jsonrpc: Version,
+  pub(crate) id: NumberOrString,
+  pub(crate) method: String,
+  #[serde(default)]
+  pub(crate) params: Value,
 }
 
+///Requests sent to the server that are still waiting for a response, keyed by request id
+#[derive(Debug, Default)]
+pub(crate) struct PendingRequests {
+  requests: HashMap<NumberOrString, Sender<String>>,
+}
+
+///Handle to a request in flight, dropping it before the response arrived cancels the request
+#[derive(Debug)]
+pub(crate) struct RequestHandle<T>
+where
+  T: LSPRequestTrait,
+{
+  lsp: LSPData,
+  id: NumberOrString,
+  timeout: Duration,
+  receiver: Receiver<String>,
+  finished: bool,
+  request: PhantomData<T>,
+}
+
+///Ids are never reused so a late response cannot be mistaken for the answer to a newer request
+static NEXT_ID: AtomicI32 = AtomicI32::new(1);
+
+pub(crate) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
 impl<T: LSPRequestTrait> LSPRequest<T> {
   pub(crate) fn new(body: Option<T::Params>) -> LSPRequest<T> {
-    let id = random::<i32>().abs();
+    let id = NumberOrString::Number(NEXT_ID.fetch_add(1, Ordering::Relaxed));
 
     LSPRequest {
       jsonrpc: Version::V2,
       method: T::METHOD.to_string(),
       params: body,
       id,
+      timeout: DEFAULT_TIMEOUT,
     }
   }
 
-  pub(crate) fn get_id(&self) -> i32 {
-    self.id
+  pub(crate) fn with_timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  pub(crate) fn get_id(&self) -> NumberOrString {
+    self.id.clone()
+  }
+
+  pub(crate) fn get_timeout(&self) -> Duration {
+    self.timeout
   }
 
   pub(crate) fn as_bytes(&self) -> Result<Vec<u8>, Error> {
@@ -47,3 +98,95 @@ impl<T: LSPRequestTrait> LSPRequest<T> {
     Ok(req.as_bytes().to_vec())
   }
 }
+
+impl PendingRequests {
+  pub(crate) fn insert(&mut self, id: NumberOrString) -> Receiver<String> {
+    let (sender, receiver) = bounded(1);
+    self.requests.insert(id, sender);
+    receiver
+  }
+
+  pub(crate) fn resolve(&mut self, id: &NumberOrString, body: String) {
+    if let Some(sender) = self.requests.remove(id) {
+      //the receiver may already be gone if the request timed out
+      let _ = sender.try_send(body);
+    }
+  }
+
+  pub(crate) fn remove(&mut self, id: &NumberOrString) {
+    self.requests.remove(id);
+  }
+
+  ///Resolves the request with a `RequestCancelled` error, returns false if it was not pending anymore
+  pub(crate) fn cancel(&mut self, id: &NumberOrString) -> bool {
+    let Some(sender) = self.requests.remove(id) else {
+      return false;
+    };
+
+    let body = serde_json::json!({
+      "jsonrpc": Version::V2,
+      "id": id,
+      "error": {
+        "code": ErrorCode::RequestCancelled.code(),
+        "message": "Request cancelled",
+      },
+    });
+    let _ = sender.try_send(body.to_string());
+    true
+  }
+
+  ///Drops every pending sender so all waiting requests fail instead of hanging
+  pub(crate) fn clear(&mut self) {
+    self.requests.clear();
+  }
+}
+
+impl<T: LSPRequestTrait> RequestHandle<T> {
+  pub(crate) fn new(
+    lsp: LSPData,
+    id: NumberOrString,
+    timeout: Duration,
+    receiver: Receiver<String>,
+  ) -> Self {
+    RequestHandle {
+      lsp,
+      id,
+      timeout,
+      receiver,
+      finished: false,
+      request: PhantomData,
+    }
+  }
+
+  ///Sends `$/cancelRequest` and resolves the pending response with a `RequestCancelled` error
+  pub(crate) fn cancel(&self) -> Result<(), Error> {
+    self.lsp.cancel_req(&self.id)
+  }
+
+  pub(crate) async fn response(mut self) -> Result<Option<LSPResponse<T>>, Error> {
+    let response = async_std::future::timeout(self.timeout, self.receiver.recv()).await;
+    self.finished = true;
+
+    let Ok(response) = response else {
+      //the server may still be working on it
+      self.cancel()?;
+      return Err(anyhow::anyhow!(
+        "Request {:?} timed out after {:?}",
+        self.id,
+        self.timeout
+      ));
+    };
+    let response = response
+      .map_err(|_| anyhow::anyhow!("Server closed before answering request {:?}", self.id))?;
+
+    LSPResponse::new(response)
+  }
+}
+
+impl<T: LSPRequestTrait> Drop for RequestHandle<T> {
+  fn drop(&mut self) {
+    if !self.finished {
+      let _ = self.cancel();
+    }
+  }
+}
@@ -1,28 +1,42 @@
 use anyhow::Error;
-use lsp_types::request::Request as LSPRequestTrait;
-use serde::Deserialize;
+use lsp_types::{request::Request as LSPRequestTrait, NumberOrString};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use super::utils::{LSPError, Version};
+use super::utils::{deserialize_some, LSPError, Version};
 
-#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 struct LSPResponseBuilder<T>
 where
   T: LSPRequestTrait,
 {
   #[serde(rename = "jsonrpc")]
   _jsonrpc: Version,
+  ///`null` is a valid result for many requests and has to be told apart from a missing one
+  #[serde(default, deserialize_with = "deserialize_some")]
   result: Option<T::Result>,
   error: Option<LSPError>,
-  id: Option<i32>,
+  id: Option<NumberOrString>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) struct LSPResponse<T>
 where
   T: LSPRequestTrait,
 {
   pub(crate) result: Result<T::Result, LSPError>,
-  id: Option<i32>,
+  id: Option<NumberOrString>,
+}
+
+///Response of the client to a request sent by the server
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct RawLSPResponse {
+  jsonrpc: Version,
+  id: NumberOrString,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  result: Option<Value>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  error: Option<LSPError>,
 }
 
 #[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -30,7 +44,7 @@ pub(crate) struct LSPMessage {
   #[serde(rename = "jsonrpc")]
   _jsonrpc: Version,
   method: Option<String>,
-  id: Option<i32>,
+  id: Option<NumberOrString>,
 }
 
 impl<T: LSPRequestTrait> LSPResponse<T> {
@@ -49,12 +63,76 @@ impl<T: LSPRequestTrait> LSPResponse<T> {
   }
 }
 
+impl RawLSPResponse {
+  pub(crate) fn new(id: NumberOrString, result: Result<Value, LSPError>) -> Self {
+    let (result, error) = match result {
+      Ok(result) => (Some(result), None),
+      Err(err) => (None, Some(err)),
+    };
+
+    RawLSPResponse {
+      jsonrpc: Version::V2,
+      id,
+      result,
+      error,
+    }
+  }
+
+  pub(crate) fn as_bytes(&self) -> Result<Vec<u8>, Error> {
+    let res = serde_json::to_string(&self)?;
+    let res = format!("Content-Length: {}\r\n\r\n{}", res.len(), res);
+    Ok(res.as_bytes().to_vec())
+  }
+}
+
 impl LSPMessage {
+  pub(crate) fn is_request(&self) -> bool {
+    self.id.is_some() && self.method.is_some()
+  }
+
   pub(crate) fn is_response(&self) -> bool {
-    self.id.is_some()
+    self.id.is_some() && self.method.is_none()
+  }
+
+  pub(crate) fn get_id(&self) -> Option<&NumberOrString> {
+    self.id.as_ref()
   }
+}
+
+#[cfg(test)]
+mod tests {
+  use lsp_types::request::SemanticTokensFullRequest;
+
+  use crate::lsp::utils::ErrorCode;
+
+  use super::LSPResponse;
+
+  #[test]
+  fn response_distinguishes_result_null_and_error() {
+    let res = LSPResponse::<SemanticTokensFullRequest>::new(
+      r#"{"jsonrpc":"2.0","id":1,"result":{"data":[0,0,2,0,0]}}"#.to_string(),
+    )
+    .unwrap()
+    .unwrap();
+    assert!(matches!(res.result, Ok(Some(_))));
+
+    let res = LSPResponse::<SemanticTokensFullRequest>::new(
+      r#"{"jsonrpc":"2.0","id":1,"result":null}"#.to_string(),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(res.result, Ok(None));
+
+    let res = LSPResponse::<SemanticTokensFullRequest>::new(
+      r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32801,"message":"modified"}}"#.to_string(),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(res.result.unwrap_err().code, ErrorCode::ContentModified);
 
-  pub(crate) fn get_id(&self) -> Option<i32> {
-    self.id
+    let res =
+      LSPResponse::<SemanticTokensFullRequest>::new(r#"{"jsonrpc":"2.0","id":1}"#.to_string())
+        .unwrap();
+    assert!(res.is_none());
   }
 }
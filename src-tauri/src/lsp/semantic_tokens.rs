@@ -0,0 +1,237 @@
+use std::cmp::Reverse;
+
+use anyhow::Error;
+use lsp_types::{
+  request::{
+    Request, SemanticTokensFullDeltaRequest, SemanticTokensFullRequest, SemanticTokensRangeRequest,
+    SemanticTokensRefresh,
+  },
+  Range, SemanticToken, SemanticTokensDeltaParams, SemanticTokensParams, SemanticTokensRangeParams,
+  SemanticTokensRangeResult, SemanticTokensResult, TextDocumentIdentifier,
+};
+use serde::{Deserialize, Serialize};
+use tauri::Url;
+
+use super::{
+  client::LSPData,
+  document_store::{DocumentStore, Refresh},
+};
+
+///Emitted with the files whose semantic tokens are outdated, their highlighting has to be
+///requested again
+pub(crate) const SEMANTIC_TOKENS_REFRESH_EVENT: &str = "lsp-semantic-tokens-refresh";
+
+///Last tokens of every document with the id the server gave them, so only the changes have to
+///be sent the next time
+pub(crate) type SemanticTokenStore = DocumentStore<CachedTokens, SemanticTokensRefresh>;
+
+#[derive(Debug, Clone)]
+pub(crate) struct CachedTokens {
+  result_id: String,
+  ///Tokens as the integers the edits of a delta refer to, five for each token
+  data: Vec<u32>,
+}
+
+///`textDocument/semanticTokens/full/delta` with the integers of the result as they were sent.
+///lsp-types groups them into tokens, but edits may start and end inside of a token.
+enum SemanticTokensRawDeltaRequest {}
+
+impl Request for SemanticTokensRawDeltaRequest {
+  type Params = SemanticTokensDeltaParams;
+  type Result = Option<RawDeltaResult>;
+  const METHOD: &'static str = SemanticTokensFullDeltaRequest::METHOD;
+}
+
+///Partial results only have the edits
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum RawDeltaResult {
+  Delta {
+    #[serde(rename = "resultId")]
+    result_id: Option<String>,
+    edits: Vec<RawEdit>,
+  },
+  Tokens {
+    #[serde(rename = "resultId")]
+    result_id: Option<String>,
+    data: Vec<u32>,
+  },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct RawEdit {
+  start: u32,
+  delete_count: u32,
+  #[serde(default)]
+  data: Vec<u32>,
+}
+
+impl Refresh for SemanticTokensRefresh {
+  const EVENT: &'static str = SEMANTIC_TOKENS_REFRESH_EVENT;
+}
+
+impl SemanticTokenStore {
+  ///Tokens of the whole document, only the changes since the last request are asked for if the
+  ///server supports deltas
+  pub(crate) async fn full(&self, lsp: &LSPData, uri: &Url) -> Result<Vec<SemanticToken>, Error> {
+    let delta = lsp
+      .lsp_info()
+      .and_then(|info| info.semantic_token_info)
+      .is_some_and(|info| info.delta);
+    let previous = match delta {
+      true => self.get(uri).map(|cached| cached.result_id),
+      false => None,
+    };
+
+    //servers reject result ids they no longer know, the whole document is requested then
+    let tokens = match previous {
+      Some(previous) => match self.request_delta(lsp, uri, previous).await {
+        Ok(tokens) => tokens,
+        Err(err) => {
+          eprintln!(
+            "Error requesting semantic token changes of {}: {}",
+            uri, err
+          );
+          self.clear(uri);
+          None
+        }
+      },
+      None => None,
+    };
+    let (result_id, data) = match tokens {
+      Some(tokens) => tokens,
+      None => self.request_full(lsp, uri).await?,
+    };
+
+    let tokens = unflatten(&data);
+    match result_id {
+      Some(result_id) if delta => self.insert(uri.clone(), CachedTokens { result_id, data }),
+      _ => self.clear(uri),
+    }
+    Ok(tokens)
+  }
+
+  ///Tokens of `range` only, like the lines shown in the editor. They are not cached, servers
+  ///without range support send the whole document.
+  pub(crate) async fn range(
+    &self,
+    lsp: &LSPData,
+    uri: &Url,
+    range: Range,
+  ) -> Result<Vec<SemanticToken>, Error> {
+    let supported = lsp
+      .lsp_info()
+      .and_then(|info| info.semantic_token_info)
+      .is_some_and(|info| info.range);
+    if !supported {
+      return self.full(lsp, uri).await;
+    }
+
+    let response = lsp
+      .request::<SemanticTokensRangeRequest>(SemanticTokensRangeParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        range,
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+      })
+      .await?;
+    Ok(match response {
+      Some(SemanticTokensRangeResult::Result(tokens)) => tokens.data,
+      Some(SemanticTokensRangeResult::Partial(partial)) => partial.data,
+      None => Vec::new(),
+    })
+  }
+
+  async fn request_full(
+    &self,
+    lsp: &LSPData,
+    uri: &Url,
+  ) -> Result<(Option<String>, Vec<u32>), Error> {
+    let response = lsp
+      .request::<SemanticTokensFullRequest>(SemanticTokensParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+      })
+      .await?;
+    Ok(match response {
+      Some(SemanticTokensResult::Tokens(tokens)) => (tokens.result_id, flatten(&tokens.data)),
+      Some(SemanticTokensResult::Partial(partial)) => (None, flatten(&partial.data)),
+      None => (None, Vec::new()),
+    })
+  }
+
+  ///Applies the changes since `previous` to the cached tokens, `None` if they can't be applied
+  ///and the whole document has to be requested
+  async fn request_delta(
+    &self,
+    lsp: &LSPData,
+    uri: &Url,
+    previous: String,
+  ) -> Result<Option<(Option<String>, Vec<u32>)>, Error> {
+    let response = lsp
+      .request::<SemanticTokensRawDeltaRequest>(SemanticTokensDeltaParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        previous_result_id: previous.clone(),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+      })
+      .await?;
+
+    let (result_id, edits) = match response {
+      Some(RawDeltaResult::Tokens { result_id, data }) => return Ok(Some((result_id, data))),
+      Some(RawDeltaResult::Delta { result_id, edits }) => (result_id, edits),
+      None => return Ok(None),
+    };
+
+    //another request may have replaced the tokens the edits refer to
+    let Some(cached) = self.get(uri).filter(|cached| cached.result_id == previous) else {
+      return Ok(None);
+    };
+    Ok(apply_edits(cached.data, edits).map(|data| (result_id, data)))
+  }
+}
+
+///Edits refer to the integers of the previous result, they are applied from the back so their
+///offsets stay valid. `None` if an edit is outside of the data.
+fn apply_edits(mut data: Vec<u32>, mut edits: Vec<RawEdit>) -> Option<Vec<u32>> {
+  edits.sort_by_key(|edit| Reverse(edit.start));
+  for edit in edits {
+    let start = edit.start as usize;
+    let end = start + edit.delete_count as usize;
+    if end > data.len() {
+      return None;
+    }
+    data.splice(start..end, edit.data);
+  }
+  Some(data)
+}
+
+fn flatten(tokens: &[SemanticToken]) -> Vec<u32> {
+  tokens
+    .iter()
+    .flat_map(|token| {
+      [
+        token.delta_line,
+        token.delta_start,
+        token.length,
+        token.token_type,
+        token.token_modifiers_bitset,
+      ]
+    })
+    .collect()
+}
+
+fn unflatten(data: &[u32]) -> Vec<SemanticToken> {
+  data
+    .chunks_exact(5)
+    .map(|token| SemanticToken {
+      delta_line: token[0],
+      delta_start: token[1],
+      length: token[2],
+      token_type: token[3],
+      token_modifiers_bitset: token[4],
+    })
+    .collect()
+}
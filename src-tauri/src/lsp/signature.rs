@@ -0,0 +1,114 @@
+use lsp_types::{ParameterLabel, PositionEncodingKind, SignatureHelp, SignatureInformation};
+use serde::Serialize;
+
+use crate::file::parser::ParsersManager;
+
+use super::{encoding::byte_offset, hover::documentation_to_html};
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SignatureHelpContent {
+  pub(crate) signatures: Vec<SignatureContent>,
+  pub(crate) active_signature: usize,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SignatureContent {
+  pub(crate) label: String,
+  pub(crate) documentation: Option<String>,
+  pub(crate) parameters: Vec<ParameterContent>,
+  pub(crate) active_parameter: Option<usize>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ParameterContent {
+  ///Start and end of the parameter in the label in UTF-16 code units, so the label can be sliced
+  ///by the frontend. Not set if the server sent a parameter that is not part of the label.
+  pub(crate) offsets: Option<[u32; 2]>,
+  pub(crate) documentation: Option<String>,
+}
+
+impl SignatureHelpContent {
+  ///`encoding` is the position encoding of the server, which is used for label offsets as well
+  pub(crate) fn new(
+    help: SignatureHelp,
+    encoding: Option<&PositionEncodingKind>,
+    parsers: &ParsersManager,
+    path: &str,
+  ) -> Self {
+    let active_signature = help.active_signature.unwrap_or(0) as usize;
+    let signatures = help
+      .signatures
+      .into_iter()
+      .map(|signature| {
+        SignatureContent::new(signature, help.active_parameter, encoding, parsers, path)
+      })
+      .collect::<Vec<_>>();
+
+    SignatureHelpContent {
+      active_signature: active_signature.min(signatures.len().saturating_sub(1)),
+      signatures,
+    }
+  }
+}
+
+impl SignatureContent {
+  fn new(
+    signature: SignatureInformation,
+    active_parameter: Option<u32>,
+    encoding: Option<&PositionEncodingKind>,
+    parsers: &ParsersManager,
+    path: &str,
+  ) -> Self {
+    let label = signature.label;
+    //simple labels are searched after the name of the function and the previous parameter
+    let mut search_from = label.find('(').map_or(0, |open| open + 1);
+    let parameters: Vec<ParameterContent> = signature
+      .parameters
+      .unwrap_or_default()
+      .into_iter()
+      .map(|parameter| {
+        let range = match parameter.label {
+          ParameterLabel::Simple(name) => label[search_from..]
+            .find(&name)
+            .map(|start| (search_from + start, search_from + start + name.len())),
+          ParameterLabel::LabelOffsets([start, end]) => {
+            byte_offset(&label, start, encoding).zip(byte_offset(&label, end, encoding))
+          }
+        };
+        if let Some((_, end)) = range {
+          search_from = end;
+        }
+
+        ParameterContent {
+          offsets: range.map(|(start, end)| [utf16_len(&label[..start]), utf16_len(&label[..end])]),
+          documentation: parameter
+            .documentation
+            .map(|documentation| documentation_to_html(&documentation, parsers, path)),
+        }
+      })
+      .collect();
+
+    //the parameter of the signature takes precedence over the one of the whole help
+    let active_parameter = signature
+      .active_parameter
+      .or(active_parameter)
+      .map(|active| active as usize)
+      .filter(|active| *active < parameters.len());
+
+    SignatureContent {
+      documentation: signature
+        .documentation
+        .map(|documentation| documentation_to_html(&documentation, parsers, path)),
+      label,
+      parameters,
+      active_parameter,
+    }
+  }
+}
+
+fn utf16_len(text: &str) -> u32 {
+  text.encode_utf16().count() as u32
+}
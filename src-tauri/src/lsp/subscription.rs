@@ -0,0 +1,93 @@
+use std::{
+  collections::HashMap,
+  marker::PhantomData,
+  sync::{Arc, Mutex},
+};
+
+use async_std::channel::{bounded, Receiver, Sender, TrySendError};
+use lsp_types::notification::Notification as LSPNotificationTrait;
+use serde_json::Value;
+
+use super::notification::RawLSPNotification;
+
+///Notifications a subscriber may lag behind before new ones are dropped
+pub(crate) const SUBSCRIPTION_CAPACITY: usize = 256;
+
+///Delivers the notifications of a server to the subscribers of their method
+#[derive(Debug, Clone, Default)]
+pub(crate) struct NotificationBus {
+  subscribers: Arc<Mutex<HashMap<String, Vec<Sender<Value>>>>>,
+}
+
+///Sending half of a subscription, it can be shared by several servers
+#[derive(Debug, Clone)]
+pub(crate) struct Subscriber {
+  method: &'static str,
+  sender: Sender<Value>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Subscription<T>
+where
+  T: LSPNotificationTrait,
+{
+  receiver: Receiver<Value>,
+  notification: PhantomData<fn() -> T>,
+}
+
+impl NotificationBus {
+  pub(crate) fn add_subscriber(&self, subscriber: &Subscriber) {
+    self
+      .subscribers
+      .lock()
+      .unwrap()
+      .entry(subscriber.method.to_string())
+      .or_default()
+      .push(subscriber.sender.clone());
+  }
+
+  ///Never blocks so a slow subscriber cannot stall the responses read by the same thread
+  pub(crate) fn publish(&self, not: RawLSPNotification) {
+    let mut subscribers = self.subscribers.lock().unwrap();
+    let Some(senders) = subscribers.get_mut(&not.method) else {
+      return;
+    };
+
+    senders.retain(|sender| match sender.try_send(not.params.clone()) {
+      Ok(()) => true,
+      Err(TrySendError::Full(_)) => {
+        eprintln!("Dropped {} notification, subscriber is lagging", not.method);
+        true
+      }
+      Err(TrySendError::Closed(_)) => false,
+    });
+  }
+}
+
+impl<T: LSPNotificationTrait> Subscription<T> {
+  pub(crate) fn new() -> (Subscriber, Self) {
+    let (sender, receiver) = bounded(SUBSCRIPTION_CAPACITY);
+
+    (
+      Subscriber {
+        method: T::METHOD,
+        sender,
+      },
+      Subscription {
+        receiver,
+        notification: PhantomData,
+      },
+    )
+  }
+
+  ///Waits for the next notification, returns `None` once no server can send one anymore
+  pub(crate) async fn recv(&self) -> Option<T::Params> {
+    loop {
+      let params = self.receiver.recv().await.ok()?;
+      match serde_json::from_value(params) {
+        Ok(params) => return Some(params),
+        Err(err) => eprintln!("Malformed {} notification: {}", T::METHOD, err),
+      }
+    }
+  }
+}
@@ -0,0 +1,311 @@
+use std::cmp::Reverse;
+
+use anyhow::Error;
+use lsp_types::{
+  request::{DocumentSymbolRequest, WorkspaceSymbolRequest, WorkspaceSymbolResolve},
+  DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, OneOf, Range, SymbolInformation,
+  SymbolKind, SymbolTag, TextDocumentIdentifier, Url, WorkspaceSymbol, WorkspaceSymbolParams,
+  WorkspaceSymbolResponse,
+};
+use serde::Serialize;
+
+use super::{
+  capabilities::{SYMBOL_KINDS, SYMBOL_TAGS},
+  client::LSPData,
+  navigation::NavigationTarget,
+  utils::file_name,
+};
+
+///Symbol of the outline, children are the symbols declared inside of it
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct OutlineSymbol {
+  pub(crate) name: String,
+  pub(crate) detail: Option<String>,
+  pub(crate) kind: SymbolKind,
+  pub(crate) tags: Vec<SymbolTag>,
+  pub(crate) range: Range,
+  ///Part of the symbol to select, like its name
+  pub(crate) selection_range: Range,
+  pub(crate) children: Vec<OutlineSymbol>,
+}
+
+///Symbols of the last workspace search, kept so the chosen one can be resolved by its id
+#[derive(Debug, Clone)]
+pub(crate) struct WorkspaceSymbols {
+  symbols: Vec<(LSPData, WorkspaceSymbol)>,
+}
+
+///Symbol shown in the go to symbol list, `id` is used to go to it
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WorkspaceSymbolEntry {
+  pub(crate) id: usize,
+  pub(crate) name: String,
+  pub(crate) kind: SymbolKind,
+  pub(crate) tags: Vec<SymbolTag>,
+  pub(crate) container_name: Option<String>,
+  pub(crate) file: String,
+}
+
+impl OutlineSymbol {
+  ///Outline of the document, flat lists are nested by the ranges of their symbols
+  pub(crate) async fn request(lsp: &LSPData, uri: Url) -> Result<Vec<Self>, Error> {
+    if !lsp.lsp_info().is_some_and(|info| info.document_symbol) {
+      return Ok(Vec::new());
+    }
+
+    let response = lsp
+      .request::<DocumentSymbolRequest>(DocumentSymbolParams {
+        text_document: TextDocumentIdentifier { uri },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+      })
+      .await?;
+
+    Ok(match response {
+      Some(DocumentSymbolResponse::Nested(symbols)) => symbols
+        .into_iter()
+        .map(Self::from_document_symbol)
+        .collect(),
+      Some(DocumentSymbolResponse::Flat(symbols)) => {
+        nest(symbols.into_iter().map(Self::from_information).collect())
+      }
+      None => Vec::new(),
+    })
+  }
+
+  ///Symbols matching `query` and the ones containing them, best matches first
+  pub(crate) fn search(symbols: Vec<Self>, query: &str) -> Vec<Self> {
+    rank(symbols, query)
+      .into_iter()
+      .map(|(_, symbol)| symbol)
+      .collect()
+  }
+
+  fn from_document_symbol(symbol: DocumentSymbol) -> Self {
+    #[allow(deprecated)]
+    let tags = tags(symbol.tags, symbol.deprecated);
+    OutlineSymbol {
+      name: symbol.name,
+      detail: symbol.detail,
+      kind: kind(symbol.kind),
+      tags,
+      range: symbol.range,
+      selection_range: symbol.selection_range,
+      children: symbol
+        .children
+        .unwrap_or_default()
+        .into_iter()
+        .map(Self::from_document_symbol)
+        .collect(),
+    }
+  }
+
+  fn from_information(symbol: SymbolInformation) -> Self {
+    #[allow(deprecated)]
+    let tags = tags(symbol.tags, symbol.deprecated);
+    OutlineSymbol {
+      name: symbol.name,
+      detail: symbol.container_name,
+      kind: kind(symbol.kind),
+      tags,
+      range: symbol.location.range,
+      selection_range: symbol.location.range,
+      children: Vec::new(),
+    }
+  }
+}
+
+impl WorkspaceSymbols {
+  ///Asks every server supporting it for symbols matching `query`
+  pub(crate) async fn request(lsps: &[LSPData], query: &str) -> Result<Self, Error> {
+    let mut symbols = Vec::new();
+    for lsp in lsps {
+      if lsp
+        .lsp_info()
+        .and_then(|info| info.workspace_symbol)
+        .is_none()
+      {
+        continue;
+      }
+
+      let response = lsp
+        .request::<WorkspaceSymbolRequest>(WorkspaceSymbolParams {
+          query: query.to_string(),
+          work_done_progress_params: Default::default(),
+          partial_result_params: Default::default(),
+        })
+        .await?;
+      let found: Vec<WorkspaceSymbol> = match response {
+        Some(WorkspaceSymbolResponse::Nested(found)) => found,
+        Some(WorkspaceSymbolResponse::Flat(found)) => {
+          found.into_iter().map(workspace_symbol).collect()
+        }
+        None => Vec::new(),
+      };
+      symbols.extend(found.into_iter().map(|symbol| (lsp.clone(), symbol)));
+    }
+
+    Ok(WorkspaceSymbols { symbols })
+  }
+
+  ///Symbols matching `query`, the best matches first
+  pub(crate) fn entries(&self, query: &str) -> Vec<WorkspaceSymbolEntry> {
+    let mut entries: Vec<(i64, WorkspaceSymbolEntry)> = self
+      .symbols
+      .iter()
+      .enumerate()
+      .filter_map(|(id, (_, symbol))| {
+        let score = fuzzy_score(&symbol.name, query)?;
+        let uri = match &symbol.location {
+          OneOf::Left(location) => &location.uri,
+          OneOf::Right(location) => &location.uri,
+        };
+        let entry = WorkspaceSymbolEntry {
+          id,
+          name: symbol.name.clone(),
+          kind: kind(symbol.kind),
+          tags: tags(symbol.tags.clone(), None),
+          container_name: symbol.container_name.clone(),
+          file: file_name(uri),
+        };
+        Some((score, entry))
+      })
+      .collect();
+    entries.sort_by_key(|(score, _)| Reverse(*score));
+    entries.into_iter().map(|(_, entry)| entry).collect()
+  }
+
+  ///Location of the symbol, servers sending only the file are asked for the range
+  pub(crate) async fn resolve(&mut self, id: usize) -> Result<NavigationTarget, Error> {
+    let (lsp, symbol) = self
+      .symbols
+      .get_mut(id)
+      .ok_or(anyhow::anyhow!("No workspace symbol {}", id))?;
+
+    let resolve = lsp.lsp_info().and_then(|info| info.workspace_symbol) == Some(true);
+    if matches!(symbol.location, OneOf::Right(_)) && resolve {
+      *symbol = lsp
+        .request::<WorkspaceSymbolResolve>(symbol.clone())
+        .await?;
+    }
+
+    let (uri, range) = match &symbol.location {
+      OneOf::Left(location) => (&location.uri, location.range),
+      OneOf::Right(location) => (&location.uri, Range::default()),
+    };
+    Ok(NavigationTarget {
+      file: file_name(uri),
+      range,
+      selection_range: range,
+      origin_range: None,
+    })
+  }
+}
+
+fn workspace_symbol(symbol: SymbolInformation) -> WorkspaceSymbol {
+  #[allow(deprecated)]
+  let tags = tags(symbol.tags, symbol.deprecated);
+  WorkspaceSymbol {
+    name: symbol.name,
+    kind: symbol.kind,
+    tags: Some(tags),
+    container_name: symbol.container_name,
+    location: OneOf::Left(symbol.location),
+    data: None,
+  }
+}
+
+///Kinds the client did not announce are shown as variables
+pub(crate) fn kind(kind: SymbolKind) -> SymbolKind {
+  match SYMBOL_KINDS.contains(&kind) {
+    true => kind,
+    false => SymbolKind::VARIABLE,
+  }
+}
+
+///Tags the client did not announce are dropped, the old deprecated flag becomes a tag
+pub(crate) fn tags(tags: Option<Vec<SymbolTag>>, deprecated: Option<bool>) -> Vec<SymbolTag> {
+  let mut tags: Vec<SymbolTag> = tags
+    .unwrap_or_default()
+    .into_iter()
+    .filter(|tag| SYMBOL_TAGS.contains(tag))
+    .collect();
+  if deprecated == Some(true) && !tags.contains(&SymbolTag::DEPRECATED) {
+    tags.push(SymbolTag::DEPRECATED);
+  }
+  tags
+}
+
+///Symbols ordered by their start and the outer ones first are nested into the last symbol
+///containing them
+fn nest(mut symbols: Vec<OutlineSymbol>) -> Vec<OutlineSymbol> {
+  symbols.sort_by_key(|symbol| (symbol.range.start, Reverse(symbol.range.end)));
+
+  let mut roots = Vec::new();
+  for symbol in symbols {
+    insert(&mut roots, symbol);
+  }
+  roots
+}
+
+fn insert(siblings: &mut Vec<OutlineSymbol>, symbol: OutlineSymbol) {
+  match siblings.last_mut() {
+    Some(last) if last.range.start <= symbol.range.start && symbol.range.end <= last.range.end => {
+      insert(&mut last.children, symbol)
+    }
+    _ => siblings.push(symbol),
+  }
+}
+
+///Symbols are ranked by their own score or the best one of their children
+fn rank(symbols: Vec<OutlineSymbol>, query: &str) -> Vec<(i64, OutlineSymbol)> {
+  let mut ranked: Vec<(i64, OutlineSymbol)> = symbols
+    .into_iter()
+    .filter_map(|mut symbol| {
+      let children = rank(std::mem::take(&mut symbol.children), query);
+      let score = fuzzy_score(&symbol.name, query)
+        .into_iter()
+        .chain(children.first().map(|(score, _)| *score))
+        .max()?;
+      symbol.children = children.into_iter().map(|(_, child)| child).collect();
+      Some((score, symbol))
+    })
+    .collect();
+  ranked.sort_by_key(|(score, _)| Reverse(*score));
+  ranked
+}
+
+///The characters of `query` have to appear in order in `text`, ignoring case. Runs of matched
+///characters and matches at the start of words score higher, shorter texts win ties.
+///Everything scores the same for an empty query, so the order of the server is kept.
+fn fuzzy_score(text: &str, query: &str) -> Option<i64> {
+  if query.is_empty() {
+    return Some(0);
+  }
+
+  let chars: Vec<char> = text.chars().collect();
+  let mut score = 0;
+  let mut next = 0;
+  let mut previous: Option<usize> = None;
+
+  for wanted in query.chars().flat_map(char::to_lowercase) {
+    let found = (next..chars.len()).find(|&i| chars[i].to_lowercase().eq(wanted.to_lowercase()))?;
+    score += 10;
+    if previous.is_some_and(|previous| previous + 1 == found) {
+      score += 15;
+    }
+    let word_start = found == 0
+      || !chars[found - 1].is_alphanumeric()
+      || (chars[found - 1].is_lowercase() && chars[found].is_uppercase());
+    if word_start {
+      score += 10;
+    }
+
+    previous = Some(found);
+    next = found + 1;
+  }
+
+  Some(score - chars.len() as i64)
+}
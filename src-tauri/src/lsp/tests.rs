@@ -0,0 +1,2142 @@
+use std::{
+  fs,
+  net::TcpListener,
+  path::{Path, PathBuf},
+  sync::Arc,
+  thread::{self, JoinHandle},
+  time::{Duration, Instant},
+};
+
+use anyhow::Error;
+use lsp_types::{
+  notification::LogMessage, request::HoverRequest, ApplyWorkspaceEditResponse, CodeActionKind,
+  DiagnosticSeverity, HoverParams, InlayHintTooltip, MessageType, Position, Range, SymbolKind,
+  SymbolTag, TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams, TextEdit,
+  TraceValue, Url, WorkspaceFolder,
+};
+use mock_lsp::{serve, Outcome, Scenario};
+use serde_json::{json, Value};
+
+use crate::file::{
+  folding::FoldState,
+  indentation::Indentation,
+  manager::FileManager,
+  parser::ParsersManager,
+  token::{Token, TokenTree},
+  workspace_edit::{apply_workspace_edit, ApplyAnnotatedWorkspaceEdit},
+};
+
+use super::{
+  capabilities::get_capabilities,
+  client::LSPData,
+  hierarchy::{HierarchyDirection, HierarchyKind},
+  lifecycle::ServerState,
+  manager::LSPManager,
+  navigation::NavigationKind,
+  request::LSPRequest,
+  trace::TRAFFIC_CAPACITY,
+  utils::{ErrorCode, LSPError},
+};
+
+///Plays one scenario per accepted connection, so every restart gets the next one
+struct MockServer {
+  address: String,
+  handle: JoinHandle<Vec<Result<Outcome, Error>>>,
+}
+
+impl MockServer {
+  fn start(scenarios: Vec<Value>) -> Self {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+
+    let handle = thread::spawn(move || {
+      scenarios
+        .into_iter()
+        .map(|scenario| {
+          let scenario: Scenario = serde_json::from_value(scenario)?;
+          let (stream, _) = listener.accept()?;
+          serve(&scenario, stream.try_clone()?, stream)
+        })
+        .collect()
+    });
+
+    MockServer { address, handle }
+  }
+
+  ///Fails the test if the client deviated from any scenario
+  fn finish(self) -> Vec<Outcome> {
+    self
+      .handle
+      .join()
+      .unwrap()
+      .into_iter()
+      .map(|outcome| outcome.unwrap())
+      .collect()
+  }
+}
+
+fn initialize(extra: Vec<Value>) -> Value {
+  initialize_with(json!({}), extra)
+}
+
+///Replaces the default capabilities with the ones in `capabilities`
+fn initialize_with(capabilities: Value, extra: Vec<Value>) -> Value {
+  let mut defaults = json!({
+    "textDocumentSync": { "openClose": true, "change": 2, "save": { "includeText": true } },
+    "semanticTokensProvider": {
+      "legend": {
+        "tokenTypes": ["keyword", "function"],
+        "tokenModifiers": ["declaration"],
+      },
+      "full": true,
+    },
+  });
+  for (key, value) in capabilities.as_object().unwrap() {
+    defaults[key] = value.clone();
+  }
+
+  let mut steps = vec![
+    json!({ "expect": {
+      "method": "initialize",
+      "reply": { "result": { "capabilities": defaults } },
+    } }),
+    json!({ "expect": { "method": "initialized" } }),
+  ];
+  steps.extend(extra);
+  json!({ "steps": steps })
+}
+
+async fn connect(server: &MockServer) -> LSPData {
+  LSPData::create()
+    .tcp(server.address.clone())
+    .file_patterns(vec!["^.+\\.rs$".to_string()])
+    .workspace_folders(vec![WorkspaceFolder {
+      uri: Url::parse("file:///workspace/").unwrap(),
+      name: "workspace".to_string(),
+    }])
+    .capabilities(get_capabilities())
+    .settings(json!({ "mock": { "enabled": true } }))
+    .build()
+    .await
+    .unwrap()
+}
+
+fn hover_params() -> HoverParams {
+  HoverParams {
+    text_document_position_params: TextDocumentPositionParams {
+      text_document: TextDocumentIdentifier {
+        uri: Url::parse("file:///workspace/main.rs").unwrap(),
+      },
+      position: Position::new(0, 3),
+    },
+    work_done_progress_params: Default::default(),
+  }
+}
+
+fn hover() -> LSPRequest<HoverRequest> {
+  LSPRequest::new(Some(hover_params()))
+}
+
+async fn wait_for_state(lsp: &LSPData, state: ServerState) {
+  let deadline = Instant::now() + Duration::from_secs(10);
+  while lsp.state() != state {
+    assert!(Instant::now() < deadline, "server never became {:?}", state);
+    async_std::task::sleep(Duration::from_millis(20)).await;
+  }
+}
+
+///Path of the `mock-lsp` binary. Cargo only sets `CARGO_BIN_EXE_*` for the package defining the
+///binary, whose integration tests have it built next to the `deps` directory of this test.
+fn mock_binary() -> PathBuf {
+  let deps = std::env::current_exe().unwrap();
+  let binary = deps
+    .parent()
+    .and_then(Path::parent)
+    .unwrap()
+    .join(format!("mock-lsp{}", std::env::consts::EXE_SUFFIX));
+  assert!(
+    binary.exists(),
+    "mock-lsp is not built, run the tests of the whole workspace"
+  );
+  binary
+}
+
+#[async_std::test]
+async fn initialize_and_shutdown() {
+  let server = MockServer::start(vec![initialize(Vec::new())]);
+  let lsp = connect(&server).await;
+
+  assert_eq!(lsp.state(), ServerState::Running);
+  assert!(lsp.lsp_info().unwrap().semantic_token_info.is_some());
+
+  lsp.shutdown().await.unwrap();
+  assert_eq!(lsp.state(), ServerState::Stopped);
+
+  let outcome = server.finish().remove(0);
+  let init = &outcome.received[0];
+  assert_eq!(init["params"]["workspaceFolders"][0]["name"], "workspace");
+  assert_eq!(init["params"]["trace"], "off");
+  //servers behind a socket may be shared, only the connection is closed
+  assert_eq!(outcome.received.len(), 2);
+}
+
+#[async_std::test]
+async fn semantic_tokens_are_decoded() {
+  let server = MockServer::start(vec![initialize(vec![json!({ "expect": {
+    "method": "textDocument/semanticTokens/full",
+    "params": { "textDocument": { "uri": "file:///workspace/main.rs" } },
+    "reply": { "result": { "data": [0, 0, 2, 0, 0, 0, 3, 4, 1, 1] } },
+  } })])]);
+  let mut manager = LSPManager::new();
+  manager.add_lsp(connect(&server).await);
+
+  let content = vec!["fn main() {}\r\n".to_string()];
+  let tokens = manager
+    .get_semantic_tokens("/workspace/main.rs", &content, None)
+    .await
+    .unwrap();
+
+  assert_eq!(tokens.len(), 1);
+  assert_eq!(tokens[0][0].token, "fn");
+  assert_eq!(tokens[0][1].start, 3);
+  assert_eq!(tokens[0][1].token, "main");
+  assert_eq!(
+    tokens[0][1].modifiers,
+    Some(vec!["declaration".to_string()])
+  );
+
+  manager.shutdown().await;
+  server.finish();
+}
+
+#[async_std::test]
+async fn semantic_tokens_are_patched_by_deltas() {
+  let server = MockServer::start(vec![initialize_with(
+    json!({ "semanticTokensProvider": {
+      "legend": { "tokenTypes": ["keyword", "function"], "tokenModifiers": ["declaration"] },
+      "full": { "delta": true },
+      "range": true,
+    } }),
+    vec![
+      json!({ "expect": {
+        "method": "textDocument/semanticTokens/full",
+        "reply": { "result": { "resultId": "1", "data": [0, 0, 2, 0, 0, 0, 3, 4, 1, 1] } },
+      } }),
+      json!({ "expect": {
+        "method": "textDocument/semanticTokens/full/delta",
+        "params": { "previousResultId": "1" },
+        "reply": { "result": {
+          "resultId": "2",
+          "edits": [{ "start": 5, "deleteCount": 5, "data": [0, 3, 3, 1, 0] }],
+        } },
+      } }),
+      json!({ "expect": {
+        "method": "textDocument/semanticTokens/range",
+        "params": { "range": {
+          "start": { "line": 1, "character": 0 },
+          "end": { "line": 2, "character": 0 },
+        } },
+        "reply": { "result": { "data": [1, 0, 2, 0, 0] } },
+      } }),
+      json!({ "request": { "method": "workspace/semanticTokens/refresh" } }),
+      //tells the test that the refresh was handled
+      json!({ "notify": {
+        "method": "window/logMessage",
+        "params": { "type": 3, "message": "done" },
+      } }),
+      json!({ "expect": {
+        "method": "textDocument/semanticTokens/full",
+        "reply": { "result": { "resultId": "3", "data": [0, 0, 2, 0, 0] } },
+      } }),
+    ],
+  )]);
+  let mut manager = LSPManager::new();
+  let logs = manager.subscribe::<LogMessage>();
+  manager.add_lsp(connect(&server).await);
+  let path = "/workspace/main.rs";
+
+  let names = |tokens: Vec<Vec<Token>>| -> Vec<Vec<String>> {
+    tokens
+      .into_iter()
+      .map(|line| line.into_iter().map(|token| token.token).collect())
+      .collect()
+  };
+
+  let content = vec!["fn main() {}\r\n".to_string(), "fn f() {}\r\n".to_string()];
+  let tokens = manager.get_semantic_tokens(path, &content, None).await;
+  assert_eq!(names(tokens.unwrap()), vec![vec!["fn", "main"]]);
+
+  let content = vec!["fn run() {}\r\n".to_string(), "fn f() {}\r\n".to_string()];
+  let tokens = manager.get_semantic_tokens(path, &content, None).await;
+  assert_eq!(names(tokens.unwrap()), vec![vec!["fn", "run"]]);
+
+  //tokens of the viewport keep the lines of the document
+  let viewport = Range::new(Position::new(1, 0), Position::new(2, 0));
+  let tokens = manager
+    .get_semantic_tokens(path, &content, Some(viewport))
+    .await;
+  assert_eq!(names(tokens.unwrap()), vec![vec![], vec!["fn"]]);
+
+  //the refresh drops the cached tokens, so the whole document is requested again
+  logs.recv().await.unwrap();
+  let tokens = manager.get_semantic_tokens(path, &content, None).await;
+  assert_eq!(names(tokens.unwrap()), vec![vec!["fn"]]);
+
+  manager.shutdown().await;
+  server.finish();
+}
+
+#[async_std::test]
+async fn rejected_deltas_fall_back_to_the_whole_document() {
+  let server = MockServer::start(vec![initialize_with(
+    json!({ "semanticTokensProvider": {
+      "legend": { "tokenTypes": ["keyword", "function"], "tokenModifiers": ["declaration"] },
+      "full": { "delta": true },
+    } }),
+    vec![
+      json!({ "expect": {
+        "method": "textDocument/semanticTokens/full",
+        "reply": { "result": { "resultId": "1", "data": [0, 0, 2, 0, 0, 0, 3, 4, 1, 1] } },
+      } }),
+      //edits may replace single integers, like the length of a token
+      json!({ "expect": {
+        "method": "textDocument/semanticTokens/full/delta",
+        "params": { "previousResultId": "1" },
+        "reply": { "result": {
+          "resultId": "2",
+          "edits": [{ "start": 7, "deleteCount": 1, "data": [3] }],
+        } },
+      } }),
+      json!({ "expect": {
+        "method": "textDocument/semanticTokens/full/delta",
+        "params": { "previousResultId": "2" },
+        "reply": { "error": { "code": -32602, "message": "unknown result id" } },
+      } }),
+      json!({ "expect": {
+        "method": "textDocument/semanticTokens/full",
+        "reply": { "result": { "resultId": "3", "data": [0, 0, 2, 0, 0] } },
+      } }),
+    ],
+  )]);
+  let mut manager = LSPManager::new();
+  manager.add_lsp(connect(&server).await);
+  let path = "/workspace/main.rs";
+  let content = vec!["fn run() {}\r\n".to_string()];
+
+  let names = |tokens: Option<Vec<Vec<Token>>>| -> Vec<String> {
+    tokens.unwrap()[0]
+      .iter()
+      .map(|token| token.token.clone())
+      .collect()
+  };
+  let tokens = manager.get_semantic_tokens(path, &content, None).await;
+  assert_eq!(names(tokens), vec!["fn", "run("]);
+  let tokens = manager.get_semantic_tokens(path, &content, None).await;
+  assert_eq!(names(tokens), vec!["fn", "run"]);
+  let tokens = manager.get_semantic_tokens(path, &content, None).await;
+  assert_eq!(names(tokens), vec!["fn"]);
+
+  manager.shutdown().await;
+  server.finish();
+}
+
+#[async_std::test]
+async fn error_responses_are_returned() {
+  let modified = json!({ "expect": {
+    "method": "textDocument/hover",
+    "reply": { "error": { "code": -32801, "message": "content modified" } },
+  } });
+  let server = MockServer::start(vec![initialize(vec![modified.clone(), modified])]);
+  let lsp = connect(&server).await;
+
+  let res = lsp.send_req(hover()).unwrap().response().await.unwrap();
+  let err = res.unwrap().result.unwrap_err();
+  assert_eq!(err.code, ErrorCode::ContentModified);
+  assert_eq!(err.message, "content modified");
+
+  //the code is kept in the errors of typed requests
+  let err = lsp
+    .request::<HoverRequest>(hover_params())
+    .await
+    .unwrap_err();
+  let err = err.downcast::<LSPError>().unwrap();
+  assert_eq!(err.code, ErrorCode::ContentModified);
+
+  lsp.shutdown().await.unwrap();
+  server.finish();
+}
+
+#[async_std::test]
+async fn timed_out_requests_are_cancelled() {
+  let server = MockServer::start(vec![initialize(vec![
+    json!({ "expect": { "method": "textDocument/hover", "delay": 300 } }),
+    json!({ "expect": { "method": "$/cancelRequest" } }),
+  ])]);
+  let lsp = connect(&server).await;
+
+  let req = hover().with_timeout(Duration::from_millis(50));
+  let id = req.get_id();
+  assert!(lsp.send_req(req).unwrap().response().await.is_err());
+
+  lsp.shutdown().await.unwrap();
+  let outcome = server.finish().remove(0);
+  assert_eq!(outcome.received[3]["params"]["id"], json!(id));
+}
+
+#[async_std::test]
+async fn traffic_is_recorded_with_latencies_and_mirrored_to_a_file() {
+  let logs = (0..TRAFFIC_CAPACITY).map(|index| {
+    json!({ "notify": {
+      "method": "window/logMessage",
+      "params": { "type": 4, "message": index.to_string() },
+    } })
+  });
+  let mut steps = vec![
+    json!({ "expect": { "method": "textDocument/hover", "delay": 30 } }),
+    json!({ "expect": { "method": "$/setTrace", "params": { "value": "verbose" } } }),
+    json!({ "expect": { "method": "textDocument/hover", "delay": 300 } }),
+    json!({ "expect": { "method": "$/cancelRequest" } }),
+  ];
+  steps.extend(logs);
+  let server = MockServer::start(vec![initialize(steps)]);
+
+  let trace_file = std::env::temp_dir().join(format!("codeforge-trace-{}", std::process::id()));
+  let _ = fs::remove_file(&trace_file);
+  let lsp = LSPData::create()
+    .tcp(server.address.clone())
+    .file_patterns(vec!["^.+\\.rs$".to_string()])
+    .workspace_folders(vec![WorkspaceFolder {
+      uri: Url::parse("file:///workspace/").unwrap(),
+      name: "workspace".to_string(),
+    }])
+    .capabilities(get_capabilities())
+    .trace_file(trace_file.clone())
+    .build()
+    .await
+    .unwrap();
+
+  lsp.request::<HoverRequest>(hover_params()).await.unwrap();
+  lsp.set_trace(TraceValue::Verbose).unwrap();
+  let timed_out = hover().with_timeout(Duration::from_millis(50));
+  assert!(lsp.send_req(timed_out).unwrap().response().await.is_err());
+
+  let last = (TRAFFIC_CAPACITY - 1).to_string();
+  let deadline = Instant::now() + Duration::from_secs(10);
+  let traffic = loop {
+    let traffic = serde_json::to_value(lsp.traffic()).unwrap();
+    let newest = traffic.as_array().and_then(|entries| entries.last());
+    if newest.is_some_and(|entry| entry["message"]["params"]["message"] == json!(last)) {
+      break traffic;
+    }
+    assert!(Instant::now() < deadline, "log messages never arrived");
+    async_std::task::sleep(Duration::from_millis(20)).await;
+  };
+  lsp.shutdown().await.unwrap();
+  server.finish();
+
+  //the oldest frames were dropped, the file keeps all of them
+  let entries = traffic.as_array().unwrap();
+  assert_eq!(entries.len(), TRAFFIC_CAPACITY);
+  assert_ne!(entries[0]["method"], "initialize");
+  let file = fs::read_to_string(&trace_file).unwrap();
+  let lines: Vec<Value> = file
+    .lines()
+    .map(|line| serde_json::from_str(line).unwrap())
+    .collect();
+  assert!(lines.len() > TRAFFIC_CAPACITY);
+  assert_eq!(lines[0]["direction"], "outgoing");
+  assert_eq!(lines[0]["method"], "initialize");
+
+  let set_trace = lines.iter().find(|line| line["method"] == "$/setTrace");
+  assert_eq!(set_trace.unwrap()["message"]["params"]["value"], "verbose");
+  //responses get the method and latency of their request, unless it was cancelled
+  let responses: Vec<&Value> = lines
+    .iter()
+    .filter(|line| line["direction"] == "incoming" && line["message"].get("result").is_some())
+    .collect();
+  assert_eq!(responses[0]["method"], "initialize");
+  assert_eq!(responses[1]["method"], "textDocument/hover");
+  assert!(responses[1]["latency"].as_u64().unwrap() >= 30);
+  assert!(responses[2]["method"].is_null());
+  assert!(responses[2]["latency"].is_null());
+  fs::remove_file(&trace_file).unwrap();
+}
+
+#[async_std::test]
+async fn server_requests_and_notifications_are_handled() {
+  let server = MockServer::start(vec![initialize(vec![
+    json!({ "request": {
+      "method": "workspace/configuration",
+      "params": { "items": [{ "section": "mock.enabled" }, { "section": "missing" }] },
+      "result": [true, null],
+    } }),
+    json!({ "request": { "method": "unknown/method" } }),
+    //gives the test time to subscribe
+    json!({ "sleep": 100 }),
+    json!({ "notify": {
+      "method": "window/logMessage",
+      "params": { "type": 3, "message": "hello" },
+    } }),
+  ])]);
+  let lsp = connect(&server).await;
+  let subscription = lsp.subscribe::<LogMessage>();
+
+  let log = subscription.recv().await.unwrap();
+  assert_eq!(log.typ, MessageType::INFO);
+  assert_eq!(log.message, "hello");
+
+  lsp.shutdown().await.unwrap();
+  let outcome = server.finish().remove(0);
+  assert_eq!(
+    outcome.received[3]["error"]["code"],
+    json!(ErrorCode::MethodNotFound.code())
+  );
+}
+
+#[async_std::test]
+async fn crashed_server_is_restarted_with_open_documents() {
+  let document = TextDocumentItem {
+    uri: Url::parse("file:///workspace/main.rs").unwrap(),
+    language_id: "rust".to_string(),
+    version: 1,
+    text: "fn main() {}".to_string(),
+  };
+  let did_open = json!({ "expect": {
+    "method": "textDocument/didOpen",
+    "params": { "textDocument": { "uri": "file:///workspace/main.rs", "version": 1 } },
+  } });
+
+  let server = MockServer::start(vec![
+    initialize(vec![did_open.clone(), json!("crash")]),
+    initialize_with(json!({ "foldingRangeProvider": true }), vec![did_open]),
+  ]);
+  let lsp = connect(&server).await;
+  //clones held by the manager and the stores see the capabilities of the new instance
+  let held = lsp.clone();
+  assert!(!held.lsp_info().unwrap().folding_range);
+  lsp.open_document(document).unwrap();
+
+  wait_for_state(&lsp, ServerState::Crashed).await;
+  assert!(lsp.send_req(hover()).is_err());
+  wait_for_state(&lsp, ServerState::Running).await;
+  assert!(held.lsp_info().unwrap().folding_range);
+
+  lsp.shutdown().await.unwrap();
+  let outcomes = server.finish();
+  assert!(outcomes[0].crashed);
+  assert!(!outcomes[1].crashed);
+}
+
+#[async_std::test]
+async fn stdio_server_is_spawned_logged_and_restarted() {
+  let dir = std::env::temp_dir().join(format!("codeforge-stdio-{}", std::process::id()));
+  fs::create_dir_all(&dir).unwrap();
+  let scenario = dir.join("scenario.json");
+  let stderr_log = dir.join("mock-lsp.log");
+  let _ = fs::remove_file(&stderr_log);
+
+  let scenarios = Path::new(env!("CARGO_MANIFEST_DIR")).join("mock_lsp/scenarios");
+  let initialize = fs::read_to_string(scenarios.join("initialize.json")).unwrap();
+  let mut crashing: Value = serde_json::from_str(&initialize).unwrap();
+  if let Value::Array(steps) = &mut crashing["steps"] {
+    steps.push(json!("crash"));
+  }
+  fs::write(&scenario, crashing.to_string()).unwrap();
+
+  let lsp = LSPData::create()
+    .path(mock_binary().display().to_string())
+    .args(vec![scenario.display().to_string()])
+    .stderr_log(stderr_log.clone())
+    .file_patterns(vec!["^.+\\.rs$".to_string()])
+    .workspace_folders(vec![WorkspaceFolder {
+      uri: Url::from_directory_path(&dir).unwrap(),
+      name: "stdio".to_string(),
+    }])
+    .capabilities(get_capabilities())
+    .build()
+    .await
+    .unwrap();
+  let logs = lsp.subscribe::<LogMessage>();
+  assert_eq!(lsp.state(), ServerState::Running);
+  assert_eq!(logs.recv().await.unwrap().message, "mock ready");
+
+  wait_for_state(&lsp, ServerState::Crashed).await;
+  //the restarted process plays the scenario without the crash
+  fs::write(&scenario, initialize).unwrap();
+  wait_for_state(&lsp, ServerState::Running).await;
+  assert_eq!(logs.recv().await.unwrap().message, "mock ready");
+
+  lsp.shutdown().await.unwrap();
+  assert_eq!(lsp.state(), ServerState::Stopped);
+
+  let log = fs::read_to_string(&stderr_log).unwrap();
+  assert_eq!(log.matches("Playing").count(), 2);
+  assert!(log.contains("Crashing as scripted"));
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[async_std::test]
+async fn file_manager_syncs_documents_incrementally() {
+  let path = std::env::temp_dir().join(format!("codeforge-sync-{}.rs", std::process::id()));
+  std::fs::write(&path, "fn main() {\n}\n").unwrap();
+  let path = path.display().to_string();
+  let uri = Url::from_file_path(&path).unwrap();
+
+  let server = MockServer::start(vec![initialize(vec![
+    json!({ "expect": {
+      "method": "textDocument/didOpen",
+      "params": { "textDocument": {
+        "uri": uri,
+        "languageId": "rs",
+        "version": 0,
+        "text": "fn main() {\r\n}\r\n",
+      } },
+    } }),
+    json!({ "expect": {
+      "method": "textDocument/didChange",
+      "params": { "textDocument": { "uri": uri, "version": 1 } },
+    } }),
+    json!({ "expect": {
+      "method": "textDocument/didSave",
+      "params": { "text": "fn run() {\r\n  run();\r\n}\r\n" },
+    } }),
+    json!({ "expect": { "method": "textDocument/didClose" } }),
+  ])]);
+  let mut manager = LSPManager::new();
+  manager.add_lsp(connect(&server).await);
+  let mut files = FileManager::new();
+
+  files
+    .open_file(&path, &ParsersManager::new(), &manager)
+    .unwrap();
+  let edits = vec![
+    TextEdit::new(
+      Range::new(Position::new(0, 3), Position::new(0, 7)),
+      "run".to_string(),
+    ),
+    TextEdit::new(
+      Range::new(Position::new(1, 0), Position::new(1, 0)),
+      "  run();\n".to_string(),
+    ),
+  ];
+  files.edit_file(&path, edits, &manager).unwrap();
+  files.save_file(&path, &manager).unwrap();
+  files.close_file(&path, &manager).unwrap();
+
+  manager.shutdown().await;
+  let outcome = server.finish().remove(0);
+  let changes = &outcome.received[3]["params"]["contentChanges"];
+  assert_eq!(changes[0]["text"], "  run();\r\n");
+  assert_eq!(
+    changes[0]["range"]["start"],
+    json!({ "line": 1, "character": 0 })
+  );
+  assert_eq!(changes[1]["text"], "run");
+  assert_eq!(
+    std::fs::read_to_string(&path).unwrap(),
+    "fn run() {\n  run();\n}\n"
+  );
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[async_std::test]
+async fn edit_positions_are_counted_in_utf16_by_default() {
+  let path = std::env::temp_dir().join(format!("codeforge-utf16-{}.rs", std::process::id()));
+  std::fs::write(&path, "let s = \"😀\";\n").unwrap();
+  let path = path.display().to_string();
+
+  let server = MockServer::start(vec![initialize(vec![
+    json!({ "expect": { "method": "textDocument/didOpen" } }),
+    json!({ "expect": { "method": "textDocument/didChange" } }),
+    json!({ "expect": { "method": "textDocument/didChange" } }),
+    json!({ "expect": { "method": "textDocument/didClose" } }),
+  ])]);
+  let mut manager = LSPManager::new();
+  manager.add_lsp(connect(&server).await);
+  let mut files = FileManager::new();
+
+  files
+    .open_file(&path, &ParsersManager::new(), &manager)
+    .unwrap();
+  //the emoji is two UTF-16 code units but four bytes
+  let edit = TextEdit::new(
+    Range::new(Position::new(0, 12), Position::new(0, 12)),
+    "!".to_string(),
+  );
+  files.edit_file(&path, vec![edit], &manager).unwrap();
+  assert_eq!(files.get_file(&path).unwrap().text(), "let s = \"😀\"!;\n");
+  assert!(files.undo_file(&path, &manager).unwrap());
+  assert_eq!(files.get_file(&path).unwrap().text(), "let s = \"😀\";\n");
+  files.close_file(&path, &manager).unwrap();
+
+  manager.shutdown().await;
+  let outcome = server.finish().remove(0);
+  let undo = &outcome.received[4]["params"]["contentChanges"][0];
+  assert_eq!(
+    undo["range"],
+    json!({
+      "start": { "line": 0, "character": 12 },
+      "end": { "line": 0, "character": 13 },
+    })
+  );
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[async_std::test]
+async fn full_sync_sends_the_whole_text() {
+  let server = MockServer::start(vec![initialize_with(
+    json!({ "textDocumentSync": 1 }),
+    vec![
+      json!({ "expect": { "method": "textDocument/didOpen" } }),
+      json!({ "expect": {
+        "method": "textDocument/didChange",
+        "params": { "contentChanges": [{ "text": "fn run() {}" }] },
+      } }),
+      json!({ "expect": { "method": "textDocument/didSave" } }),
+    ],
+  )]);
+  let lsp = connect(&server).await;
+  let uri = Url::parse("file:///workspace/main.rs").unwrap();
+
+  lsp
+    .open_document(TextDocumentItem {
+      uri: uri.clone(),
+      language_id: "rust".to_string(),
+      version: 0,
+      text: "fn main() {}".to_string(),
+    })
+    .unwrap();
+  let edit = TextEdit::new(
+    Range::new(Position::new(0, 3), Position::new(0, 7)),
+    "run".to_string(),
+  );
+  lsp
+    .change_document(&uri, 1, vec![edit], "fn run() {}".to_string())
+    .unwrap();
+  lsp.save_document(&uri).unwrap();
+
+  lsp.shutdown().await.unwrap();
+  let outcome = server.finish().remove(0);
+  assert!(outcome.received[3]["params"]["contentChanges"][0]
+    .get("range")
+    .is_none());
+  assert!(outcome.received[4]["params"].get("text").is_none());
+}
+
+#[async_std::test]
+async fn pushed_and_pulled_diagnostics_are_stored() {
+  let diagnostic = |line: u32, severity: u32, source: &str| {
+    json!({
+      "range": { "start": { "line": line, "character": 0 }, "end": { "line": line, "character": 2 } },
+      "severity": severity,
+      "source": source,
+      "message": source,
+    })
+  };
+
+  let server = MockServer::start(vec![initialize_with(
+    json!({ "diagnosticProvider": { "interFileDependencies": false, "workspaceDiagnostics": false } }),
+    vec![
+      json!({ "expect": { "method": "textDocument/didOpen" } }),
+      json!({ "notify": {
+        "method": "textDocument/publishDiagnostics",
+        "params": {
+          "uri": "file:///workspace/lib.rs",
+          "diagnostics": [diagnostic(0, 1, "rustc"), diagnostic(1, 2, "clippy")],
+        },
+      } }),
+      json!({ "expect": {
+        "method": "textDocument/diagnostic",
+        "params": { "textDocument": { "uri": "file:///workspace/lib.rs" } },
+        "reply": { "result": {
+          "kind": "full",
+          "resultId": "1",
+          "items": [diagnostic(2, 3, "pull")],
+        } },
+      } }),
+      json!({ "request": { "method": "workspace/diagnostic/refresh" } }),
+      json!({ "expect": {
+        "method": "textDocument/diagnostic",
+        "params": { "previousResultId": "1" },
+        "reply": { "result": { "kind": "unchanged", "resultId": "2" } },
+      } }),
+      //tells the test that the refresh was handled
+      json!({ "notify": {
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": "file:///workspace/done.rs", "diagnostics": [diagnostic(0, 4, "done")] },
+      } }),
+      json!({ "expect": { "method": "textDocument/didClose" } }),
+    ],
+  )]);
+  let mut manager = LSPManager::new();
+  manager.track_diagnostics();
+  manager.add_lsp(connect(&server).await);
+  manager
+    .open_document("/workspace/lib.rs", 0, "fn main() {}".to_string())
+    .unwrap();
+
+  let deadline = Instant::now() + Duration::from_secs(10);
+  while manager
+    .get_diagnostics(Some("/workspace/done.rs"), None, None)
+    .unwrap()
+    .is_empty()
+  {
+    assert!(Instant::now() < deadline, "refresh was never handled");
+    async_std::task::sleep(Duration::from_millis(20)).await;
+  }
+
+  let all = manager
+    .get_diagnostics(Some("/workspace/lib.rs"), None, None)
+    .unwrap();
+  assert_eq!(all[0].diagnostics.len(), 3);
+  let warnings = manager
+    .get_diagnostics(None, Some(DiagnosticSeverity::WARNING), None)
+    .unwrap();
+  assert_eq!(warnings.len(), 1);
+  assert_eq!(warnings[0].diagnostics.len(), 2);
+  let clippy = manager.get_diagnostics(None, None, Some("clippy")).unwrap();
+  assert_eq!(clippy[0].diagnostics[0].message, "clippy");
+
+  manager.close_document("/workspace/lib.rs").unwrap();
+  let pushed = manager
+    .get_diagnostics(Some("/workspace/lib.rs"), None, None)
+    .unwrap();
+  assert_eq!(pushed[0].diagnostics.len(), 2);
+
+  manager.shutdown().await;
+  server.finish();
+}
+
+#[async_std::test]
+async fn outdated_pulls_are_cancelled() {
+  let server = MockServer::start(vec![initialize_with(
+    json!({ "diagnosticProvider": { "interFileDependencies": false, "workspaceDiagnostics": false } }),
+    vec![
+      json!({ "expect": { "method": "textDocument/didOpen" } }),
+      json!({ "expect": { "method": "textDocument/diagnostic", "delay": 300 } }),
+      json!({ "expect": { "method": "textDocument/didChange" } }),
+      json!({ "expect": { "method": "$/cancelRequest" } }),
+      json!({ "expect": {
+        "method": "textDocument/diagnostic",
+        "reply": { "result": {
+          "kind": "full",
+          "items": [{
+            "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 2 } },
+            "message": "changed",
+          }],
+        } },
+      } }),
+    ],
+  )]);
+  let path = "/workspace/lib.rs";
+  let mut manager = LSPManager::new();
+  manager.add_lsp(connect(&server).await);
+  manager
+    .open_document(path, 0, "fn main() {}".to_string())
+    .unwrap();
+  //the first pull has to be sent before the document changes
+  async_std::task::sleep(Duration::from_millis(100)).await;
+  let name = Range::new(Position::new(0, 3), Position::new(0, 7));
+  let edit = TextEdit::new(name, "run".into());
+  manager
+    .change_document(path, 1, vec![edit], "fn run() {}".to_string())
+    .unwrap();
+
+  let deadline = Instant::now() + Duration::from_secs(10);
+  let diagnostics = loop {
+    let diagnostics = manager.get_diagnostics(Some(path), None, None).unwrap();
+    if let Some(file) = diagnostics.into_iter().next() {
+      break file.diagnostics;
+    }
+    assert!(Instant::now() < deadline, "diagnostics were never pulled");
+    async_std::task::sleep(Duration::from_millis(20)).await;
+  };
+  assert_eq!(diagnostics[0].message, "changed");
+
+  manager.shutdown().await;
+  let outcomes = server.finish();
+  let pull = &outcomes[0].received[3];
+  assert_eq!(outcomes[0].received[5]["params"]["id"], pull["id"]);
+}
+
+#[async_std::test]
+async fn completions_are_resolved_and_inserted() {
+  let path = std::env::temp_dir().join(format!("codeforge-complete-{}.rs", std::process::id()));
+  std::fs::write(&path, "use std::fs;\nfn main() {\n  pr\n}\n").unwrap();
+  let path = path.display().to_string();
+
+  let item = |label: &str, sort: &str| {
+    json!({
+      "label": label,
+      "sortText": sort,
+      "insertTextFormat": 2,
+      "textEdit": {
+        "range": { "start": { "line": 2, "character": 2 }, "end": { "line": 2, "character": 4 } },
+        "newText": format!("{}!(\"$1\")$0", label),
+      },
+    })
+  };
+  let server = MockServer::start(vec![initialize_with(
+    json!({ "completionProvider": { "triggerCharacters": ["."], "resolveProvider": true } }),
+    vec![
+      json!({ "expect": { "method": "textDocument/didOpen" } }),
+      json!({ "expect": {
+        "method": "textDocument/completion",
+        "params": { "position": { "line": 2, "character": 4 }, "context": { "triggerKind": 1 } },
+        "reply": { "result": {
+          "isIncomplete": false,
+          "items": [item("println", "2"), item("print", "1"), item("format", "0")],
+        } },
+      } }),
+      json!({ "expect": {
+        "method": "completionItem/resolve",
+        "params": { "label": "println" },
+        "reply": { "result": {
+          "label": "println",
+          "insertTextFormat": 2,
+          "textEdit": {
+            "range": { "start": { "line": 2, "character": 2 }, "end": { "line": 2, "character": 4 } },
+            "newText": "println!(\"$1\")$0",
+          },
+          "additionalTextEdits": [{
+            "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 0 } },
+            "newText": "use std::io;\n",
+          }],
+        } },
+      } }),
+      json!({ "expect": { "method": "textDocument/didChange" } }),
+    ],
+  )]);
+  let mut manager = LSPManager::new();
+  manager.add_lsp(connect(&server).await);
+  let mut files = FileManager::new();
+  files
+    .open_file(&path, &ParsersManager::new(), &manager)
+    .unwrap();
+
+  let entries = manager
+    .complete(&path, Position::new(2, 4), None, "  pr")
+    .await
+    .unwrap();
+  let labels: Vec<Value> = entries
+    .iter()
+    .map(|entry| serde_json::to_value(entry).unwrap()["label"].clone())
+    .collect();
+  assert_eq!(labels, vec![json!("print"), json!("println")]);
+
+  //the list is complete, so typing only filters it
+  let entries = manager
+    .complete(&path, Position::new(2, 5), Some("i"), "  pri")
+    .await
+    .unwrap();
+  assert_eq!(entries.len(), 2);
+  let id = serde_json::to_value(&entries[1]).unwrap()["id"]
+    .as_u64()
+    .unwrap() as usize;
+  let accepted = manager
+    .accept_completion(&path, id, Position::new(2, 5))
+    .await
+    .unwrap();
+  assert_eq!(
+    accepted.tabstops,
+    vec![
+      vec![Range::new(Position::new(3, 12), Position::new(3, 12))],
+      vec![Range::new(Position::new(3, 14), Position::new(3, 14))],
+    ]
+  );
+  files.edit_file(&path, accepted.edits, &manager).unwrap();
+  let file = files.get_file(&path).unwrap();
+  assert_eq!(file.line(0), Some("use std::io;"));
+  assert_eq!(file.line(3), Some("  println!(\"\")"));
+
+  //neither a word nor a trigger character
+  assert!(manager
+    .complete(&path, Position::new(3, 15), Some(" "), "  println!(\"\") ")
+    .await
+    .unwrap()
+    .is_empty());
+
+  manager.shutdown().await;
+  server.finish();
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[async_std::test]
+async fn completions_after_multi_byte_characters_use_utf16_columns() {
+  let path = std::env::temp_dir().join(format!("codeforge-accents-{}.rs", std::process::id()));
+  std::fs::write(&path, "let é = fo\n").unwrap();
+  let path = path.display().to_string();
+
+  let server = MockServer::start(vec![initialize_with(
+    json!({ "completionProvider": {} }),
+    vec![
+      json!({ "expect": { "method": "textDocument/didOpen" } }),
+      json!({ "expect": {
+        "method": "textDocument/completion",
+        "params": { "position": { "line": 0, "character": 10 } },
+        "reply": { "result": [
+          { "label": "foo", "insertTextFormat": 2, "insertText": "foo(${1:é})$0" },
+          { "label": "bar" },
+        ] },
+      } }),
+      json!({ "expect": { "method": "textDocument/didChange" } }),
+    ],
+  )]);
+  let mut manager = LSPManager::new();
+  manager.add_lsp(connect(&server).await);
+  let mut files = FileManager::new();
+  files
+    .open_file(&path, &ParsersManager::new(), &manager)
+    .unwrap();
+
+  //`é` is one UTF-16 code unit but two bytes, the word is still `fo`
+  let entries = manager
+    .complete(&path, Position::new(0, 10), None, "let é = fo")
+    .await
+    .unwrap();
+  assert_eq!(entries.len(), 1);
+  let accepted = manager
+    .accept_completion(&path, 0, Position::new(0, 10))
+    .await
+    .unwrap();
+  assert_eq!(
+    accepted.tabstops,
+    vec![
+      vec![Range::new(Position::new(0, 12), Position::new(0, 13))],
+      vec![Range::new(Position::new(0, 14), Position::new(0, 14))],
+    ]
+  );
+  files.edit_file(&path, accepted.edits, &manager).unwrap();
+  let file = files.get_file(&path).unwrap();
+  assert_eq!(file.line(0), Some("let é = foo(é)"));
+
+  manager.shutdown().await;
+  let outcome = server.finish().remove(0);
+  assert_eq!(
+    outcome.received[4]["params"]["contentChanges"][0]["range"],
+    json!({
+      "start": { "line": 0, "character": 8 },
+      "end": { "line": 0, "character": 10 },
+    })
+  );
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[async_std::test]
+async fn hover_and_signature_help_are_rendered() {
+  let server = MockServer::start(vec![initialize_with(
+    json!({ "positionEncoding": "utf-8" }),
+    vec![
+      json!({ "expect": {
+        "method": "textDocument/hover",
+        "params": { "position": { "line": 0, "character": 3 } },
+        "reply": { "result": {
+          "contents": { "kind": "markdown", "value": "```rust\nfn f()\n```\nDocs" },
+          "range": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 4 } },
+        } },
+      } }),
+      json!({ "expect": {
+        "method": "textDocument/signatureHelp",
+        "params": { "context": { "triggerKind": 2, "triggerCharacter": "(" } },
+        "reply": { "result": {
+          "signatures": [{
+            "label": "fn f(\u{e4}: u8, b: u8)",
+            "documentation": "Plain <docs>",
+            "parameters": [{ "label": [5, 11] }, { "label": "b: u8" }],
+          }],
+          "activeParameter": 1,
+        } },
+      } }),
+    ],
+  )]);
+  let mut manager = LSPManager::new();
+  manager.add_lsp(connect(&server).await);
+  let parsers = ParsersManager::new();
+
+  let hover = manager
+    .hover("/workspace/main.rs", Position::new(0, 3), &parsers)
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(
+    hover.html,
+    "<pre><code data-language=\"rust\">fn f()</code></pre><p>Docs</p>"
+  );
+  assert_eq!(
+    hover.range,
+    Some(Range::new(Position::new(0, 3), Position::new(0, 4)))
+  );
+
+  //the first parameter is given in bytes, both are returned in UTF-16 code units
+  let help = manager
+    .signature_help(
+      "/workspace/main.rs",
+      Position::new(0, 5),
+      Some("(".to_string()),
+      &parsers,
+    )
+    .await
+    .unwrap()
+    .unwrap();
+  let signature = &help.signatures[help.active_signature];
+  let offsets: Vec<Option<[u32; 2]>> = signature
+    .parameters
+    .iter()
+    .map(|parameter| parameter.offsets)
+    .collect();
+  assert_eq!(offsets, vec![Some([5, 10]), Some([12, 17])]);
+  assert_eq!(signature.active_parameter, Some(1));
+  assert_eq!(
+    signature.documentation.as_deref(),
+    Some("<p>Plain &lt;docs&gt;</p>")
+  );
+
+  manager.shutdown().await;
+  server.finish();
+}
+
+#[async_std::test]
+async fn goto_responses_are_normalized() {
+  let range = |line: u32| json!({ "start": { "line": line, "character": 0 }, "end": { "line": line, "character": 4 } });
+  let server = MockServer::start(vec![initialize(vec![
+    json!({ "expect": {
+      "method": "textDocument/definition",
+      "params": { "position": { "line": 2, "character": 1 } },
+      "reply": { "result": [{
+        "originSelectionRange": range(2),
+        "targetUri": "file:///workspace/lib.rs",
+        "targetRange": range(7),
+        "targetSelectionRange": range(8),
+      }] },
+    } }),
+    json!({ "expect": {
+      "method": "textDocument/references",
+      "params": { "context": { "includeDeclaration": true } },
+      "reply": { "result": [
+        { "uri": "file:///workspace/main.rs", "range": range(2) },
+        { "uri": "file:///workspace/main.rs", "range": range(2) },
+        { "uri": "untitled:scratch", "range": range(0) },
+      ] },
+    } }),
+  ])]);
+  let mut manager = LSPManager::new();
+  manager.add_lsp(connect(&server).await);
+
+  let definitions = manager
+    .navigate(
+      "/workspace/main.rs",
+      Position::new(2, 1),
+      NavigationKind::Definition,
+    )
+    .await
+    .unwrap();
+  assert_eq!(definitions.len(), 1);
+  assert_eq!(definitions[0].file, "/workspace/lib.rs");
+  assert_eq!(definitions[0].range.start.line, 7);
+  assert_eq!(definitions[0].selection_range.start.line, 8);
+  assert_eq!(
+    definitions[0].origin_range.map(|range| range.start.line),
+    Some(2)
+  );
+  assert!(definitions[0].is_file());
+
+  //duplicates are dropped and targets that are no files are kept but not opened
+  let references = manager
+    .navigate(
+      "/workspace/main.rs",
+      Position::new(2, 1),
+      NavigationKind::References,
+    )
+    .await
+    .unwrap();
+  let files: Vec<(&str, bool)> = references
+    .iter()
+    .map(|target| (target.file.as_str(), target.is_file()))
+    .collect();
+  assert_eq!(
+    files,
+    vec![("/workspace/main.rs", true), ("untitled:scratch", false)]
+  );
+
+  manager.shutdown().await;
+  server.finish();
+}
+
+#[async_std::test]
+async fn rename_edits_open_files_and_files_on_disk() {
+  let dir = std::env::temp_dir().join(format!("codeforge-rename-{}", std::process::id()));
+  std::fs::create_dir_all(&dir).unwrap();
+  let main = dir.join("main.rs").display().to_string();
+  let lib = dir.join("lib.rs").display().to_string();
+  let util = dir.join("util.rs").display().to_string();
+  std::fs::write(&main, "fn main() {\n  run();\n}\n").unwrap();
+  std::fs::write(&lib, "pub fn run() {}\n").unwrap();
+  let (main_uri, lib_uri, util_uri) = (
+    Url::from_file_path(&main).unwrap(),
+    Url::from_file_path(&lib).unwrap(),
+    Url::from_file_path(&util).unwrap(),
+  );
+
+  let edit = |line: u32, character: u32| {
+    json!({
+      "range": {
+        "start": { "line": line, "character": character },
+        "end": { "line": line, "character": character + 3 },
+      },
+      "newText": "start",
+    })
+  };
+  let annotated = |mut edit: Value| {
+    edit["annotationId"] = json!("move");
+    edit
+  };
+  let server = MockServer::start(vec![initialize_with(
+    json!({ "renameProvider": { "prepareProvider": true } }),
+    vec![
+      json!({ "expect": { "method": "textDocument/didOpen" } }),
+      json!({ "expect": {
+        "method": "textDocument/prepareRename",
+        "params": { "position": { "line": 1, "character": 3 } },
+        "reply": { "result": {
+          "start": { "line": 1, "character": 2 },
+          "end": { "line": 1, "character": 5 },
+        } },
+      } }),
+      json!({ "expect": {
+        "method": "textDocument/rename",
+        "params": { "newName": "start" },
+        "reply": { "result": {
+          "documentChanges": [
+            { "textDocument": { "uri": main_uri, "version": 0 }, "edits": [edit(1, 2)] },
+            {
+              "textDocument": { "uri": lib_uri, "version": null },
+              "edits": [annotated(edit(0, 7))],
+            },
+            { "kind": "rename", "oldUri": lib_uri, "newUri": util_uri, "annotationId": "move" },
+          ],
+          "changeAnnotations": {
+            "move": { "label": "Move to util.rs", "needsConfirmation": true },
+          },
+        } },
+      } }),
+      json!({ "expect": {
+        "method": "textDocument/didChange",
+        "params": { "textDocument": { "uri": main_uri, "version": 1 } },
+      } }),
+      json!({ "expect": { "method": "textDocument/didClose" } }),
+    ],
+  )]);
+  let mut manager = LSPManager::new();
+  manager.add_lsp(connect(&server).await);
+  let parsers = ParsersManager::new();
+  let mut files = FileManager::new();
+  files.open_file(&main, &parsers, &manager).unwrap();
+
+  let target = manager
+    .prepare_rename(&main, Position::new(1, 3), "  run();")
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(target.placeholder, "run");
+
+  let edit = manager
+    .rename(&main, Position::new(1, 3), "start".to_string())
+    .await
+    .unwrap()
+    .unwrap();
+  //annotated changes are refused without a confirmation and nothing is touched, the edit of lib
+  //is the first of them
+  let refused = apply_workspace_edit(edit.clone(), false, &mut files, &parsers, &manager);
+  assert_eq!(refused.unwrap_err().failed_change, Some(1));
+  assert_eq!(files.get_file(&main).unwrap().line(1), Some("  run();"));
+
+  let changed = apply_workspace_edit(edit, true, &mut files, &parsers, &manager).unwrap();
+  assert_eq!(changed, vec![main.clone(), lib.clone(), util.clone()]);
+  assert_eq!(files.get_file(&main).unwrap().line(1), Some("  start();"));
+  assert!(!std::path::Path::new(&lib).exists());
+  assert_eq!(
+    std::fs::read_to_string(&util).unwrap(),
+    "pub fn start() {}\n"
+  );
+  files.close_file(&main, &manager).unwrap();
+
+  manager.shutdown().await;
+  server.finish();
+  std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[async_std::test]
+async fn code_actions_are_filtered_resolved_and_executed() {
+  let dir = std::env::temp_dir().join(format!("codeforge-action-{}", std::process::id()));
+  std::fs::create_dir_all(&dir).unwrap();
+  let open = dir.join("open.rs").display().to_string();
+  let disk = dir.join("disk.rs").display().to_string();
+  std::fs::write(&open, "use std::fs;\nfn main() {}\n").unwrap();
+  std::fs::write(&disk, "use std::io;\n").unwrap();
+  let (open_uri, disk_uri) = (
+    Url::from_file_path(&open).unwrap(),
+    Url::from_file_path(&disk).unwrap(),
+  );
+
+  let remove_line = json!([{
+    "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 1, "character": 0 } },
+    "newText": "",
+  }]);
+  let unused = json!({
+    "range": { "start": { "line": 0, "character": 4 }, "end": { "line": 0, "character": 11 } },
+    "message": "unused import",
+  });
+  let server = MockServer::start(vec![initialize_with(
+    json!({ "codeActionProvider": { "resolveProvider": true } }),
+    vec![
+      json!({ "notify": {
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": open_uri, "diagnostics": [unused] },
+      } }),
+      json!({ "expect": { "method": "textDocument/didOpen" } }),
+      json!({ "expect": {
+        "method": "textDocument/codeAction",
+        "params": { "context": { "diagnostics": [unused], "only": ["quickfix"] } },
+        "reply": { "result": [
+          { "title": "Extract", "kind": "refactor.extract" },
+          { "title": "Run", "command": "mock.run" },
+          { "title": "Remove import", "kind": "quickfix", "data": 1 },
+          { "title": "Remove all imports", "kind": "quickfix.all", "isPreferred": true },
+        ] },
+      } }),
+      json!({ "expect": {
+        "method": "codeAction/resolve",
+        "params": { "title": "Remove import", "data": 1 },
+        "reply": { "result": {
+          "title": "Remove import",
+          "kind": "quickfix",
+          "edit": { "changes": { open_uri.as_str(): remove_line } },
+          "command": { "title": "Clean up", "command": "mock.cleanUp", "arguments": [disk_uri] },
+        } },
+      } }),
+      json!({ "expect": { "method": "textDocument/didChange" } }),
+      json!({ "expect": {
+        "method": "workspace/executeCommand",
+        "params": { "command": "mock.cleanUp", "arguments": [disk_uri] },
+      } }),
+      json!({ "request": {
+        "method": "workspace/applyEdit",
+        "params": { "edit": { "changes": { disk_uri.as_str(): remove_line } } },
+        "result": { "applied": true },
+      } }),
+      //tells the test that the edit was applied
+      json!({ "notify": {
+        "method": "window/logMessage",
+        "params": { "type": 3, "message": "done" },
+      } }),
+      json!({ "expect": { "method": "textDocument/didClose" } }),
+    ],
+  )]);
+  let lsp = connect(&server).await;
+  let mut manager = LSPManager::new();
+  manager.track_diagnostics();
+  let logs = manager.subscribe::<LogMessage>();
+  manager.add_lsp(lsp.clone());
+  let parsers = ParsersManager::new();
+  let files = Arc::new(async_std::sync::Mutex::new(FileManager::new()));
+
+  //what the app does with the whole state
+  let (handler_manager, handler_files) = (manager.clone(), files.clone());
+  lsp.on_request::<ApplyAnnotatedWorkspaceEdit, _, _>(move |params| {
+    let (manager, files) = (handler_manager.clone(), handler_files.clone());
+    async move {
+      let mut files = files.lock().await;
+      let applied = apply_workspace_edit(
+        params.edit,
+        false,
+        &mut files,
+        &ParsersManager::new(),
+        &manager,
+      );
+      Ok(ApplyWorkspaceEditResponse {
+        applied: applied.is_ok(),
+        failure_reason: None,
+        failed_change: None,
+      })
+    }
+  });
+
+  let deadline = Instant::now() + Duration::from_secs(10);
+  while manager
+    .get_diagnostics(Some(&open), None, None)
+    .unwrap()
+    .is_empty()
+  {
+    assert!(Instant::now() < deadline, "diagnostics never arrived");
+    async_std::task::sleep(Duration::from_millis(20)).await;
+  }
+  files
+    .lock()
+    .await
+    .open_file(&open, &parsers, &manager)
+    .unwrap();
+
+  //actions without a kind and other kinds are dropped, preferred ones come first
+  let range = Range::new(Position::new(0, 4), Position::new(0, 4));
+  let entries = manager
+    .code_actions(&open, range, Some(vec![CodeActionKind::QUICKFIX]))
+    .await
+    .unwrap();
+  let titles: Vec<&str> = entries
+    .iter()
+    .map(|entry| entry.action.title.as_str())
+    .collect();
+  assert_eq!(titles, vec!["Remove all imports", "Remove import"]);
+
+  let action = manager
+    .resolve_code_action(&open, entries[1].id)
+    .await
+    .unwrap();
+  let mut open_files = files.lock().await;
+  let changed = apply_workspace_edit(
+    action.edit.unwrap(),
+    false,
+    &mut open_files,
+    &parsers,
+    &manager,
+  )
+  .unwrap();
+  assert_eq!(changed, vec![open.clone()]);
+  assert_eq!(
+    open_files.get_file(&open).unwrap().line(0),
+    Some("fn main() {}")
+  );
+  drop(open_files);
+
+  manager
+    .execute_command(&open, action.action.command.unwrap())
+    .unwrap()
+    .await
+    .unwrap();
+  logs.recv().await.unwrap();
+  assert_eq!(std::fs::read_to_string(&disk).unwrap(), "");
+
+  files.lock().await.close_file(&open, &manager).unwrap();
+  manager.shutdown().await;
+  server.finish();
+  std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[async_std::test]
+async fn formatting_is_applied_as_one_undoable_change() {
+  let path = std::env::temp_dir().join(format!("codeforge-format-{}.rs", std::process::id()));
+  std::fs::write(&path, "fn main() {\n  if a {\n    b( );\n  }\n}\n").unwrap();
+  let path = path.display().to_string();
+
+  let edit = |line: u32, start: u32, end: u32, text: &str| {
+    json!({
+      "range": {
+        "start": { "line": line, "character": start },
+        "end": { "line": line, "character": end },
+      },
+      "newText": text,
+    })
+  };
+  let server = MockServer::start(vec![initialize_with(
+    json!({
+      "documentFormattingProvider": true,
+      "documentOnTypeFormattingProvider": { "firstTriggerCharacter": "}", "moreTriggerCharacter": [";"] },
+    }),
+    vec![
+      json!({ "expect": { "method": "textDocument/didOpen" } }),
+      json!({ "expect": {
+        "method": "textDocument/formatting",
+        "params": { "options": { "tabSize": 2, "insertSpaces": true } },
+        "reply": { "result": [edit(2, 6, 7, ""), edit(0, 2, 2, "_")] },
+      } }),
+      json!({ "expect": {
+        "method": "textDocument/didChange",
+        "params": { "textDocument": { "version": 1 } },
+      } }),
+      json!({ "expect": {
+        "method": "textDocument/didChange",
+        "params": { "textDocument": { "version": 2 } },
+      } }),
+      json!({ "expect": {
+        "method": "textDocument/didChange",
+        "params": { "textDocument": { "version": 3 } },
+      } }),
+      json!({ "expect": {
+        "method": "textDocument/onTypeFormatting",
+        "params": { "ch": ";", "position": { "line": 2, "character": 8 } },
+        "reply": { "result": [edit(2, 0, 4, "\t")] },
+      } }),
+    ],
+  )]);
+  let mut manager = LSPManager::new();
+  manager.add_lsp(connect(&server).await);
+  let mut files = FileManager::new();
+  files
+    .open_file(&path, &ParsersManager::new(), &manager)
+    .unwrap();
+  let lines = |files: &FileManager| -> Vec<String> {
+    let file = files.get_file(&path).unwrap();
+    (0..5)
+      .map(|line| file.line(line).unwrap().to_string())
+      .collect()
+  };
+  let original = lines(&files);
+
+  let indentation = files.get_file(&path).unwrap().indentation();
+  assert_eq!(
+    indentation,
+    Indentation {
+      tabs: false,
+      size: 2
+    }
+  );
+  let edits = manager
+    .format(&path, None, indentation.into())
+    .await
+    .unwrap();
+  files.edit_file(&path, edits, &manager).unwrap();
+  let formatted = lines(&files);
+  assert_eq!(formatted[0], "fn_ main() {");
+  assert_eq!(formatted[2], "    b();");
+
+  //both edits are reverted at once
+  assert!(files.undo_file(&path, &manager).unwrap());
+  assert_eq!(lines(&files), original);
+  assert!(!files.undo_file(&path, &manager).unwrap());
+  assert!(files.redo_file(&path, &manager).unwrap());
+  assert_eq!(lines(&files), formatted);
+
+  //only trigger characters of the server are sent
+  let position = Position::new(2, 8);
+  let none = manager
+    .format_on_type(&path, position, "a", indentation.into())
+    .await
+    .unwrap();
+  assert!(none.is_empty());
+  let edits = manager
+    .format_on_type(&path, position, ";", indentation.into())
+    .await
+    .unwrap();
+  assert_eq!(edits[0].new_text, "\t");
+
+  manager.shutdown().await;
+  server.finish();
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[async_std::test]
+async fn inlay_hints_are_inserted_as_virtual_tokens() {
+  let position = |line: u32, character: u32| json!({ "line": line, "character": character });
+  let server = MockServer::start(vec![initialize_with(
+    json!({ "inlayHintProvider": { "resolveProvider": true } }),
+    vec![
+      json!({ "expect": {
+        "method": "textDocument/inlayHint",
+        "params": { "range": { "start": position(0, 0), "end": position(2, 0) } },
+        "reply": { "result": [
+          { "position": position(0, 5), "label": ": i32", "kind": 1 },
+          {
+            "position": position(1, 2),
+            "label": [{ "value": "a" }, { "value": ":" }],
+            "kind": 2,
+            "paddingRight": true,
+            "data": 1,
+          },
+        ] },
+      } }),
+      json!({ "expect": {
+        "method": "inlayHint/resolve",
+        "params": { "data": 1 },
+        "reply": { "result": {
+          "position": position(1, 2),
+          "label": [{ "value": "a" }, { "value": ":" }],
+          "kind": 2,
+          "tooltip": "parameter a",
+        } },
+      } }),
+      json!({ "request": { "method": "workspace/inlayHint/refresh" } }),
+      //tells the test that the refresh was handled
+      json!({ "notify": {
+        "method": "window/logMessage",
+        "params": { "type": 3, "message": "done" },
+      } }),
+    ],
+  )]);
+  let mut manager = LSPManager::new();
+  let logs = manager.subscribe::<LogMessage>();
+  manager.add_lsp(connect(&server).await);
+  let path = "/workspace/main.rs";
+
+  let token = |start: u32, token: &str| Token {
+    start,
+    token: token.to_string(),
+    ..Default::default()
+  };
+  let mut tree = TokenTree::new();
+  tree.set_tokens(vec![
+    vec![token(0, "let"), token(4, "x"), token(6, "="), token(8, "1")],
+    vec![token(0, "f"), token(1, "("), token(2, "2"), token(3, ")")],
+  ]);
+  let range = Range::new(Position::new(0, 0), Position::new(2, 0));
+  tree.insert_virtual(manager.inlay_hints(path, range).await.unwrap());
+
+  let line = |line: usize| -> Vec<(u32, &str, bool)> {
+    tree.tokens[line]
+      .iter()
+      .map(|token| (token.start, token.token.as_str(), token.virtual_))
+      .collect()
+  };
+  assert_eq!(
+    line(0),
+    vec![
+      (0, "let", false),
+      (4, "x", false),
+      (5, ": i32", true),
+      (6, "=", false),
+      (8, "1", false),
+    ]
+  );
+  //label parts keep their order in front of the token at the same column
+  assert_eq!(
+    line(1),
+    vec![
+      (0, "f", false),
+      (1, "(", false),
+      (2, "a", true),
+      (2, ": ", true),
+      (2, "2", false),
+      (3, ")", false),
+    ]
+  );
+  assert_eq!(tree.tokens[1][3].type_, "inlay_hint.parameter");
+
+  //resolved hints are kept, so the second call sends nothing
+  for _ in 0..2 {
+    let hint = manager
+      .resolve_inlay_hint(path, Position::new(1, 2))
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(
+      hint.tooltip,
+      Some(InlayHintTooltip::String("parameter a".to_string()))
+    );
+  }
+
+  logs.recv().await.unwrap();
+  let refreshed = manager
+    .resolve_inlay_hint(path, Position::new(1, 2))
+    .await
+    .unwrap();
+  assert!(refreshed.is_none());
+
+  manager.shutdown().await;
+  server.finish();
+}
+
+#[async_std::test]
+async fn inlay_hints_after_multi_byte_characters_are_placed_in_bytes() {
+  let path = std::env::temp_dir().join(format!("codeforge-hints-{}.rs", std::process::id()));
+  std::fs::write(&path, "let é = 1;\n").unwrap();
+  let path = path.display().to_string();
+
+  let server = MockServer::start(vec![initialize_with(
+    json!({ "inlayHintProvider": true }),
+    vec![
+      json!({ "expect": { "method": "textDocument/didOpen" } }),
+      json!({ "expect": {
+        "method": "textDocument/inlayHint",
+        "reply": { "result": [
+          { "position": { "line": 0, "character": 5 }, "label": ": i32", "kind": 1 },
+        ] },
+      } }),
+    ],
+  )]);
+  let mut manager = LSPManager::new();
+  manager.add_lsp(connect(&server).await);
+  let mut files = FileManager::new();
+  files
+    .open_file(&path, &ParsersManager::new(), &manager)
+    .unwrap();
+
+  let token = |start: u32, token: &str| Token {
+    start,
+    token: token.to_string(),
+    ..Default::default()
+  };
+  let mut tree = TokenTree::new();
+  tree.set_tokens(vec![vec![
+    token(0, "let"),
+    token(4, "é"),
+    token(7, "="),
+    token(9, "1"),
+  ]]);
+  //`é` is one UTF-16 code unit but two bytes
+  files
+    .insert_inlay_hints(&mut tree, &path, &manager, None)
+    .await;
+  let hint = &tree.tokens[0][2];
+  assert_eq!(
+    (hint.start, hint.token.as_str(), hint.virtual_),
+    (6, ": i32", true)
+  );
+
+  manager.shutdown().await;
+  server.finish();
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[async_std::test]
+async fn code_lenses_are_resolved_and_run() {
+  let lens_range = json!({
+    "start": { "line": 0, "character": 3 },
+    "end": { "line": 0, "character": 7 },
+  });
+  let server = MockServer::start(vec![initialize_with(
+    json!({ "codeLensProvider": { "resolveProvider": true } }),
+    vec![
+      json!({ "expect": {
+        "method": "textDocument/codeLens",
+        "params": { "textDocument": { "uri": "file:///workspace/main.rs" } },
+        "reply": { "result": [
+          {
+            "range": lens_range,
+            "command": { "title": "Run", "command": "rust-analyzer.runSingle", "arguments": [1] },
+          },
+          { "range": lens_range, "data": { "impls": 1 } },
+        ] },
+      } }),
+      json!({ "expect": {
+        "method": "codeLens/resolve",
+        "params": { "data": { "impls": 1 } },
+        "reply": { "result": {
+          "range": lens_range,
+          "command": { "title": "1 implementation", "command": "mock.showImpls" },
+        } },
+      } }),
+      json!({ "expect": {
+        "method": "workspace/executeCommand",
+        "params": { "command": "mock.showImpls" },
+        "reply": { "result": null },
+      } }),
+      json!({ "request": { "method": "workspace/codeLens/refresh" } }),
+      //tells the test that the refresh was handled
+      json!({ "notify": {
+        "method": "window/logMessage",
+        "params": { "type": 3, "message": "done" },
+      } }),
+    ],
+  )]);
+  let mut manager = LSPManager::new();
+  let logs = manager.subscribe::<LogMessage>();
+  manager.add_lsp(connect(&server).await);
+  let path = "/workspace/main.rs";
+
+  let ran = Arc::new(std::sync::Mutex::new(Vec::new()));
+  let recorded = ran.clone();
+  manager.register_command("rust-analyzer.runSingle", move |command| {
+    recorded.lock().unwrap().push(command.arguments);
+    Ok(None)
+  });
+
+  let lenses = manager.code_lenses(path).await.unwrap();
+  assert_eq!(lenses.len(), 2);
+  assert_eq!(lenses[0].command.as_ref().unwrap().title, "Run");
+  assert!(lenses[1].command.is_none());
+
+  //resolved lenses are kept, so the second call sends nothing
+  for _ in 0..2 {
+    let lens = manager.resolve_code_lens(path, 1).await.unwrap();
+    assert_eq!(lens.command.unwrap().title, "1 implementation");
+  }
+
+  //client commands never reach the server
+  for id in 0..2 {
+    let command = manager.resolve_code_lens(path, id).await.unwrap().command;
+    manager
+      .execute_command(path, command.unwrap())
+      .unwrap()
+      .await
+      .unwrap();
+  }
+  assert_eq!(*ran.lock().unwrap(), vec![Some(vec![json!(1)])]);
+
+  logs.recv().await.unwrap();
+  assert!(manager.resolve_code_lens(path, 1).await.is_err());
+
+  manager.shutdown().await;
+  server.finish();
+}
+
+#[async_std::test]
+async fn folding_ranges_are_merged_and_collapsed_regions_kept() {
+  let dir = std::env::temp_dir().join(format!("codeforge-fold-{}", std::process::id()));
+  std::fs::create_dir_all(&dir).unwrap();
+  let path = dir.join("main.rs").display().to_string();
+  let fold_file = dir.join("folds.json");
+  std::fs::write(
+    &path,
+    "use std::{\n  fs,\n};\n\nfn main() {\n  let a = 1;\n  if a == 1 {\n    a;\n  }\n}\n",
+  )
+  .unwrap();
+
+  let range = |start: u32, end: u32| json!({ "startLine": start, "endLine": end, "kind": "region" });
+  let server = MockServer::start(vec![initialize_with(
+    json!({ "foldingRangeProvider": true }),
+    vec![
+      json!({ "expect": { "method": "textDocument/didOpen" } }),
+      json!({ "expect": {
+        "method": "textDocument/foldingRange",
+        "reply": { "result": [range(4, 8)] },
+      } }),
+      json!({ "expect": { "method": "textDocument/didChange" } }),
+      json!({ "expect": {
+        "method": "textDocument/foldingRange",
+        "reply": { "result": [range(5, 9)] },
+      } }),
+      json!({ "expect": { "method": "textDocument/didSave" } }),
+    ],
+  )]);
+  let mut manager = LSPManager::new();
+  manager.add_lsp(connect(&server).await);
+  let mut parsers = ParsersManager::new();
+  parsers
+    .add_language(
+      tree_sitter_rust::language(),
+      "",
+      "",
+      "",
+      include_str!("../../queries/rust/folds.scm"),
+      "^.+\\.rs$".to_string(),
+    )
+    .unwrap();
+  let mut files = FileManager::new();
+  files.load_folds(fold_file.clone());
+  files.open_file(&path, &parsers, &manager).unwrap();
+
+  //the server range wins at line 4, the closing bracket of the if stays visible
+  let regions = files
+    .folding_ranges(&path, &parsers, &manager)
+    .await
+    .unwrap();
+  let lines: Vec<(u32, u32, bool)> = regions
+    .iter()
+    .map(|region| (region.range.start_line, region.range.end_line, region.range.kind.is_some()))
+    .collect();
+  assert_eq!(lines, vec![(0, 2, false), (4, 8, true), (6, 7, false)]);
+
+  files.set_folded(&path, 6, true).unwrap();
+  let comment = TextEdit::new(Range::new(Position::new(5, 0), Position::new(5, 0)), "//a\n".into());
+  files.edit_file(&path, vec![comment], &manager).unwrap();
+  let regions = files
+    .folding_ranges(&path, &parsers, &manager)
+    .await
+    .unwrap();
+  let collapsed: Vec<u32> = regions
+    .iter()
+    .filter(|region| region.collapsed)
+    .map(|region| region.range.start_line)
+    .collect();
+  assert_eq!(collapsed, vec![7]);
+
+  //the moved region is saved with the content and restored after a restart
+  files.save_file(&path, &manager).unwrap();
+  assert!(FoldState::load(fold_file).is_collapsed(&path, 7));
+
+  manager.shutdown().await;
+  server.finish();
+  std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[async_std::test]
+async fn symbols_are_nested_ranked_and_resolved() {
+  let uri = "file:///workspace/main.rs";
+  let range = |start: u32, end: u32| {
+    json!({ "start": { "line": start, "character": 0 }, "end": { "line": end, "character": 1 } })
+  };
+  let server = MockServer::start(vec![initialize_with(
+    json!({
+      "documentSymbolProvider": true,
+      "workspaceSymbolProvider": { "resolveProvider": true },
+    }),
+    vec![
+      json!({ "expect": {
+        "method": "textDocument/documentSymbol",
+        "reply": { "result": [
+          { "name": "new", "kind": 6, "deprecated": true, "location": { "uri": uri, "range": range(1, 3) } },
+          { "name": "Config", "kind": 23, "location": { "uri": uri, "range": range(0, 4) } },
+          { "name": "odd", "kind": 99, "location": { "uri": uri, "range": range(6, 6) } },
+        ] },
+      } }),
+      json!({ "expect": {
+        "method": "textDocument/documentSymbol",
+        "reply": { "result": [
+          { "name": "other", "kind": 12, "range": range(0, 2), "selectionRange": range(0, 0) },
+          {
+            "name": "main",
+            "kind": 12,
+            "range": range(3, 9),
+            "selectionRange": range(3, 3),
+            "children": [{
+              "name": "parse_config",
+              "kind": 12,
+              "tags": [1, 2],
+              "range": range(4, 8),
+              "selectionRange": range(4, 4),
+            }],
+          },
+        ] },
+      } }),
+      json!({ "expect": {
+        "method": "workspace/symbol",
+        "params": { "query": "cfg" },
+        "reply": { "result": [
+          { "name": "ConfigFile", "kind": 5, "location": { "uri": "file:///workspace/lib.rs" }, "data": 7 },
+          { "name": "unrelated", "kind": 12, "location": { "uri": uri, "range": range(0, 0) } },
+          { "name": "cfg", "kind": 13, "location": { "uri": uri, "range": range(2, 2) } },
+        ] },
+      } }),
+      json!({ "expect": {
+        "method": "workspaceSymbol/resolve",
+        "params": { "name": "ConfigFile", "data": 7 },
+        "reply": { "result": {
+          "name": "ConfigFile",
+          "kind": 5,
+          "location": { "uri": "file:///workspace/lib.rs", "range": range(10, 20) },
+        } },
+      } }),
+    ],
+  )]);
+  let mut manager = LSPManager::new();
+  manager.add_lsp(connect(&server).await);
+  let path = "/workspace/main.rs";
+
+  //flat symbols are nested by their ranges, unknown kinds become variables
+  let outline = manager.outline(path, None).await.unwrap();
+  assert_eq!(outline.len(), 2);
+  assert_eq!(outline[0].name, "Config");
+  assert_eq!(outline[0].children[0].name, "new");
+  assert_eq!(outline[0].children[0].tags, vec![SymbolTag::DEPRECATED]);
+  assert_eq!(outline[1].kind, SymbolKind::VARIABLE);
+
+  //parents of matches are kept, unknown tags are dropped
+  let outline = manager.outline(path, Some("pc")).await.unwrap();
+  assert_eq!(outline.len(), 1);
+  assert_eq!(outline[0].name, "main");
+  assert_eq!(outline[0].children[0].tags, vec![SymbolTag::DEPRECATED]);
+
+  let entries = manager.workspace_symbols("cfg").await.unwrap();
+  let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+  assert_eq!(names, vec!["cfg", "ConfigFile"]);
+  assert_eq!(entries[1].file, "/workspace/lib.rs");
+
+  let target = manager
+    .resolve_workspace_symbol(entries[1].id)
+    .await
+    .unwrap();
+  assert_eq!(target.file, "/workspace/lib.rs");
+  assert_eq!(target.range.start.line, 10);
+
+  manager.shutdown().await;
+  server.finish();
+}
+
+#[async_std::test]
+async fn hierarchies_are_expanded_lazily_and_cached() {
+  let path = std::env::temp_dir().join(format!("codeforge-hierarchy-{}.rs", std::process::id()));
+  std::fs::write(&path, "fn main() {\n  run();\n}\n").unwrap();
+  let uri = Url::from_file_path(&path).unwrap();
+  let path = path.display().to_string();
+
+  let item = |name: &str, line: u32| {
+    let range = json!({
+      "start": { "line": line, "character": 0 },
+      "end": { "line": line, "character": 4 },
+    });
+    json!({ "name": name, "kind": 12, "uri": uri, "range": range, "selectionRange": range })
+  };
+  //items of documents that are not open are never cached
+  let mut foo = item("Foo", 8);
+  foo["uri"] = json!("file:///workspace/foo.rs");
+  let server = MockServer::start(vec![initialize(vec![
+    json!({ "expect": { "method": "textDocument/didOpen" } }),
+    json!({ "expect": {
+      "method": "textDocument/prepareCallHierarchy",
+      "reply": { "result": [item("run", 5)] },
+    } }),
+    json!({ "expect": {
+      "method": "callHierarchy/incomingCalls",
+      "params": { "item": { "name": "run" } },
+      "reply": { "result": [{ "from": item("main", 0), "fromRanges": [item("run", 1)["range"]] }] },
+    } }),
+    json!({ "expect": { "method": "textDocument/didChange" } }),
+    json!({ "expect": {
+      "method": "callHierarchy/incomingCalls",
+      "reply": { "result": [{ "from": item("main", 1), "fromRanges": [item("run", 2)["range"]] }] },
+    } }),
+    json!({ "expect": {
+      "method": "textDocument/prepareTypeHierarchy",
+      "reply": { "result": [foo] },
+    } }),
+    json!({ "expect": {
+      "method": "typeHierarchy/supertypes",
+      "params": { "item": { "name": "Foo" } },
+      "reply": { "result": [item("Bar", 9)] },
+    } }),
+    json!({ "expect": {
+      "method": "typeHierarchy/supertypes",
+      "params": { "item": { "name": "Foo" } },
+      "reply": { "result": [item("Bar", 9)] },
+    } }),
+  ])]);
+  let mut manager = LSPManager::new();
+  manager.add_lsp(connect(&server).await);
+  let mut files = FileManager::new();
+  files
+    .open_file(&path, &ParsersManager::new(), &manager)
+    .unwrap();
+
+  let roots = manager
+    .prepare_hierarchy(&path, Position::new(1, 2), HierarchyKind::Call)
+    .await
+    .unwrap();
+  assert_eq!(roots[0].name, "run");
+
+  //the second expansion is answered from the cache
+  let mut ids = Vec::new();
+  for _ in 0..2 {
+    let callers = manager
+      .expand_hierarchy(roots[0].id, HierarchyDirection::Incoming)
+      .await
+      .unwrap();
+    assert_eq!(callers[0].name, "main");
+    assert_eq!(callers[0].call_ranges[0].start.line, 1);
+    ids.push(callers[0].id);
+  }
+
+  //an edit of the document asks the server again, the callers keep their ids
+  let edit = TextEdit::new(Range::new(Position::new(0, 0), Position::new(0, 0)), "//\n".into());
+  files.edit_file(&path, vec![edit], &manager).unwrap();
+  let callers = manager
+    .expand_hierarchy(roots[0].id, HierarchyDirection::Incoming)
+    .await
+    .unwrap();
+  assert_eq!(callers[0].call_ranges[0].start.line, 2);
+  ids.push(callers[0].id);
+  assert!(ids.iter().all(|id| *id == ids[0]));
+
+  let roots = manager
+    .prepare_hierarchy(&path, Position::new(8, 0), HierarchyKind::Type)
+    .await
+    .unwrap();
+  let mut supertypes = Vec::new();
+  for _ in 0..2 {
+    let expanded = manager
+      .expand_hierarchy(roots[0].id, HierarchyDirection::Supertypes)
+      .await
+      .unwrap();
+    assert_eq!(expanded[0].name, "Bar");
+    supertypes.push(expanded[0].id);
+  }
+  assert_eq!(supertypes[0], supertypes[1]);
+  assert!(manager
+    .expand_hierarchy(roots[0].id, HierarchyDirection::Outgoing)
+    .await
+    .is_err());
+
+  manager.shutdown().await;
+  server.finish();
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[async_std::test]
+async fn occurrences_are_marked_and_linked_edits_mirrored() {
+  let dir = std::env::temp_dir().join(format!("codeforge-linked-{}", std::process::id()));
+  std::fs::create_dir_all(&dir).unwrap();
+  let path = dir.join("main.rs").display().to_string();
+  std::fs::write(&path, "let x = 1;\nx;\n<div></div>\n").unwrap();
+
+  let range = |line: u32, start: u32, end: u32| {
+    json!({ "start": { "line": line, "character": start }, "end": { "line": line, "character": end } })
+  };
+  let server = MockServer::start(vec![initialize_with(
+    json!({ "documentHighlightProvider": true, "linkedEditingRangeProvider": true }),
+    vec![
+      json!({ "expect": { "method": "textDocument/didOpen" } }),
+      json!({ "expect": {
+        "method": "textDocument/documentHighlight",
+        "params": { "position": { "line": 0, "character": 4 } },
+        "reply": { "result": [
+          { "range": range(0, 4, 5), "kind": 3 },
+          { "range": range(1, 0, 1), "kind": 2 },
+        ] },
+      } }),
+      json!({ "expect": {
+        "method": "textDocument/linkedEditingRange",
+        "params": { "position": { "line": 2, "character": 1 } },
+        "reply": { "result": {
+          "ranges": [range(2, 1, 4), range(2, 7, 10)],
+          "wordPattern": "[a-z]+",
+        } },
+      } }),
+      json!({ "expect": { "method": "textDocument/didChange" } }),
+      json!({ "expect": {
+        "method": "textDocument/linkedEditingRange",
+        "reply": { "result": {
+          "ranges": [range(2, 1, 5), range(2, 8, 12)],
+          "wordPattern": "[a-z]+",
+        } },
+      } }),
+      json!({ "expect": { "method": "textDocument/didChange" } }),
+      //both undos
+      json!({ "expect": { "method": "textDocument/didChange" } }),
+      json!({ "expect": { "method": "textDocument/didChange" } }),
+    ],
+  )]);
+  let mut manager = LSPManager::new();
+  manager.add_lsp(connect(&server).await);
+  let parsers = ParsersManager::new();
+  let mut files = FileManager::new();
+  files.open_file(&path, &parsers, &manager).unwrap();
+
+  let token = |start: u32, token: &str| Token {
+    start,
+    token: token.to_string(),
+    ..Default::default()
+  };
+  let mut tree = TokenTree::new();
+  tree.set_tokens(vec![
+    vec![token(0, "let"), token(4, "x"), token(6, "=")],
+    vec![token(0, "x"), token(1, ";")],
+  ]);
+  let highlights = manager
+    .document_highlights(&path, Position::new(0, 4))
+    .await
+    .unwrap();
+  tree.mark_occurrences(&highlights);
+  let modifiers = |line: usize, index: usize| tree.tokens[line][index].modifiers.clone();
+  assert_eq!(modifiers(0, 0), None);
+  assert_eq!(modifiers(0, 1), Some(vec!["occurrence.write".to_string()]));
+  assert_eq!(modifiers(1, 0), Some(vec!["occurrence.read".to_string()]));
+  assert_eq!(modifiers(1, 1), None);
+
+  let rename = TextEdit::new(Range::new(Position::new(2, 1), Position::new(2, 4)), "span".into());
+  let edits = files.edit_linked(&path, rename, &manager).await.unwrap();
+  assert_eq!(edits.len(), 2);
+  assert_eq!(files.get_file(&path).unwrap().line(2), Some("<span></span>"));
+
+  //the word would no longer match the pattern, so the edit is not mirrored
+  let digit = TextEdit::new(Range::new(Position::new(2, 5), Position::new(2, 5)), "1".into());
+  let edits = files.edit_linked(&path, digit, &manager).await.unwrap();
+  assert_eq!(edits.len(), 1);
+  assert_eq!(files.get_file(&path).unwrap().line(2), Some("<span1></span>"));
+
+  //the mirrored edit is undone as one change
+  files.undo_file(&path, &manager).unwrap();
+  files.undo_file(&path, &manager).unwrap();
+  assert_eq!(files.get_file(&path).unwrap().line(2), Some("<div></div>"));
+
+  manager.shutdown().await;
+  server.finish();
+  std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,164 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  fs::{File, OpenOptions},
+  io::Write,
+  path::Path,
+  sync::{Arc, Mutex},
+  time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Error;
+use lsp_types::{
+  notification::{Cancel, Notification},
+  NumberOrString,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+///Frames kept per server before the oldest ones are dropped
+pub(crate) const TRAFFIC_CAPACITY: usize = 1000;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum Direction {
+  ///Read from the server
+  Incoming,
+  ///Written to the server
+  Outgoing,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TrafficEntry {
+  direction: Direction,
+  ///Milliseconds since the unix epoch
+  timestamp: u64,
+  ///For responses the method of the request they answer
+  method: Option<String>,
+  id: Option<NumberOrString>,
+  ///Milliseconds between a request and its response, only set on responses
+  latency: Option<u64>,
+  message: Value,
+}
+
+///Bounded record of every frame exchanged with a server, optionally mirrored to a file
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TrafficLog {
+  inner: Arc<Mutex<TrafficLogInner>>,
+}
+
+#[derive(Debug, Default)]
+struct TrafficLogInner {
+  entries: VecDeque<TrafficEntry>,
+  ///Requests waiting for a response, keyed by the direction they were sent in. Cancelled
+  ///requests are dropped as they may never be answered.
+  in_flight: HashMap<(Direction, NumberOrString), (String, Instant)>,
+  file: Option<File>,
+}
+
+#[derive(Deserialize)]
+struct FrameInfo {
+  method: Option<String>,
+  id: Option<NumberOrString>,
+}
+
+impl TrafficLog {
+  ///Appends every recorded frame as one json line to `path`
+  pub(crate) fn with_file(path: &Path) -> Result<Self, Error> {
+    if let Some(dir) = path.parent() {
+      std::fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+    let log = TrafficLog::default();
+    log.inner.lock().unwrap().file = Some(file);
+    Ok(log)
+  }
+
+  ///Records a frame, outgoing frames still carry their `Content-Length` header
+  pub(crate) fn record(&self, direction: Direction, frame: &[u8]) {
+    let body = match frame.windows(4).position(|window| window == b"\r\n\r\n") {
+      Some(start) if direction == Direction::Outgoing => &frame[start + 4..],
+      _ => frame,
+    };
+    let message: Value = serde_json::from_slice(body)
+      .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).to_string()));
+    let FrameInfo { mut method, id } =
+      serde_json::from_value(message.clone()).unwrap_or(FrameInfo {
+        method: None,
+        id: None,
+      });
+
+    let mut inner = self.inner.lock().unwrap();
+    if method.as_deref() == Some(Cancel::METHOD) {
+      if let Ok(cancelled) = serde_json::from_value(message["params"]["id"].clone()) {
+        inner.in_flight.remove(&(direction, cancelled));
+      }
+    }
+
+    let mut latency = None;
+    if let Some(id) = &id {
+      match &method {
+        Some(request) => {
+          //requests of a server that never answers are dropped, the oldest first
+          if inner.in_flight.len() == TRAFFIC_CAPACITY {
+            let oldest = inner
+              .in_flight
+              .iter()
+              .min_by_key(|(_, (_, sent))| *sent)
+              .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+              inner.in_flight.remove(&oldest);
+            }
+          }
+          inner
+            .in_flight
+            .insert((direction, id.clone()), (request.clone(), Instant::now()));
+        }
+        None => {
+          let request_direction = match direction {
+            Direction::Incoming => Direction::Outgoing,
+            Direction::Outgoing => Direction::Incoming,
+          };
+          if let Some((request, sent)) = inner.in_flight.remove(&(request_direction, id.clone())) {
+            method = Some(request);
+            latency = Some(sent.elapsed().as_millis() as u64);
+          }
+        }
+      }
+    }
+
+    let entry = TrafficEntry {
+      direction,
+      timestamp: SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis() as u64)
+        .unwrap_or_default(),
+      method,
+      id,
+      latency,
+      message,
+    };
+
+    if let Some(file) = &mut inner.file {
+      let line = serde_json::to_string(&entry).unwrap_or_default();
+      if let Err(err) = writeln!(file, "{}", line) {
+        eprintln!("Error writing lsp traffic: {}", err);
+      }
+    }
+
+    if inner.entries.len() == TRAFFIC_CAPACITY {
+      inner.entries.pop_front();
+    }
+    inner.entries.push_back(entry);
+  }
+
+  ///Forgets the requests of a closed connection, they will never be answered
+  pub(crate) fn clear_in_flight(&self) {
+    self.inner.lock().unwrap().in_flight.clear();
+  }
+
+  pub(crate) fn entries(&self) -> Vec<TrafficEntry> {
+    self.inner.lock().unwrap().entries.iter().cloned().collect()
+  }
+}
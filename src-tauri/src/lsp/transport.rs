@@ -0,0 +1,250 @@
+use std::{
+  fmt,
+  io::{BufRead, BufReader, Read, Write},
+  net::{Shutdown, TcpStream, ToSocketAddrs},
+  process::{Child, ExitStatus},
+  time::Duration,
+};
+
+#[cfg(unix)]
+use std::{os::unix::net::UnixStream, path::PathBuf};
+
+use anyhow::Error;
+
+use super::process::{LSPProcess, SpawnOptions};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+///How the client reaches a language server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Transport {
+  ///Spawns the server and talks to it over stdin and stdout
+  Stdio(SpawnOptions),
+  ///Connects to an already running server
+  Tcp(String),
+  #[cfg(unix)]
+  Unix(PathBuf),
+}
+
+///The other end of a connection, either a spawned process or a socket
+#[derive(Debug)]
+pub(crate) enum Endpoint {
+  Process(Child),
+  Tcp(TcpStream),
+  #[cfg(unix)]
+  Unix(UnixStream),
+}
+
+pub(crate) struct Connection {
+  pub(crate) writer: MessageWriter,
+  pub(crate) reader: MessageReader,
+  pub(crate) endpoint: Endpoint,
+}
+
+pub(crate) struct MessageWriter(Box<dyn Write + Send>);
+
+pub(crate) struct MessageReader(BufReader<Box<dyn Read + Send>>);
+
+impl Transport {
+  ///Connects on a blocking thread, reaching a socket can take up to `CONNECT_TIMEOUT`
+  pub(crate) async fn connect(&self) -> Result<Connection, Error> {
+    let transport = self.clone();
+    async_std::task::spawn_blocking(move || transport.connect_blocking()).await
+  }
+
+  ///Servers behind a socket may be shared with other clients, so they are never shut down
+  pub(crate) fn is_socket(&self) -> bool {
+    !matches!(self, Transport::Stdio(_))
+  }
+
+  fn connect_blocking(&self) -> Result<Connection, Error> {
+    match self {
+      Transport::Stdio(options) => {
+        let LSPProcess {
+          child,
+          stdin,
+          stdout,
+        } = LSPProcess::spawn(options)?;
+
+        Ok(Connection::new(stdin, stdout, Endpoint::Process(child)))
+      }
+      Transport::Tcp(address) => {
+        let stream = connect_tcp(address)?;
+        let reader = stream.try_clone()?;
+        let endpoint = Endpoint::Tcp(stream.try_clone()?);
+
+        Ok(Connection::new(stream, reader, endpoint))
+      }
+      #[cfg(unix)]
+      Transport::Unix(path) => {
+        let stream = UnixStream::connect(path)
+          .map_err(|err| anyhow::anyhow!("failed to connect to {}: {}", path.display(), err))?;
+        let reader = stream.try_clone()?;
+        let endpoint = Endpoint::Unix(stream.try_clone()?);
+
+        Ok(Connection::new(stream, reader, endpoint))
+      }
+    }
+  }
+
+  pub(crate) fn name(&self) -> String {
+    match self {
+      Transport::Stdio(options) => options.path.clone(),
+      Transport::Tcp(address) => address.clone(),
+      #[cfg(unix)]
+      Transport::Unix(path) => path.display().to_string(),
+    }
+  }
+}
+
+fn connect_tcp(address: &str) -> Result<TcpStream, Error> {
+  let mut last_err = None;
+  for addr in address.to_socket_addrs()? {
+    match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+      Ok(stream) => {
+        stream.set_nodelay(true)?;
+        return Ok(stream);
+      }
+      Err(err) => last_err = Some(err),
+    }
+  }
+
+  Err(match last_err {
+    Some(err) => anyhow::anyhow!("failed to connect to {}: {}", address, err),
+    None => anyhow::anyhow!("{} did not resolve to an address", address),
+  })
+}
+
+impl Endpoint {
+  ///Kills the process or closes the socket, returns the exit status of a process
+  pub(crate) fn close(&mut self) -> Result<Option<ExitStatus>, Error> {
+    match self {
+      Endpoint::Process(child) => {
+        //fails if the process already exited which is fine
+        let _ = child.kill();
+        Ok(Some(child.wait()?))
+      }
+      Endpoint::Tcp(stream) => {
+        let _ = stream.shutdown(Shutdown::Both);
+        Ok(None)
+      }
+      #[cfg(unix)]
+      Endpoint::Unix(stream) => {
+        let _ = stream.shutdown(Shutdown::Both);
+        Ok(None)
+      }
+    }
+  }
+
+  ///Sockets have nothing to wait for and are closed right away
+  pub(crate) fn has_exited(&mut self) -> Result<bool, Error> {
+    match self {
+      Endpoint::Process(child) => Ok(child.try_wait()?.is_some()),
+      _ => Ok(true),
+    }
+  }
+}
+
+impl Connection {
+  fn new(
+    writer: impl Write + Send + 'static,
+    reader: impl Read + Send + 'static,
+    endpoint: Endpoint,
+  ) -> Self {
+    Connection {
+      writer: MessageWriter(Box::new(writer)),
+      reader: MessageReader::new(reader),
+      endpoint,
+    }
+  }
+}
+
+impl MessageWriter {
+  ///Writes an already framed message
+  pub(crate) fn send(&mut self, msg: &[u8]) -> Result<(), Error> {
+    self.0.write_all(msg)?;
+    self.0.flush()?;
+    Ok(())
+  }
+}
+
+impl MessageReader {
+  pub(crate) fn new(reader: impl Read + Send + 'static) -> Self {
+    MessageReader(BufReader::new(Box::new(reader)))
+  }
+
+  ///Reads the next message, `None` means the connection was closed
+  pub(crate) fn read_msg(&mut self) -> Result<Option<String>, Error> {
+    let reader = &mut self.0;
+    let mut size = None;
+    let mut buf = String::new();
+    loop {
+      buf.clear();
+      if reader.read_line(&mut buf)? == 0 {
+        return Ok(None);
+      }
+      if !buf.ends_with("\r\n") {
+        return Err(anyhow::anyhow!("Malformed header: {:?}", buf));
+      }
+
+      let buf = &buf[..buf.len() - 2];
+      if buf.is_empty() {
+        break;
+      }
+      let mut parts = buf.splitn(2, ": ");
+      let key = parts.next().unwrap();
+      let value = parts
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Malformed header: {:?}", buf))?;
+
+      if key.eq_ignore_ascii_case("Content-Length") {
+        size = Some(value.parse::<usize>()?);
+      }
+    }
+    let size = size.ok_or_else(|| anyhow::anyhow!("Missing Content-Length header"))?;
+    let mut buf = buf.into_bytes();
+    buf.resize(size, 0);
+    reader.read_exact(&mut buf)?;
+    let buf = String::from_utf8(buf)?;
+
+    Ok(Some(buf))
+  }
+}
+
+impl fmt::Debug for MessageWriter {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("MessageWriter")
+  }
+}
+
+impl fmt::Debug for MessageReader {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("MessageReader")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use super::MessageReader;
+
+  #[test]
+  fn read_msg_splits_frames() {
+    let frames = "Content-Length: 2\r\n\r\n{}content-length: 4\r\nContent-Type: utf-8\r\n\r\nnull";
+    let mut reader = MessageReader::new(Cursor::new(frames.as_bytes().to_vec()));
+
+    assert_eq!(reader.read_msg().unwrap().as_deref(), Some("{}"));
+    assert_eq!(reader.read_msg().unwrap().as_deref(), Some("null"));
+    assert_eq!(reader.read_msg().unwrap(), None);
+  }
+
+  #[test]
+  fn read_msg_rejects_malformed_headers() {
+    let mut reader = MessageReader::new(Cursor::new(b"Content-Length 2\r\n\r\n{}".to_vec()));
+    assert!(reader.read_msg().is_err());
+
+    let mut reader = MessageReader::new(Cursor::new(b"Content-Type: utf-8\r\n\r\n{}".to_vec()));
+    assert!(reader.read_msg().is_err());
+  }
+}
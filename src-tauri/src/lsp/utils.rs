@@ -1,11 +1,15 @@
 use core::fmt;
 
 use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use tauri::Url;
 
-#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub(crate) struct LSPError {
   pub(crate) code: ErrorCode,
   pub(crate) message: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub(crate) data: Option<Value>,
 }
 
 #[derive(Debug, PartialEq, Clone, Copy, Eq)]
@@ -21,6 +25,18 @@ pub enum ErrorCode {
   InvalidParams,
   ///Internal JSON-RPC error.
   InternalError,
+  ///A request was received before the server was initialized.
+  ServerNotInitialized,
+  ///Error code without a definition in the specification.
+  UnknownError,
+  ///A request failed but it was syntactically correct.
+  RequestFailed,
+  ///The server cancelled the request, it may be sent again.
+  ServerCancelled,
+  ///The content of a document got modified outside normal conditions.
+  ContentModified,
+  ///The client cancelled a request and the server has detected the cancel.
+  RequestCancelled,
   ///Reserved for implementation-defined server-errors.
   ServerError(i64),
 }
@@ -66,6 +82,24 @@ impl<'a> Visitor<'a> for VersionVisitor {
   }
 }
 
+impl LSPError {
+  pub(crate) fn new(code: ErrorCode, message: String) -> Self {
+    LSPError {
+      code,
+      message,
+      data: None,
+    }
+  }
+}
+
+impl fmt::Display for LSPError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} ({})", self.message, self.code.code())
+  }
+}
+
+impl std::error::Error for LSPError {}
+
 impl ErrorCode {
   pub const fn from_code(code: i64) -> Self {
     match code {
@@ -74,9 +108,38 @@ impl ErrorCode {
       -32601 => ErrorCode::MethodNotFound,
       -32602 => ErrorCode::InvalidParams,
       -32603 => ErrorCode::InternalError,
+      -32002 => ErrorCode::ServerNotInitialized,
+      -32001 => ErrorCode::UnknownError,
+      -32803 => ErrorCode::RequestFailed,
+      -32802 => ErrorCode::ServerCancelled,
+      -32801 => ErrorCode::ContentModified,
+      -32800 => ErrorCode::RequestCancelled,
       code => ErrorCode::ServerError(code),
     }
   }
+
+  pub const fn code(&self) -> i64 {
+    match *self {
+      ErrorCode::ParseError => -32700,
+      ErrorCode::InvalidRequest => -32600,
+      ErrorCode::MethodNotFound => -32601,
+      ErrorCode::InvalidParams => -32602,
+      ErrorCode::InternalError => -32603,
+      ErrorCode::ServerNotInitialized => -32002,
+      ErrorCode::UnknownError => -32001,
+      ErrorCode::RequestFailed => -32803,
+      ErrorCode::ServerCancelled => -32802,
+      ErrorCode::ContentModified => -32801,
+      ErrorCode::RequestCancelled => -32800,
+      ErrorCode::ServerError(code) => code,
+    }
+  }
+}
+
+impl Serialize for ErrorCode {
+  fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+    ser.serialize_i64(self.code())
+  }
 }
 
 impl<'a> Deserialize<'a> for ErrorCode {
@@ -86,3 +149,20 @@ impl<'a> Deserialize<'a> for ErrorCode {
     Ok(ErrorCode::from_code(code))
   }
 }
+
+///Deserializes a present field into `Some` even if it is `null`, a missing field stays `None`
+pub(crate) fn deserialize_some<'a, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+  T: Deserialize<'a>,
+  D: Deserializer<'a>,
+{
+  T::deserialize(deserializer).map(Some)
+}
+
+///The path for files so the frontend can match it with its open files, the uri for anything else
+pub(crate) fn file_name(uri: &Url) -> String {
+  match uri.to_file_path() {
+    Ok(path) => path.display().to_string(),
+    Err(_) => uri.to_string(),
+  }
+}
@@ -1,16 +1,40 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use std::path::{Path, PathBuf};
+
 use async_std::sync::Mutex;
-use file::{manager::FileManager, parser::ParsersManager};
-use lsp_types::notification::Progress;
+use file::{
+  manager::FileManager,
+  parser::ParsersManager,
+  workspace_edit::{apply_workspace_edit, ApplyAnnotatedWorkspaceEdit},
+};
+use lsp_types::{
+  notification::{LogMessage, LogTrace, Progress, ShowMessage},
+  ApplyWorkspaceEditResponse, CodeActionKind, CompletionItem, DiagnosticSeverity, InlayHint,
+  Position, Range, TextEdit, TraceValue,
+};
+use serde_json::{json, Value};
 
-use tauri::{async_runtime::block_on, Manager, State, Url};
+use tauri::{async_runtime::block_on, AppHandle, Manager, RunEvent, State, Url};
 
-use crate::file::token::TokenTree;
+use crate::file::{folding::FoldRegion, token::TokenTree};
 use crate::lsp::{
-  capabilities::get_capabilities, client::LSPData, manager::LSPManager,
-  notification::LSPNotification,
+  capabilities::get_capabilities,
+  client::LSPData,
+  code_action::CodeActionEntry,
+  code_lens::CodeLensEntry,
+  completion::CompletionEntry,
+  diagnostics::FileDiagnostics,
+  hierarchy::{HierarchyDirection, HierarchyEntry, HierarchyKind},
+  hover::HoverContent,
+  lifecycle::ServerState,
+  manager::LSPManager,
+  navigation::{NavigationKind, NavigationTarget},
+  rename::RenameTarget,
+  signature::SignatureHelpContent,
+  symbol::{OutlineSymbol, WorkspaceSymbolEntry},
+  trace::TrafficEntry,
 };
 
 mod file;
@@ -22,21 +46,29 @@ pub(crate) struct AppState(Mutex<LSPManager>, Mutex<FileManager>, Mutex<ParsersM
 #[tauri::command]
 async fn greet(state: State<'_, AppState>) -> Result<TokenTree, ()> {
   let start = std::time::Instant::now();
+  let lsp = state.0.lock().await;
   let mut res = state.1.lock().await;
-  let ress =
-    res.get_highlighting("c:/Users/benja/Documents/Coding/Apps/CodeForge/src-tauri/src/main.rs");
+  let mut ress = res.get_highlighting(&test_file()).unwrap();
+  res
+    .insert_inlay_hints(&mut ress, &test_file(), &lsp, None)
+    .await;
   println!("Highlighting done in {:?}", start.elapsed());
-  Ok(ress.unwrap())
+  Ok(ress)
 }
 
 #[tauri::command]
-async fn test(state: State<'_, AppState>, file: String) -> Result<TokenTree, ()> {
+async fn test(
+  state: State<'_, AppState>,
+  file: String,
+  viewport: Option<Range>,
+  cursor: Option<Position>,
+) -> Result<TokenTree, ()> {
   let start = std::time::Instant::now();
   let lsp = state.0.lock().await;
   let mut file_manager = state.1.lock().await;
 
   let res = file_manager
-    .get_semantic_highlighting(&file, &lsp)
+    .get_semantic_highlighting(&file, &lsp, viewport, cursor)
     .await
     .unwrap();
 
@@ -45,20 +77,681 @@ async fn test(state: State<'_, AppState>, file: String) -> Result<TokenTree, ()>
   Ok(res)
 }
 
+#[tauri::command]
+async fn lsp_state(state: State<'_, AppState>, file: String) -> Result<Option<ServerState>, ()> {
+  let lsp = state.0.lock().await;
+  let uri = Url::from_file_path(file).map_err(|_| ())?;
+
+  Ok(lsp.get_state(&uri))
+}
+
+#[tauri::command]
+async fn lsp_traffic(
+  state: State<'_, AppState>,
+  file: String,
+) -> Result<Option<Vec<TrafficEntry>>, ()> {
+  let lsp = state.0.lock().await;
+  let uri = Url::from_file_path(file).map_err(|_| ())?;
+
+  Ok(lsp.get_traffic(&uri))
+}
+
+#[tauri::command]
+async fn lsp_set_trace(
+  state: State<'_, AppState>,
+  file: String,
+  value: TraceValue,
+) -> Result<(), String> {
+  let lsp = state.0.lock().await;
+  let uri = Url::from_file_path(file).map_err(|_| "Invalid file path".to_string())?;
+
+  lsp.set_trace(&uri, value).map_err(|err| err.to_string())
+}
+
+///Diagnostics of `file` or every file, optionally only the ones at least as severe as `severity`
+#[tauri::command]
+async fn diagnostics(
+  state: State<'_, AppState>,
+  file: Option<String>,
+  severity: Option<DiagnosticSeverity>,
+  source: Option<String>,
+) -> Result<Vec<FileDiagnostics>, String> {
+  let lsp = state.0.lock().await;
+
+  lsp
+    .get_diagnostics(file.as_deref(), severity, source.as_deref())
+    .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn open_file(state: State<'_, AppState>, file: String) -> Result<(), String> {
+  let lsp = state.0.lock().await;
+  let mut file_manager = state.1.lock().await;
+  let parsers = state.2.lock().await;
+
+  file_manager
+    .open_file(&file, &parsers, &lsp)
+    .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn edit_file(
+  state: State<'_, AppState>,
+  file: String,
+  edits: Vec<TextEdit>,
+) -> Result<(), String> {
+  let lsp = state.0.lock().await;
+  let mut file_manager = state.1.lock().await;
+
+  file_manager
+    .edit_file(&file, edits, &lsp)
+    .map_err(|err| err.to_string())
+}
+
+///Applies the edit and mirrors it into the linked ranges, returns every applied edit
+#[tauri::command]
+async fn edit_linked(
+  state: State<'_, AppState>,
+  file: String,
+  edit: TextEdit,
+) -> Result<Vec<TextEdit>, String> {
+  let lsp = state.0.lock().await;
+  let mut file_manager = state.1.lock().await;
+
+  file_manager
+    .edit_linked(&file, edit, &lsp)
+    .await
+    .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn save_file(state: State<'_, AppState>, file: String) -> Result<(), String> {
+  let lsp = state.0.lock().await;
+  let file_manager = state.1.lock().await;
+
+  file_manager
+    .save_file(&file, &lsp)
+    .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn close_file(state: State<'_, AppState>, file: String) -> Result<(), String> {
+  let lsp = state.0.lock().await;
+  let mut file_manager = state.1.lock().await;
+
+  file_manager
+    .close_file(&file, &lsp)
+    .map_err(|err| err.to_string())
+}
+
+///Reverts the last change of the file, false if there was nothing to undo
+#[tauri::command]
+async fn undo_file(state: State<'_, AppState>, file: String) -> Result<bool, String> {
+  let lsp = state.0.lock().await;
+  let mut file_manager = state.1.lock().await;
+
+  file_manager
+    .undo_file(&file, &lsp)
+    .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn redo_file(state: State<'_, AppState>, file: String) -> Result<bool, String> {
+  let lsp = state.0.lock().await;
+  let mut file_manager = state.1.lock().await;
+
+  file_manager
+    .redo_file(&file, &lsp)
+    .map_err(|err| err.to_string())
+}
+
+///Regions of the file that can be folded, folded ones stay folded across edits and restarts
+#[tauri::command]
+async fn folding_ranges(
+  state: State<'_, AppState>,
+  file: String,
+) -> Result<Vec<FoldRegion>, String> {
+  let lsp = state.0.lock().await;
+  let mut file_manager = state.1.lock().await;
+  let parsers = state.2.lock().await;
+
+  file_manager
+    .folding_ranges(&file, &parsers, &lsp)
+    .await
+    .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn set_folded(
+  state: State<'_, AppState>,
+  file: String,
+  line: u32,
+  collapsed: bool,
+) -> Result<(), String> {
+  let mut file_manager = state.1.lock().await;
+
+  file_manager
+    .set_folded(&file, line, collapsed)
+    .map_err(|err| err.to_string())
+}
+
+///Formats the file, or only `range` of it, as one change that can be undone
+#[tauri::command]
+async fn format_file(
+  state: State<'_, AppState>,
+  file: String,
+  range: Option<Range>,
+) -> Result<(), String> {
+  let lsp = state.0.lock().await;
+  let mut file_manager = state.1.lock().await;
+
+  let indentation = file_manager
+    .get_file(&file)
+    .ok_or(format!("File {} is not open", file))?
+    .indentation();
+  let edits = lsp
+    .format(&file, range, indentation.into())
+    .await
+    .map_err(|err| err.to_string())?;
+  if edits.is_empty() {
+    return Ok(());
+  }
+  file_manager
+    .edit_file(&file, edits, &lsp)
+    .map_err(|err| err.to_string())
+}
+
+///Formats the code at `position` after `typed` was typed there, as one change that can be undone
+#[tauri::command]
+async fn format_on_type(
+  state: State<'_, AppState>,
+  file: String,
+  position: Position,
+  typed: String,
+) -> Result<(), String> {
+  let lsp = state.0.lock().await;
+  let mut file_manager = state.1.lock().await;
+
+  let indentation = file_manager
+    .get_file(&file)
+    .ok_or(format!("File {} is not open", file))?
+    .indentation();
+  let edits = lsp
+    .format_on_type(&file, position, &typed, indentation.into())
+    .await
+    .map_err(|err| err.to_string())?;
+  if edits.is_empty() {
+    return Ok(());
+  }
+  file_manager
+    .edit_file(&file, edits, &lsp)
+    .map_err(|err| err.to_string())
+}
+
+///Completions at `position`, `trigger` is the character that was just typed
+#[tauri::command]
+async fn complete(
+  state: State<'_, AppState>,
+  file: String,
+  position: Position,
+  trigger: Option<String>,
+) -> Result<Vec<CompletionEntry>, String> {
+  let mut lsp = state.0.lock().await;
+  let file_manager = state.1.lock().await;
+
+  let line = file_manager
+    .get_file(&file)
+    .and_then(|open_file| open_file.line(position.line))
+    .ok_or(format!("Invalid position {:?} in {}", position, file))?;
+  lsp
+    .complete(&file, position, trigger.as_deref(), line)
+    .await
+    .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn resolve_completion(
+  state: State<'_, AppState>,
+  file: String,
+  id: usize,
+) -> Result<CompletionItem, String> {
+  let mut lsp = state.0.lock().await;
+
+  lsp
+    .resolve_completion(&file, id)
+    .await
+    .map_err(|err| err.to_string())
+}
+
+///Inserts the item and returns the ranges of its tabstops in the order they are visited
+#[tauri::command]
+async fn accept_completion(
+  state: State<'_, AppState>,
+  file: String,
+  id: usize,
+  position: Position,
+) -> Result<Vec<Vec<Range>>, String> {
+  let mut lsp = state.0.lock().await;
+  let mut file_manager = state.1.lock().await;
+
+  let accepted = lsp
+    .accept_completion(&file, id, position)
+    .await
+    .map_err(|err| err.to_string())?;
+  file_manager
+    .edit_file(&file, accepted.edits, &lsp)
+    .map_err(|err| err.to_string())?;
+  Ok(accepted.tabstops)
+}
+
+///Hover of `position` with its markdown rendered as html
+#[tauri::command]
+async fn hover(
+  state: State<'_, AppState>,
+  file: String,
+  position: Position,
+) -> Result<Option<HoverContent>, String> {
+  let lsp = state.0.lock().await;
+  let parsers = state.2.lock().await;
+
+  lsp
+    .hover(&file, position, &parsers)
+    .await
+    .map_err(|err| err.to_string())
+}
+
+///Inlay hint shown at `position` with its tooltips and label locations
+#[tauri::command]
+async fn resolve_inlay_hint(
+  state: State<'_, AppState>,
+  file: String,
+  position: Position,
+) -> Result<Option<InlayHint>, String> {
+  let lsp = state.0.lock().await;
+
+  lsp
+    .resolve_inlay_hint(&file, position)
+    .await
+    .map_err(|err| err.to_string())
+}
+
+///Signatures of the call at `position`, `trigger` is the character that was just typed
+#[tauri::command]
+async fn signature_help(
+  state: State<'_, AppState>,
+  file: String,
+  position: Position,
+  trigger: Option<String>,
+) -> Result<Option<SignatureHelpContent>, String> {
+  let lsp = state.0.lock().await;
+  let parsers = state.2.lock().await;
+
+  lsp
+    .signature_help(&file, position, trigger, &parsers)
+    .await
+    .map_err(|err| err.to_string())
+}
+
+///Targets of the symbol at `position`. The editor jumps to the first target unless references
+///are listed, only that one is opened so it can be shown right away.
+#[tauri::command]
+async fn navigate(
+  state: State<'_, AppState>,
+  file: String,
+  position: Position,
+  kind: NavigationKind,
+) -> Result<Vec<NavigationTarget>, String> {
+  let lsp = state.0.lock().await;
+  let mut file_manager = state.1.lock().await;
+  let parsers = state.2.lock().await;
+
+  let targets = lsp
+    .navigate(&file, position, kind)
+    .await
+    .map_err(|err| err.to_string())?;
+  let jump = targets
+    .first()
+    .filter(|target| kind != NavigationKind::References && target.is_file());
+  if let Some(target) = jump {
+    if let Err(err) = file_manager.open_file(&target.file, &parsers, &lsp) {
+      eprintln!("Error opening {}: {}", target.file, err);
+    }
+  }
+
+  Ok(targets)
+}
+
+///Outline of the file, only the symbols matching `query` and their parents if it is set
+#[tauri::command]
+async fn outline(
+  state: State<'_, AppState>,
+  file: String,
+  query: Option<String>,
+) -> Result<Vec<OutlineSymbol>, String> {
+  let lsp = state.0.lock().await;
+
+  lsp
+    .outline(&file, query.as_deref())
+    .await
+    .map_err(|err| err.to_string())
+}
+
+///Symbols of the whole workspace matching `query`, the best matches first
+#[tauri::command]
+async fn workspace_symbols(
+  state: State<'_, AppState>,
+  query: String,
+) -> Result<Vec<WorkspaceSymbolEntry>, String> {
+  let mut lsp = state.0.lock().await;
+
+  lsp
+    .workspace_symbols(&query)
+    .await
+    .map_err(|err| err.to_string())
+}
+
+///Location of a symbol found by `workspace_symbols`, its file is opened so it can be shown
+#[tauri::command]
+async fn goto_workspace_symbol(
+  state: State<'_, AppState>,
+  id: usize,
+) -> Result<NavigationTarget, String> {
+  let mut lsp = state.0.lock().await;
+  let mut file_manager = state.1.lock().await;
+  let parsers = state.2.lock().await;
+
+  let target = lsp
+    .resolve_workspace_symbol(id)
+    .await
+    .map_err(|err| err.to_string())?;
+  if target.is_file() {
+    file_manager
+      .open_file(&target.file, &parsers, &lsp)
+      .map_err(|err| err.to_string())?;
+  }
+
+  Ok(target)
+}
+
+///Roots of a call or type hierarchy of the symbol at `position`
+#[tauri::command]
+async fn prepare_hierarchy(
+  state: State<'_, AppState>,
+  file: String,
+  position: Position,
+  kind: HierarchyKind,
+) -> Result<Vec<HierarchyEntry>, String> {
+  let mut lsp = state.0.lock().await;
+
+  lsp
+    .prepare_hierarchy(&file, position, kind)
+    .await
+    .map_err(|err| err.to_string())
+}
+
+///Children of a hierarchy item, like its callers or its supertypes
+#[tauri::command]
+async fn expand_hierarchy(
+  state: State<'_, AppState>,
+  id: usize,
+  direction: HierarchyDirection,
+) -> Result<Vec<HierarchyEntry>, String> {
+  let mut lsp = state.0.lock().await;
+
+  lsp
+    .expand_hierarchy(id, direction)
+    .await
+    .map_err(|err| err.to_string())
+}
+
+///What a rename at `position` replaces, `None` if nothing can be renamed there
+#[tauri::command]
+async fn prepare_rename(
+  state: State<'_, AppState>,
+  file: String,
+  position: Position,
+) -> Result<Option<RenameTarget>, String> {
+  let lsp = state.0.lock().await;
+  let file_manager = state.1.lock().await;
+
+  let line = file_manager
+    .get_file(&file)
+    .and_then(|open_file| open_file.line(position.line))
+    .ok_or(format!("Invalid position {:?} in {}", position, file))?;
+  lsp
+    .prepare_rename(&file, position, line)
+    .await
+    .map_err(|err| err.to_string())
+}
+
+///Renames the symbol at `position` everywhere and returns the changed files. Edits the server
+///wants confirmed fail until the rename is repeated with `confirmed` set.
+#[tauri::command]
+async fn rename(
+  state: State<'_, AppState>,
+  file: String,
+  position: Position,
+  new_name: String,
+  confirmed: bool,
+) -> Result<Vec<String>, String> {
+  let lsp = state.0.lock().await;
+  let mut file_manager = state.1.lock().await;
+  let parsers = state.2.lock().await;
+
+  let edit = lsp
+    .rename(&file, position, new_name)
+    .await
+    .map_err(|err| err.to_string())?;
+  let Some(edit) = edit else {
+    return Ok(Vec::new());
+  };
+  apply_workspace_edit(edit, confirmed, &mut file_manager, &parsers, &lsp)
+    .map_err(|failure| failure.to_string())
+}
+
+///Code actions for `range`, `only` keeps the given kinds like `quickfix` or `refactor.extract`
+#[tauri::command]
+async fn code_actions(
+  state: State<'_, AppState>,
+  file: String,
+  range: Range,
+  only: Option<Vec<CodeActionKind>>,
+) -> Result<Vec<CodeActionEntry>, String> {
+  let mut lsp = state.0.lock().await;
+
+  lsp
+    .code_actions(&file, range, only)
+    .await
+    .map_err(|err| err.to_string())
+}
+
+///Applies the edit of the action and runs its command, returns the files changed by the edit.
+///Edits the server applies while running the command are handled by `apply_server_edits`.
+#[tauri::command]
+async fn apply_code_action(
+  state: State<'_, AppState>,
+  file: String,
+  id: usize,
+  confirmed: bool,
+) -> Result<Vec<String>, String> {
+  let mut lsp = state.0.lock().await;
+  let mut file_manager = state.1.lock().await;
+  let parsers = state.2.lock().await;
+
+  let action = lsp
+    .resolve_code_action(&file, id)
+    .await
+    .map_err(|err| err.to_string())?;
+  let changed = match action.edit {
+    Some(edit) => apply_workspace_edit(edit, confirmed, &mut file_manager, &parsers, &lsp)
+      .map_err(|failure| failure.to_string())?,
+    None => Vec::new(),
+  };
+  let Some(command) = action.action.command else {
+    return Ok(changed);
+  };
+
+  let execution = lsp
+    .execute_command(&file, command)
+    .map_err(|err| err.to_string())?;
+  //the server applies the edits of the command through the state
+  drop(parsers);
+  drop(file_manager);
+  drop(lsp);
+  execution.await.map_err(|err| err.to_string())?;
+  Ok(changed)
+}
+
+#[tauri::command]
+async fn code_lenses(
+  state: State<'_, AppState>,
+  file: String,
+) -> Result<Vec<CodeLensEntry>, String> {
+  let lsp = state.0.lock().await;
+
+  lsp.code_lenses(&file).await.map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+async fn resolve_code_lens(
+  state: State<'_, AppState>,
+  file: String,
+  id: usize,
+) -> Result<CodeLensEntry, String> {
+  let lsp = state.0.lock().await;
+
+  lsp
+    .resolve_code_lens(&file, id)
+    .await
+    .map_err(|err| err.to_string())
+}
+
+///Runs the command of the lens, in the editor for client commands or else on the server
+#[tauri::command]
+async fn run_code_lens(
+  state: State<'_, AppState>,
+  file: String,
+  id: usize,
+) -> Result<Option<Value>, String> {
+  let lsp = state.0.lock().await;
+
+  let lens = lsp
+    .resolve_code_lens(&file, id)
+    .await
+    .map_err(|err| err.to_string())?;
+  let Some(command) = lens.command else {
+    return Ok(None);
+  };
+
+  let execution = lsp
+    .execute_command(&file, command)
+    .map_err(|err| err.to_string())?;
+  //the server applies the edits of the command through the state
+  drop(lsp);
+  execution.await.map_err(|err| err.to_string())
+}
+
+///Client commands are emitted as `lsp-client-command` event for the frontend to run them
+fn register_client_commands(lsp: &LSPManager, app: AppHandle) {
+  for command in CLIENT_COMMANDS {
+    let app = app.clone();
+    lsp.register_command(command, move |command| {
+      app.emit(CLIENT_COMMAND_EVENT, command)?;
+      Ok(None)
+    });
+  }
+}
+
+///Applies the workspace edits the server asks for, edits that need a confirmation are refused
+fn apply_server_edits(lsp: &LSPData, app: AppHandle) {
+  lsp.on_request::<ApplyAnnotatedWorkspaceEdit, _, _>(move |params| {
+    let app = app.clone();
+    async move {
+      let state: State<'_, AppState> = app.state();
+      let lsp = state.0.lock().await;
+      let mut file_manager = state.1.lock().await;
+      let parsers = state.2.lock().await;
+
+      let applied = apply_workspace_edit(params.edit, false, &mut file_manager, &parsers, &lsp);
+      Ok(match applied {
+        Ok(_) => ApplyWorkspaceEditResponse {
+          applied: true,
+          failure_reason: None,
+          failed_change: None,
+        },
+        Err(failure) => failure.into(),
+      })
+    }
+  });
+}
+
 fn main() {
   tauri::Builder::default()
-    .invoke_handler(tauri::generate_handler![greet, test])
+    .invoke_handler(tauri::generate_handler![
+      greet,
+      test,
+      lsp_state,
+      lsp_traffic,
+      lsp_set_trace,
+      diagnostics,
+      open_file,
+      edit_file,
+      edit_linked,
+      save_file,
+      close_file,
+      undo_file,
+      redo_file,
+      folding_ranges,
+      set_folded,
+      format_file,
+      format_on_type,
+      complete,
+      resolve_completion,
+      accept_completion,
+      hover,
+      resolve_inlay_hint,
+      signature_help,
+      navigate,
+      outline,
+      workspace_symbols,
+      goto_workspace_symbol,
+      prepare_hierarchy,
+      expand_hierarchy,
+      prepare_rename,
+      rename,
+      code_actions,
+      apply_code_action,
+      code_lenses,
+      resolve_code_lens,
+      run_code_lens
+    ])
     .manage(AppState(
       Mutex::new(LSPManager::new()),
       Mutex::new(FileManager::new()),
       Mutex::new(ParsersManager::new()),
     ))
     .setup(|app| {
-      let lsp = block_on(test_lsp());
+      let lsp = block_on(test_lsp(app.path().app_log_dir()?));
+      let fold_file = app.path().app_data_dir()?.join("folds.json");
       let state: State<'_, AppState> = app.state();
 
+      apply_server_edits(&lsp, app.handle().clone());
+
       block_on(async {
-        state.0.lock().await.add_lsp(lsp);
+        let mut lsp_manager = state.0.lock().await;
+        lsp_manager.add_lsp(lsp);
+        lsp_manager.forward::<Progress>(app.handle().clone(), "lsp-progress");
+        lsp_manager.forward::<ShowMessage>(app.handle().clone(), "lsp-show-message");
+        lsp_manager.forward::<LogMessage>(app.handle().clone(), "lsp-log-message");
+        lsp_manager.forward::<LogTrace>(app.handle().clone(), "lsp-log-trace");
+        lsp_manager.track_diagnostics();
+        lsp_manager.emit_diagnostics(app.handle().clone());
+        lsp_manager.emit_inlay_hint_refresh(app.handle().clone());
+        lsp_manager.emit_semantic_tokens_refresh(app.handle().clone());
+        lsp_manager.emit_code_lens_refresh(app.handle().clone());
+        register_client_commands(&lsp_manager, app.handle().clone());
+        drop(lsp_manager);
+
         state
           .2
           .lock()
@@ -68,38 +761,69 @@ fn main() {
             tree_sitter_configs::HIGHLIGHTS_QUERY,
             tree_sitter_configs::INJECTIONS_QUERY,
             tree_sitter_configs::TAGS_QUERY,
+            include_str!("../queries/rust/folds.scm"),
             "^.+\\.rs$".to_string(),
           )
           .unwrap();
-        state
-          .1
-          .lock()
-          .await
-          .open_file(
-            "c:/Users/benja/Documents/Coding/Apps/CodeForge/src-tauri/src/main.rs",
-            &*state.2.lock().await,
-          )
+        let mut file_manager = state.1.lock().await;
+        file_manager.load_folds(fold_file);
+        file_manager
+          .open_file(&test_file(), &*state.2.lock().await, &*state.0.lock().await)
           .unwrap();
       });
 
       Ok(())
     })
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+    .build(tauri::generate_context!())
+    .expect("error while building tauri application")
+    .run(|app, event| {
+      if let RunEvent::Exit = event {
+        let state: State<'_, AppState> = app.state();
+        block_on(async { state.0.lock().await.shutdown().await });
+      }
+    });
 }
 
-fn not_handler(not: lsp::notification::RawLSPNotification) {
-  #[allow(clippy::single_match)]
-  match not.method.as_str() {
-    "$/progress" => {
-      let params: LSPNotification<Progress> = not.parse().unwrap();
-      //println!("{:?}", params);
-    }
-    _ => {}
+fn test_file() -> String {
+  let current_dir = std::env::current_dir().expect("failed to get current directory");
+  let path = current_dir.join("src").join("main.rs");
+  path.display().to_string()
+}
+
+const CLIENT_COMMAND_EVENT: &str = "lsp-client-command";
+
+///Commands of rust-analyzer's code lenses, it only sends lenses with commands the client has
+const CLIENT_COMMANDS: [&str; 4] = [
+  "rust-analyzer.runSingle",
+  "rust-analyzer.debugSingle",
+  "rust-analyzer.showReferences",
+  "rust-analyzer.gotoLocation",
+];
+
+#[cfg(all(target_os = "windows", target_arch = "x86_64"))]
+const RUST_ANALYZER: &str = "rust-analyzer-x86_64-pc-windows-msvc.exe";
+#[cfg(all(target_os = "windows", target_arch = "aarch64"))]
+const RUST_ANALYZER: &str = "rust-analyzer-aarch64-pc-windows-msvc.exe";
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+const RUST_ANALYZER: &str = "rust-analyzer-x86_64-unknown-linux-gnu";
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+const RUST_ANALYZER: &str = "rust-analyzer-aarch64-unknown-linux-gnu";
+#[cfg(all(target_os = "macos", target_arch = "x86_64"))]
+const RUST_ANALYZER: &str = "rust-analyzer-x86_64-apple-darwin";
+#[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+const RUST_ANALYZER: &str = "rust-analyzer-aarch64-apple-darwin";
+
+///Prefers the bundled binary in `test/` and falls back to the one on the PATH
+fn rust_analyzer_path(current_dir: &Path) -> String {
+  let bundled = current_dir.join("test").join(RUST_ANALYZER);
+  if bundled.is_file() {
+    bundled.display().to_string()
+  } else {
+    format!("rust-analyzer{}", std::env::consts::EXE_SUFFIX)
   }
 }
 
-async fn test_lsp() -> LSPData {
+async fn test_lsp(log_dir: PathBuf) -> LSPData {
   let current_dir = std::env::current_dir().expect("failed to get current directory");
 
   let work_dir = vec![lsp_types::WorkspaceFolder {
@@ -111,16 +835,25 @@ async fn test_lsp() -> LSPData {
       .to_string(),
   }];
 
+  let mut capabilities = get_capabilities();
+  capabilities.experimental = Some(json!({ "commands": { "commands": CLIENT_COMMANDS } }));
+
   LSPData::create()
-    .path(format!(
-      "{}/test/rust-analyzer-x86_64-pc-windows-msvc.exe",
-      current_dir.display()
-    ))
+    .path(rust_analyzer_path(&current_dir))
     .args(Vec::new())
+    .cwd(current_dir.clone())
+    .env(vec![("RA_LOG".to_string(), "error".to_string())])
+    .stderr_log(log_dir.join("rust-analyzer.log"))
+    .language_id("rust".to_string())
     .file_patterns(vec!["^.+\\.rs$".to_string()])
     .workspace_folders(work_dir)
-    .capabilities(get_capabilities())
-    .not_handler(not_handler)
+    .capabilities(capabilities)
+    .settings(json!({
+      "rust-analyzer": {
+        "cargo": { "buildScripts": { "enable": true } },
+        "procMacro": { "enable": true },
+      }
+    }))
     .build()
     .await
     .expect("failed to build lsp client")